@@ -0,0 +1,35 @@
+// Reports the active tab of the focused browser window to Diaroo. Must match
+// the extension port in Diaroo's settings.
+const PORT = 47613;
+const ENDPOINT = `http://127.0.0.1:${PORT}/tab`;
+
+async function send(url, title) {
+  try {
+    await fetch(ENDPOINT, {
+      method: "POST",
+      headers: { "Content-Type": "application/json", "X-Diaroo-Extension": "1" },
+      body: JSON.stringify({ url, title }),
+    });
+  } catch {
+    // Diaroo is not running or not monitoring
+  }
+}
+
+async function reportActiveTab() {
+  const win = await chrome.windows.getLastFocused({ populate: true });
+  const tab = win.focused ? win.tabs?.find((t) => t.active) : undefined;
+  // An empty URL tells Diaroo that no browser window has focus
+  await send(tab?.url ?? "", tab?.title ?? "");
+}
+
+chrome.tabs.onActivated.addListener(reportActiveTab);
+chrome.tabs.onUpdated.addListener((_tabId, change, tab) => {
+  if (tab.active && (change.url || change.title)) {
+    reportActiveTab();
+  }
+});
+chrome.windows.onFocusChanged.addListener(reportActiveTab);
+
+// Diaroo may start after the browser; report again every minute
+chrome.alarms.create("report", { periodInMinutes: 1 });
+chrome.alarms.onAlarm.addListener(reportActiveTab);
@@ -0,0 +1,15 @@
+use crate::storage::audit_log::{self, AuditEntry};
+use crate::AppState;
+use tauri::State;
+
+/// Recorded LLM requests, newest first.
+#[tauri::command]
+pub fn list_llm_audit(
+    state: State<'_, AppState>,
+    limit: Option<u32>,
+    offset: Option<u32>,
+) -> Result<Vec<AuditEntry>, String> {
+    let data_dir = state.config.lock().unwrap().data_path();
+    audit_log::list(&data_dir, limit.unwrap_or(100), offset.unwrap_or(0))
+        .map_err(|e| e.to_string())
+}
@@ -1,4 +1,10 @@
+use crate::services::digest_generator::DigestGenerator;
+use crate::services::llm_client::LlmClient;
+use crate::services::redaction;
+use crate::storage::audit_log::{self, AuditRecord, AuditSettings};
+use crate::storage::config::LlmStep;
 use crate::AppState;
+use chrono::Local;
 use tauri::{Emitter, State};
 use tokio::io::{AsyncBufReadExt, BufReader};
 use tokio::process::Command;
@@ -14,6 +20,34 @@ pub async fn run_claude(
     std::fs::create_dir_all(&data_dir)
         .map_err(|e| format!("Failed to prepare data directory: {}", e))?;
 
+    // Q&A keeps using the claude CLI unless a Q&A model is configured
+    if !config.qa_llm.is_empty() && config.llm_settings(LlmStep::Qa).llm_provider != "claude-code" {
+        let llm = LlmClient::for_step(&config, LlmStep::Qa);
+        // The CLI reads the data folder itself; other providers get today's data in the prompt
+        let today = Local::now().format("%Y-%m-%d").to_string();
+        let (qa_prompt, mapping) =
+            DigestGenerator::qa_prompt(&state.activity_log, &config, &today, &prompt)
+                .map_err(|e| format!("Failed to build Q&A context: {}", e))?;
+        let handle = app_handle.clone();
+        tokio::spawn(async move {
+            let text = match llm.send_multimodal(&qa_prompt, &[]).await {
+                Ok(text) => redaction::restore(&text, &mapping),
+                Err(e) => format!("Error: {}", e),
+            };
+            for line in text.lines() {
+                let _ = handle.emit(
+                    "claude-output",
+                    serde_json::json!({ "text": line, "done": false }),
+                );
+            }
+            let _ = handle.emit(
+                "claude-output",
+                serde_json::json!({ "text": "", "done": true }),
+            );
+        });
+        return Ok(());
+    }
+
     let mut cmd = Command::new("claude");
     crate::services::shell_path::apply_shell_path(&mut cmd);
     let mut child = cmd
@@ -29,9 +63,12 @@ pub async fn run_claude(
     let stdout = child.stdout.take().ok_or("No stdout")?;
     let mut reader = BufReader::new(stdout).lines();
 
+    let audit = AuditSettings::from_config(&config);
     let handle = app_handle.clone();
     tokio::spawn(async move {
+        let mut response_bytes = 0;
         while let Ok(Some(line)) = reader.next_line().await {
+            response_bytes += line.len() + 1;
             let _ = handle.emit(
                 "claude-output",
                 serde_json::json!({ "text": line, "done": false }),
@@ -41,6 +78,22 @@ pub async fn run_claude(
             "claude-output",
             serde_json::json!({ "text": "", "done": true }),
         );
+
+        if let Some(audit) = audit {
+            let record = AuditRecord {
+                provider: "claude-code",
+                endpoint: "",
+                model: "",
+                prompt: &prompt,
+                images: &[],
+                response: Ok(response_bytes),
+                activity_ids: &[],
+                secrets: &[],
+            };
+            if let Err(e) = audit_log::record(&audit, record) {
+                log::warn!("Failed to write LLM audit entry: {}", e);
+            }
+        }
     });
 
     Ok(())
@@ -1,5 +1,6 @@
 use crate::services::auto_report::AutoReportScheduler;
 use crate::services::scheduled_monitoring::ScheduledMonitoringScheduler;
+use crate::services::scheduler::Scheduler;
 use crate::storage::config::AppConfig;
 use crate::AppState;
 use tauri::{Emitter, State};
@@ -18,6 +19,20 @@ pub async fn set_config(
 ) -> Result<(), String> {
     // Trim whitespace from API key (common copy-paste issue)
     config.api_key = config.api_key.trim().to_string();
+    for step in [&mut config.extraction_llm, &mut config.digest_llm, &mut config.qa_llm]
+        .into_iter()
+        .chain(config.llm_fallbacks.iter_mut())
+    {
+        step.api_key = step.api_key.trim().to_string();
+    }
+    for profile in &mut config.digest_profiles {
+        profile.api_key = profile.api_key.trim().to_string();
+    }
+
+    config.check_llm_overrides().map_err(|e| e.to_string())?;
+
+    // Tools the capture needs must be present, or every screenshot would fail
+    Scheduler::check_capture(&config).await.map_err(|e| e.to_string())?;
 
     let old_pet_name = {
         let old = state.config.lock().unwrap();
@@ -74,7 +89,7 @@ fn restart_auto_report(app_handle: &tauri::AppHandle, state: &State<'_, AppState
     }
 
     let config = state.config.lock().unwrap().clone();
-    if config.auto_report_enabled {
+    if !config.scheduled_profiles().is_empty() {
         let (tx, rx) = tokio::sync::watch::channel(false);
         *state.auto_report_stop_tx.lock().unwrap() = Some(tx);
         AutoReportScheduler::start(
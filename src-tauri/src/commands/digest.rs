@@ -2,7 +2,9 @@ use chrono::Local;
 use tauri_plugin_notification::NotificationExt;
 
 use crate::services::digest_generator::DigestGenerator;
+use crate::services::jobs::{Cancelled, JobKind};
 use crate::services::llm_client::LlmClient;
+use crate::storage::config::LlmStep;
 use crate::storage::screenshot_store::ScreenshotStore;
 use crate::AppState;
 use tauri::State;
@@ -12,27 +14,46 @@ pub async fn generate_digest(
     state: State<'_, AppState>,
     app_handle: tauri::AppHandle,
     date: Option<String>,
+    profile: Option<String>,
 ) -> Result<String, String> {
     let config = state.config.lock().unwrap().clone();
+    let profile = config
+        .digest_profile(profile.as_deref())
+        .map_err(|e| e.to_string())?;
     let activity_log = state.activity_log.clone();
     let store = ScreenshotStore::new(config.data_path());
-    let llm = LlmClient::new(
-        &config.llm_provider,
-        &config.api_key,
-        &config.model,
-        &config.api_endpoint,
-        Some(config.data_path()),
-    );
-
     let target_date = date.unwrap_or_else(|| Local::now().format("%Y-%m-%d").to_string());
 
-    let report_path =
-        DigestGenerator::generate_digest_for_date(&activity_log, &store, &llm, &target_date)
-            .await
-            .map_err(|e| {
-                log::error!("Failed to generate digest for {}: {:?}", target_date, e);
-                e.to_string()
-            })?;
+    let job = state.jobs.start(JobKind::Digest, &target_date);
+    crate::refresh_tray_menu(&app_handle);
+    let llm = LlmClient::for_step(&config, LlmStep::Extraction).with_cancel(job.token().clone());
+
+    let result = DigestGenerator::generate_digest_for_date(
+        &activity_log,
+        &store,
+        &llm,
+        &config,
+        &target_date,
+        &profile,
+        job.token(),
+    )
+    .await;
+    drop(job);
+    crate::refresh_tray_menu(&app_handle);
+
+    let report_path = result.map_err(|e| {
+        if e.is::<Cancelled>() {
+            log::info!("Digest generation for {} cancelled", target_date);
+        } else {
+            log::error!("Failed to generate digest for {}: {:?}", target_date, e);
+        }
+        e.to_string()
+    })?;
+
+    // Extra profiles are side reports; only the default report ends the work day
+    if !profile.is_default() {
+        return Ok(report_path.to_string_lossy().to_string());
+    }
 
     // Stop monitoring — report marks end of work
     let was_monitoring = {
@@ -0,0 +1,32 @@
+use crate::services::timesheet::{self, TimesheetFormat};
+use crate::AppState;
+use tauri::State;
+
+/// Export work blocks for `start_date..=end_date` to `path` as csv, ics, toggl or clockify.
+/// Returns the number of blocks written.
+#[tauri::command]
+pub fn export_timesheet(
+    state: State<'_, AppState>,
+    start_date: String,
+    end_date: String,
+    format: String,
+    path: String,
+) -> Result<usize, String> {
+    let format = TimesheetFormat::parse(&format).map_err(|e| e.to_string())?;
+    let projects = state.config.lock().unwrap().projects.clone();
+
+    let blocks =
+        timesheet::build_work_blocks(&state.activity_log, &projects, &start_date, &end_date)
+            .map_err(|e| e.to_string())?;
+    let contents = timesheet::render(&blocks, format).map_err(|e| e.to_string())?;
+    std::fs::write(&path, contents).map_err(|e| e.to_string())?;
+
+    log::info!(
+        "Exported {} work blocks ({} to {}) to {}",
+        blocks.len(),
+        start_date,
+        end_date,
+        path
+    );
+    Ok(blocks.len())
+}
@@ -0,0 +1,17 @@
+use crate::services::jobs::JobInfo;
+use crate::AppState;
+use tauri::State;
+
+#[tauri::command]
+pub fn list_jobs(state: State<'_, AppState>) -> Result<Vec<JobInfo>, String> {
+    Ok(state.jobs.list())
+}
+
+#[tauri::command]
+pub fn cancel_job(state: State<'_, AppState>, id: String) -> Result<(), String> {
+    if state.jobs.cancel(&id) {
+        Ok(())
+    } else {
+        Err(format!("No running job with id {}", id))
+    }
+}
@@ -1,6 +1,10 @@
+pub mod audit;
 pub mod claude;
 pub mod config;
 pub mod digest;
+pub mod export;
+pub mod jobs;
 pub mod monitor;
 pub mod pets;
+pub mod prompts;
 pub mod reports;
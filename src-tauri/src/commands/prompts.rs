@@ -0,0 +1,160 @@
+use chrono::Local;
+use serde::Serialize;
+
+use crate::services::commits;
+use crate::services::digest_generator::{
+    DigestGenerator, DEFAULT_DIGEST_PROMPT, DEFAULT_EXTRACT_PROMPT, SCREEN_TEXT_ONLY_NOTE,
+    TEXT_EXTRACT_PROMPT,
+};
+use crate::services::extraction::STRUCTURED_OUTPUT_INSTRUCTIONS;
+use crate::services::prompt_template::{self, PromptTemplate, TemplateKind};
+use crate::services::redaction::Redactor;
+use crate::services::summarization;
+use crate::storage::config::{AppConfig, LlmStep};
+use crate::AppState;
+use tauri::State;
+use tauri_plugin_opener::OpenerExt;
+
+#[derive(Serialize)]
+pub struct PromptPreview {
+    pub template: String,
+    pub text: String,
+    pub errors: Vec<String>,
+    pub warnings: Vec<String>,
+}
+
+#[tauri::command]
+pub fn list_prompt_templates() -> Vec<PromptTemplate> {
+    prompt_template::list_templates(DEFAULT_EXTRACT_PROMPT, DEFAULT_DIGEST_PROMPT)
+}
+
+/// Render the exact prompt that would be sent for `kind` ("extract" or "digest"),
+/// using `template` (or the configured one) and the data recorded for `date`.
+/// Extract previews use the next chunk of unbatched screenshots, or the last
+/// chunk of the day when everything has been processed.
+#[tauri::command]
+pub async fn render_prompt_preview(
+    state: State<'_, AppState>,
+    kind: String,
+    template: Option<String>,
+    date: Option<String>,
+) -> Result<PromptPreview, String> {
+    let config = state.config.lock().unwrap().clone();
+    let kind = TemplateKind::parse(&kind).map_err(|e| e.to_string())?;
+    let date = date.unwrap_or_else(|| Local::now().format("%Y-%m-%d").to_string());
+    let activity_log = state.activity_log.clone();
+
+    if !activity_log.date_exists(&date) {
+        return Err(format!("No activity recorded for {}", date));
+    }
+
+    let structured = config.extraction_mode == "structured";
+    // The CLIs cannot see screenshots and get the text-only extract prompt
+    let extract_provider = config.llm_settings(LlmStep::Extraction).llm_provider;
+    let text_only = matches!(extract_provider.as_str(), "claude-code" | "codex");
+    let (configured, default_body, vars) = match kind {
+        TemplateKind::Extract => {
+            let today = Local::now().format("%Y-%m-%d").to_string();
+            let unbatched = if date == today {
+                activity_log.get_unbatched_entries().map_err(|e| e.to_string())?
+            } else {
+                Vec::new()
+            };
+            let chunk_size = DigestGenerator::chunk_size(&config);
+            let entries = if unbatched.is_empty() {
+                let all = activity_log
+                    .get_entries_for_date(&date)
+                    .map_err(|e| e.to_string())?;
+                let start = all.len().saturating_sub(chunk_size);
+                all[start..].to_vec()
+            } else {
+                unbatched.into_iter().take(chunk_size).collect()
+            };
+            let frames = DigestGenerator::chunk_frames(&entries, &config);
+            let screen_text = config.ocr_mode != "off";
+            let mut vars =
+                DigestGenerator::extract_variables(&entries, &frames, structured, screen_text);
+            vars.set(
+                "has_images",
+                DigestGenerator::has_images(!text_only && config.ocr_mode != "instead"),
+            );
+            let default_body = if text_only {
+                TEXT_EXTRACT_PROMPT
+            } else {
+                DEFAULT_EXTRACT_PROMPT
+            };
+            (config.extract_template.clone(), default_body, vars)
+        }
+        TemplateKind::Digest => {
+            let mut vars = DigestGenerator::digest_variables(&activity_log, &config, &date)
+                .map_err(|e| e.to_string())?;
+            vars.set("commits", commits::to_text(&commits::for_date(&config, &date).await));
+            (config.digest_template.clone(), DEFAULT_DIGEST_PROMPT, vars)
+        }
+    };
+
+    let name = template.unwrap_or(configured);
+    let template = if kind == TemplateKind::Extract && text_only {
+        prompt_template::load_template_or_builtin(kind, &name, default_body)
+    } else {
+        prompt_template::load_template(kind, &name, default_body)
+    }
+    .map_err(|e| e.to_string())?;
+    let mut validation = prompt_template::validate(&template.body, kind);
+    let mut text = if validation.is_ok() {
+        prompt_template::render(&template.body, &vars).map_err(|e| e.to_string())?
+    } else {
+        String::new()
+    };
+    if kind == TemplateKind::Extract
+        && !text_only
+        && config.ocr_mode == "instead"
+        && !text.is_empty()
+    {
+        text.push_str("\n\n");
+        text.push_str(SCREEN_TEXT_ONLY_NOTE);
+    }
+    if kind == TemplateKind::Extract && structured && !text.is_empty() {
+        text.push_str("\n\n");
+        text.push_str(STRUCTURED_OUTPUT_INSTRUCTIONS);
+    }
+    // Show the prompt as it leaves the machine; new placeholders are not stored
+    let known = activity_log
+        .get_redactions_for_date(&date)
+        .map_err(|e| e.to_string())?;
+    let text = Redactor::new(&config.redaction, known).redact(&text);
+    if kind == TemplateKind::Extract && text_only {
+        validation.warnings.push(format!(
+            "{} cannot see screenshots; this is the text-only prompt, rendered with has_images empty",
+            extract_provider
+        ));
+    }
+    if kind == TemplateKind::Digest {
+        let model = config.llm_settings(LlmStep::Digest).model;
+        let threshold = config.digest_token_threshold_for(&model);
+        let estimated = summarization::estimate_tokens(&text);
+        if threshold > 0 && estimated > threshold {
+            validation.warnings.push(format!(
+                "Prompt is ~{} tokens, above the {} token threshold for {}; batch summaries will be condensed hour by hour first",
+                estimated, threshold, model
+            ));
+        }
+    }
+
+    Ok(PromptPreview {
+        template: template.name,
+        text,
+        errors: validation.errors,
+        warnings: validation.warnings,
+    })
+}
+
+#[tauri::command]
+pub fn open_prompts_dir(app: tauri::AppHandle) -> Result<(), String> {
+    let dir = AppConfig::prompts_dir();
+    std::fs::create_dir_all(&dir).map_err(|e| e.to_string())?;
+
+    app.opener()
+        .open_path(dir.to_string_lossy().to_string(), None::<&str>)
+        .map_err(|e| e.to_string())
+}
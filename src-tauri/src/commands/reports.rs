@@ -2,6 +2,7 @@ use serde::Serialize;
 
 use crate::services::digest_generator::{DEFAULT_DIGEST_PROMPT, DEFAULT_EXTRACT_PROMPT};
 use crate::storage::config::AppConfig;
+use crate::storage::report_history::{self, DiffLine, ReportVersion};
 use crate::AppState;
 use tauri::State;
 use tauri_plugin_opener::OpenerExt;
@@ -10,6 +11,26 @@ use tauri_plugin_opener::OpenerExt;
 pub struct DateInfo {
     pub date: String,
     pub has_report: bool,
+    /// Profiles with a report for this date ("default" is `report.md`).
+    pub profiles: Vec<String>,
+}
+
+/// Report file of `profile` (`None` = default profile).
+fn report_file(config: &AppConfig, profile: Option<&str>) -> Result<String, String> {
+    config
+        .digest_profile(profile)
+        .map(|p| p.report_file())
+        .map_err(|e| e.to_string())
+}
+
+/// Names of the profiles that have a report in `date_dir`.
+fn profiles_with_report(config: &AppConfig, date_dir: &std::path::Path) -> Vec<String> {
+    config
+        .all_profiles()
+        .into_iter()
+        .filter(|p| date_dir.join(p.report_file()).exists())
+        .map(|p| p.name)
+        .collect()
 }
 
 #[tauri::command]
@@ -33,9 +54,11 @@ pub fn list_data_dates(state: State<'_, AppState>) -> Result<Vec<DateInfo>, Stri
                 && entry.path().join("activity.db").exists()
             {
                 let has_report = entry.path().join("report.md").exists();
+                let profiles = profiles_with_report(&config, &entry.path());
                 Some(DateInfo {
                     date: name,
                     has_report,
+                    profiles,
                 })
             } else {
                 None
@@ -61,11 +84,11 @@ pub fn list_reports(state: State<'_, AppState>) -> Result<Vec<String>, String> {
         .filter_map(|entry| {
             let entry = entry.ok()?;
             let name = entry.file_name().to_string_lossy().to_string();
-            // Must match YYYY-MM-DD pattern and contain report.md
+            // Must match YYYY-MM-DD pattern and contain a report of any profile
             if name.len() == 10
                 && name.chars().nth(4) == Some('-')
                 && name.chars().nth(7) == Some('-')
-                && entry.path().join("report.md").exists()
+                && !profiles_with_report(&config, &entry.path()).is_empty()
             {
                 Some(name)
             } else {
@@ -80,21 +103,87 @@ pub fn list_reports(state: State<'_, AppState>) -> Result<Vec<String>, String> {
 }
 
 #[tauri::command]
-pub fn read_report(state: State<'_, AppState>, date: String) -> Result<String, String> {
+pub fn read_report(
+    state: State<'_, AppState>,
+    date: String,
+    profile: Option<String>,
+) -> Result<String, String> {
     let config = state.config.lock().unwrap().clone();
-    let report_path = config.data_path().join(&date).join("report.md");
+    let file = report_file(&config, profile.as_deref())?;
+    let report_path = config.data_path().join(&date).join(file);
 
     std::fs::read_to_string(&report_path).map_err(|e| e.to_string())
 }
 
+#[tauri::command]
+pub fn list_report_versions(
+    state: State<'_, AppState>,
+    date: String,
+    profile: Option<String>,
+) -> Result<Vec<ReportVersion>, String> {
+    let config = state.config.lock().unwrap().clone();
+    let file = report_file(&config, profile.as_deref())?;
+    report_history::list_versions(&config.data_path().join(&date), &file)
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub fn read_report_version(
+    state: State<'_, AppState>,
+    date: String,
+    profile: Option<String>,
+    id: String,
+) -> Result<String, String> {
+    let config = state.config.lock().unwrap().clone();
+    let file = report_file(&config, profile.as_deref())?;
+    report_history::read_version(&config.data_path().join(&date), &file, &id)
+        .map_err(|e| e.to_string())
+}
+
+/// Line diff from version `from_id` to version `to_id`.
+#[tauri::command]
+pub fn diff_report_versions(
+    state: State<'_, AppState>,
+    date: String,
+    profile: Option<String>,
+    from_id: String,
+    to_id: String,
+) -> Result<Vec<DiffLine>, String> {
+    let config = state.config.lock().unwrap().clone();
+    let file = report_file(&config, profile.as_deref())?;
+    let date_dir = config.data_path().join(&date);
+    let old =
+        report_history::read_version(&date_dir, &file, &from_id).map_err(|e| e.to_string())?;
+    let new =
+        report_history::read_version(&date_dir, &file, &to_id).map_err(|e| e.to_string())?;
+    Ok(report_history::diff_lines(&old, &new))
+}
+
+#[tauri::command]
+pub fn promote_report_version(
+    state: State<'_, AppState>,
+    date: String,
+    profile: Option<String>,
+    id: String,
+) -> Result<(), String> {
+    let config = state.config.lock().unwrap().clone();
+    let file = report_file(&config, profile.as_deref())?;
+    report_history::promote(&config.data_path().join(&date), &file, &id)
+        .map_err(|e| e.to_string())?;
+    log::info!("Promoted {} version {} for {}", file, id, date);
+    Ok(())
+}
+
 #[tauri::command]
 pub fn open_report_file(
     app: tauri::AppHandle,
     state: State<'_, AppState>,
     date: String,
+    profile: Option<String>,
 ) -> Result<(), String> {
     let config = state.config.lock().unwrap().clone();
-    let path = config.data_path().join(&date).join("report.md");
+    let file = report_file(&config, profile.as_deref())?;
+    let path = config.data_path().join(&date).join(file);
 
     if !path.exists() {
         return Err("Report file not found".to_string());
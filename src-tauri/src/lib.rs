@@ -3,6 +3,7 @@ mod services;
 mod storage;
 
 use services::activity_log::ActivityLog;
+use services::jobs::JobRegistry;
 use storage::config::AppConfig;
 
 use std::path::Path;
@@ -40,6 +41,7 @@ pub struct AppState {
     pub stop_tx: Mutex<Option<tokio::sync::watch::Sender<bool>>>,
     pub auto_report_stop_tx: Mutex<Option<tokio::sync::watch::Sender<bool>>>,
     pub scheduled_monitoring_stop_tx: Mutex<Option<tokio::sync::watch::Sender<bool>>>,
+    pub jobs: Arc<JobRegistry>,
     pub quitting: std::sync::atomic::AtomicBool,
 }
 
@@ -130,10 +132,10 @@ pub(crate) fn rebuild_tray_menu(app: &tauri::AppHandle, is_monitoring: bool) {
         "Start Monitoring"
     };
 
-    let (current_size, wander_enabled) = {
+    let (current_size, wander_enabled, has_jobs) = {
         let state = app.state::<AppState>();
         let config = state.config.lock().unwrap();
-        (config.pet_size.clone(), config.wander_enabled)
+        (config.pet_size.clone(), config.wander_enabled, !state.jobs.is_empty())
     };
 
     let pet_visible = app
@@ -152,17 +154,32 @@ pub(crate) fn rebuild_tray_menu(app: &tauri::AppHandle, is_monitoring: bool) {
     let settings_item = MenuItem::with_id(app, "settings", "Settings", true, None::<&str>).unwrap();
     let quit_item = MenuItem::with_id(app, "quit", "Quit", true, None::<&str>).unwrap();
 
-    let menu = Menu::with_items(
-        app,
-        &[&toggle_item, &digest_item, &reports_item, &size_submenu, &wander_item, &hide_show_item, &change_pet_item, &settings_item, &quit_item],
-    )
-    .unwrap();
+    let menu = if has_jobs {
+        let cancel_item = MenuItem::with_id(app, "cancel_jobs", "Cancel", true, None::<&str>).unwrap();
+        Menu::with_items(
+            app,
+            &[&toggle_item, &digest_item, &cancel_item, &reports_item, &size_submenu, &wander_item, &hide_show_item, &change_pet_item, &settings_item, &quit_item],
+        )
+        .unwrap()
+    } else {
+        Menu::with_items(
+            app,
+            &[&toggle_item, &digest_item, &reports_item, &size_submenu, &wander_item, &hide_show_item, &change_pet_item, &settings_item, &quit_item],
+        )
+        .unwrap()
+    };
 
     if let Some(tray) = app.tray_by_id("main-tray") {
         let _ = tray.set_menu(Some(menu));
     }
 }
 
+/// Rebuild the tray menu for the current monitoring state, e.g. after a job starts or finishes.
+pub(crate) fn refresh_tray_menu(app: &tauri::AppHandle) {
+    let is_monitoring = *app.state::<AppState>().is_monitoring.lock().unwrap();
+    rebuild_tray_menu(app, is_monitoring);
+}
+
 pub(crate) fn update_tray_icon(app: &tauri::AppHandle, is_monitoring: bool) {
     if let Some(tray) = app.tray_by_id("main-tray") {
         if is_monitoring {
@@ -219,6 +236,7 @@ pub fn run() {
         stop_tx: Mutex::new(None),
         auto_report_stop_tx: Mutex::new(None),
         scheduled_monitoring_stop_tx: Mutex::new(None),
+        jobs: Arc::new(JobRegistry::new()),
         quitting: std::sync::atomic::AtomicBool::new(false),
     };
 
@@ -392,6 +410,11 @@ pub fn run() {
                                 }
                             }
                         }
+                        "cancel_jobs" => {
+                            let state = app.state::<AppState>();
+                            let count = state.jobs.cancel_all();
+                            log::info!("Cancelled {} job(s) from tray", count);
+                        }
                         "size_small" | "size_medium" | "size_large" => {
                             let new_size = match event.id.as_ref() {
                                 "size_small" => "small",
@@ -532,6 +555,7 @@ pub fn run() {
                             if let Some(tx) = state.scheduled_monitoring_stop_tx.lock().unwrap().take() {
                                 let _ = tx.send(true);
                             }
+                            state.jobs.cancel_all();
                             state.quitting.store(true, std::sync::atomic::Ordering::SeqCst);
                             app.exit(0);
                         }
@@ -619,7 +643,7 @@ pub fn run() {
             {
                 let state = app.state::<AppState>();
                 let cfg = state.config.lock().unwrap().clone();
-                if cfg.auto_report_enabled {
+                if !cfg.scheduled_profiles().is_empty() {
                     let (tx, rx) = tokio::sync::watch::channel(false);
                     *state.auto_report_stop_tx.lock().unwrap() = Some(tx);
                     services::auto_report::AutoReportScheduler::start(
@@ -713,6 +737,9 @@ pub fn run() {
             commands::monitor::start_monitoring,
             commands::monitor::stop_monitoring,
             commands::digest::generate_digest,
+            commands::export::export_timesheet,
+            commands::jobs::list_jobs,
+            commands::jobs::cancel_job,
             commands::config::get_config,
             commands::config::set_config,
             commands::config::save_pet_position,
@@ -725,9 +752,17 @@ pub fn run() {
             commands::reports::list_data_dates,
             commands::reports::list_reports,
             commands::reports::read_report,
+            commands::reports::list_report_versions,
+            commands::reports::read_report_version,
+            commands::reports::diff_report_versions,
+            commands::reports::promote_report_version,
             commands::reports::open_report_file,
             commands::reports::open_prompt_file,
             commands::reports::open_extract_prompt_file,
+            commands::prompts::list_prompt_templates,
+            commands::prompts::render_prompt_preview,
+            commands::prompts::open_prompts_dir,
+            commands::audit::list_llm_audit,
         ])
         .on_window_event(|_window, _event| {
             #[cfg(target_os = "macos")]
@@ -1,8 +1,14 @@
 use anyhow::Result;
 use chrono::Local;
 use rusqlite::{params, Connection};
+use std::collections::HashMap;
 use std::path::PathBuf;
-use std::sync::Mutex;
+use std::sync::{Arc, Mutex};
+
+use crate::services::browser_tab::BrowserTab;
+use crate::services::dev_context::DevContext;
+use crate::services::meetings::{self, Meeting};
+use crate::services::screenshot::ScreenArea;
 
 const SCHEMA: &str = "
     CREATE TABLE IF NOT EXISTS activity_log (
@@ -20,7 +26,29 @@ const SCHEMA: &str = "
         summary TEXT NOT NULL DEFAULT '',
         entry_count INTEGER NOT NULL DEFAULT 0
     );
+    CREATE TABLE IF NOT EXISTS observations (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        activity_id INTEGER NOT NULL REFERENCES activity_log(id),
+        batch_id TEXT NOT NULL,
+        timestamp TEXT NOT NULL DEFAULT '',
+        app_name TEXT NOT NULL DEFAULT '',
+        window_title TEXT NOT NULL DEFAULT '',
+        description TEXT NOT NULL DEFAULT ''
+    );
+    CREATE TABLE IF NOT EXISTS redactions (
+        placeholder TEXT PRIMARY KEY,
+        value TEXT NOT NULL
+    );
+    CREATE TABLE IF NOT EXISTS meetings (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        start TEXT NOT NULL,
+        end TEXT NOT NULL,
+        platform TEXT NOT NULL DEFAULT '',
+        title TEXT NOT NULL DEFAULT '',
+        source TEXT NOT NULL
+    );
     CREATE INDEX IF NOT EXISTS idx_activity_batch ON activity_log(batch_id);
+    CREATE INDEX IF NOT EXISTS idx_observations_activity ON observations(activity_id);
     CREATE INDEX IF NOT EXISTS idx_activity_timestamp ON activity_log(timestamp);
 ";
 
@@ -29,6 +57,7 @@ pub struct ActivityLog {
     base_dir: PathBuf,
     conn: Mutex<Connection>,
     current_date: Mutex<String>,
+    day_locks: Mutex<HashMap<String, Arc<tokio::sync::Mutex<()>>>>,
 }
 
 #[derive(Debug, Clone, serde::Serialize)]
@@ -40,6 +69,36 @@ pub struct ActivityEntry {
     pub app_name: String,
     pub image_hash: String,
     pub batch_id: Option<String>,
+    pub project_id: Option<String>,
+    /// Text read from the screenshot by local OCR; empty when OCR is off.
+    pub ocr_text: String,
+    /// URL of the active browser tab, from the browser extension; empty otherwise.
+    pub url: String,
+    /// Git repository of the focused terminal or IDE; empty otherwise.
+    pub repo_path: String,
+    pub git_branch: String,
+}
+
+/// What was read and detected with a screenshot, stored in the same insert
+/// so the batch task never sees a row without it.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct CaptureContext<'a> {
+    pub project_id: Option<&'a str>,
+    /// Local OCR text; empty when OCR is off.
+    pub ocr_text: &'a str,
+    /// Bounds of the window the screenshot was cropped to.
+    pub window_bounds: Option<&'a ScreenArea>,
+    pub tab: Option<&'a BrowserTab>,
+    pub dev_context: Option<&'a DevContext>,
+}
+
+/// A repository and branch worked in on a day.
+#[derive(Debug, Clone)]
+pub struct RepoUsage {
+    pub repo_path: String,
+    pub branch: String,
+    /// Last commit subject seen on the branch that day.
+    pub commit_subject: String,
 }
 
 #[derive(Debug, Clone, serde::Serialize)]
@@ -48,6 +107,19 @@ pub struct BatchSummary {
     pub timestamp: String,
     pub summary: String,
     pub entry_count: i64,
+    /// Provider that answered ("ollama:llava"), "text-only" without one, or
+    /// empty for batches recorded before this was tracked.
+    pub provider: String,
+}
+
+/// Per-screenshot fields from a structured extraction, linked to an `activity_log` row.
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct Observation {
+    pub activity_id: i64,
+    pub timestamp: String,
+    pub app_name: String,
+    pub window_title: String,
+    pub description: String,
 }
 
 fn open_day_db(base_dir: &PathBuf, date: &str) -> Result<Connection> {
@@ -56,9 +128,38 @@ fn open_day_db(base_dir: &PathBuf, date: &str) -> Result<Connection> {
     let db_path = day_dir.join("activity.db");
     let conn = Connection::open(db_path)?;
     conn.execute_batch(SCHEMA)?;
+    migrate(&conn)?;
     Ok(conn)
 }
 
+/// Add columns introduced after a day's database was first created.
+fn migrate(conn: &Connection) -> Result<()> {
+    ensure_column(conn, "activity_log", "project_id", "TEXT")?;
+    ensure_column(conn, "llm_batches", "provider", "TEXT NOT NULL DEFAULT ''")?;
+    ensure_column(conn, "activity_log", "ocr_text", "TEXT NOT NULL DEFAULT ''")?;
+    for column in ["window_x", "window_y", "window_width", "window_height"] {
+        ensure_column(conn, "activity_log", column, "INTEGER")?;
+    }
+    ensure_column(conn, "activity_log", "url", "TEXT NOT NULL DEFAULT ''")?;
+    ensure_column(conn, "activity_log", "domain", "TEXT NOT NULL DEFAULT ''")?;
+    for column in ["repo_path", "git_branch", "commit_subject"] {
+        ensure_column(conn, "activity_log", column, "TEXT NOT NULL DEFAULT ''")?;
+    }
+    Ok(())
+}
+
+fn ensure_column(conn: &Connection, table: &str, column: &str, decl: &str) -> Result<()> {
+    let mut stmt = conn.prepare(&format!("PRAGMA table_info({})", table))?;
+    let exists = stmt
+        .query_map([], |row| row.get::<_, String>(1))?
+        .filter_map(|name| name.ok())
+        .any(|name| name == column);
+    if !exists {
+        conn.execute_batch(&format!("ALTER TABLE {} ADD COLUMN {} {}", table, column, decl))?;
+    }
+    Ok(())
+}
+
 fn today_str() -> String {
     Local::now().format("%Y-%m-%d").to_string()
 }
@@ -73,9 +174,19 @@ impl ActivityLog {
             base_dir: base_dir.clone(),
             conn: Mutex::new(conn),
             current_date: Mutex::new(date),
+            day_locks: Mutex::new(HashMap::new()),
         })
     }
 
+    /// Per-day processing lock. Batch processing and digest generation for a date
+    /// hold it so overlapping runs never send the same entries to the LLM twice.
+    pub fn day_lock(&self, date: &str) -> Arc<tokio::sync::Mutex<()>> {
+        let mut locks = self.day_locks.lock().unwrap();
+        // Locks nobody holds or waits for are only referenced by the map
+        locks.retain(|_, lock| Arc::strong_count(lock) > 1);
+        locks.entry(date.to_string()).or_default().clone()
+    }
+
     /// Ensure we're using today's database; roll over if the date changed.
     pub fn ensure_today(&self) -> Result<()> {
         let today = today_str();
@@ -90,11 +201,22 @@ impl ActivityLog {
         Ok(())
     }
 
+    /// Date of the database new activity is written to, after rolling over.
+    pub fn current_date(&self) -> Result<String> {
+        self.ensure_today()?;
+        Ok(self.current_date.lock().unwrap().clone())
+    }
+
     /// Open (or create) the database for a specific date.
     pub fn open_for_date(&self, date: &str) -> Result<Connection> {
         open_day_db(&self.base_dir, date)
     }
 
+    /// Whether a database exists for `date`, without creating one.
+    pub fn date_exists(&self, date: &str) -> bool {
+        self.base_dir.join(date).join("activity.db").exists()
+    }
+
     pub fn insert_activity(
         &self,
         timestamp: &str,
@@ -102,22 +224,105 @@ impl ActivityLog {
         window_title: &str,
         app_name: &str,
         image_hash: &str,
+        context: &CaptureContext,
     ) -> Result<i64> {
         self.ensure_today()?;
         let conn = self.conn.lock().unwrap();
+        let bounds = context.window_bounds;
+        let tab = context.tab;
+        let dev = context.dev_context;
         conn.execute(
-            "INSERT INTO activity_log (timestamp, screenshot_path, window_title, app_name, image_hash)
-             VALUES (?1, ?2, ?3, ?4, ?5)",
-            params![timestamp, screenshot_path, window_title, app_name, image_hash],
+            "INSERT INTO activity_log (timestamp, screenshot_path, window_title, app_name, image_hash, project_id,
+                                       ocr_text, window_x, window_y, window_width, window_height,
+                                       url, domain, repo_path, git_branch, commit_subject)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16)",
+            params![
+                timestamp,
+                screenshot_path,
+                window_title,
+                app_name,
+                image_hash,
+                context.project_id,
+                context.ocr_text,
+                bounds.map(|b| b.x),
+                bounds.map(|b| b.y),
+                bounds.map(|b| b.width),
+                bounds.map(|b| b.height),
+                tab.map_or("", |t| t.url.as_str()),
+                tab.map_or("", |t| t.domain.as_str()),
+                dev.map_or("", |d| d.repo_path.as_str()),
+                dev.map_or("", |d| d.branch.as_str()),
+                dev.map_or("", |d| d.commit_subject.as_str()),
+            ],
+        )?;
+        Ok(conn.last_insert_rowid())
+    }
+
+    /// Open a detected meeting interval today; returns its id.
+    pub fn start_meeting(&self, timestamp: &str, platform: &str) -> Result<i64> {
+        self.ensure_today()?;
+        let conn = self.conn.lock().unwrap();
+        conn.execute(
+            "INSERT INTO meetings (start, end, platform, source) VALUES (?1, ?1, ?2, ?3)",
+            params![timestamp, platform, meetings::SOURCE_DETECTED],
         )?;
         Ok(conn.last_insert_rowid())
     }
 
+    /// Move the end of a meeting interval opened today.
+    pub fn extend_meeting(&self, id: i64, end: &str, platform: &str) -> Result<()> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute(
+            "UPDATE meetings SET end = ?1, platform = ?2 WHERE id = ?3",
+            params![end, platform, id],
+        )?;
+        Ok(())
+    }
+
+    /// Replace the calendar events stored for `date` with a fresh import.
+    pub fn replace_calendar_meetings(&self, date: &str, events: &[Meeting]) -> Result<()> {
+        let mut conn = self.open_for_date(date)?;
+        let tx = conn.transaction()?;
+        tx.execute(
+            "DELETE FROM meetings WHERE source = ?1",
+            params![meetings::SOURCE_CALENDAR],
+        )?;
+        for event in events {
+            tx.execute(
+                "INSERT INTO meetings (start, end, platform, title, source) VALUES (?1, ?2, ?3, ?4, ?5)",
+                params![event.start, event.end, event.platform, event.title, event.source],
+            )?;
+        }
+        tx.commit()?;
+        Ok(())
+    }
+
+    /// Detected and imported meetings of `date`, oldest first.
+    pub fn get_meetings_for_date(&self, date: &str) -> Result<Vec<Meeting>> {
+        let conn = self.open_for_date(date)?;
+        let mut stmt = conn.prepare(
+            "SELECT start, end, platform, title, source FROM meetings ORDER BY start ASC",
+        )?;
+        let meetings = stmt
+            .query_map([], |row| {
+                Ok(Meeting {
+                    start: row.get(0)?,
+                    end: row.get(1)?,
+                    platform: row.get(2)?,
+                    title: row.get(3)?,
+                    source: row.get(4)?,
+                })
+            })?
+            .collect::<std::result::Result<Vec<_>, _>>()?;
+        Ok(meetings)
+    }
+
     pub fn get_unbatched_entries(&self) -> Result<Vec<ActivityEntry>> {
         self.ensure_today()?;
         let conn = self.conn.lock().unwrap();
         let mut stmt = conn.prepare(
-            "SELECT id, timestamp, screenshot_path, window_title, app_name, image_hash, batch_id
+            "SELECT id, timestamp, screenshot_path, window_title, app_name, image_hash, batch_id, project_id,
+                    ocr_text, url, repo_path, git_branch
              FROM activity_log WHERE batch_id IS NULL ORDER BY timestamp ASC",
         )?;
         let entries = stmt
@@ -130,36 +335,85 @@ impl ActivityLog {
                     app_name: row.get(4)?,
                     image_hash: row.get(5)?,
                     batch_id: row.get(6)?,
+                    project_id: row.get(7)?,
+                    ocr_text: row.get(8)?,
+                    url: row.get(9)?,
+                    repo_path: row.get(10)?,
+                    git_branch: row.get(11)?,
                 })
             })?
             .collect::<std::result::Result<Vec<_>, _>>()?;
         Ok(entries)
     }
 
-    pub fn mark_entries_batched(&self, entry_ids: &[i64], batch_id: &str) -> Result<()> {
-        let conn = self.conn.lock().unwrap();
-        for id in entry_ids {
-            conn.execute(
-                "UPDATE activity_log SET batch_id = ?1 WHERE id = ?2",
-                params![batch_id, id],
-            )?;
-        }
-        Ok(())
+    /// Get all activity entries from a specific date's database, oldest first.
+    pub fn get_entries_for_date(&self, date: &str) -> Result<Vec<ActivityEntry>> {
+        let conn = self.open_for_date(date)?;
+        let mut stmt = conn.prepare(
+            "SELECT id, timestamp, screenshot_path, window_title, app_name, image_hash, batch_id, project_id,
+                    ocr_text, url, repo_path, git_branch
+             FROM activity_log ORDER BY timestamp ASC",
+        )?;
+        let entries = stmt
+            .query_map([], |row| {
+                Ok(ActivityEntry {
+                    id: row.get(0)?,
+                    timestamp: row.get(1)?,
+                    screenshot_path: row.get(2)?,
+                    window_title: row.get(3)?,
+                    app_name: row.get(4)?,
+                    image_hash: row.get(5)?,
+                    batch_id: row.get(6)?,
+                    project_id: row.get(7)?,
+                    ocr_text: row.get(8)?,
+                    url: row.get(9)?,
+                    repo_path: row.get(10)?,
+                    git_branch: row.get(11)?,
+                })
+            })?
+            .collect::<std::result::Result<Vec<_>, _>>()?;
+        Ok(entries)
     }
 
-    pub fn insert_batch_summary(
+    /// Store a batch summary, its observations and link its entries in one transaction,
+    /// so a failed or cancelled write never leaves entries pointing at a missing batch.
+    pub fn commit_batch(
         &self,
         batch_id: &str,
         timestamp: &str,
         summary: &str,
-        entry_count: i64,
+        entry_ids: &[i64],
+        observations: &[Observation],
+        provider: &str,
     ) -> Result<()> {
-        let conn = self.conn.lock().unwrap();
-        conn.execute(
-            "INSERT INTO llm_batches (id, timestamp, summary, entry_count)
-             VALUES (?1, ?2, ?3, ?4)",
-            params![batch_id, timestamp, summary, entry_count],
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction()?;
+        tx.execute(
+            "INSERT INTO llm_batches (id, timestamp, summary, entry_count, provider)
+             VALUES (?1, ?2, ?3, ?4, ?5)",
+            params![batch_id, timestamp, summary, entry_ids.len() as i64, provider],
         )?;
+        for id in entry_ids {
+            tx.execute(
+                "UPDATE activity_log SET batch_id = ?1 WHERE id = ?2",
+                params![batch_id, id],
+            )?;
+        }
+        for obs in observations {
+            tx.execute(
+                "INSERT INTO observations (activity_id, batch_id, timestamp, app_name, window_title, description)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+                params![
+                    obs.activity_id,
+                    batch_id,
+                    obs.timestamp,
+                    obs.app_name,
+                    obs.window_title,
+                    obs.description
+                ],
+            )?;
+        }
+        tx.commit()?;
         Ok(())
     }
 
@@ -167,7 +421,7 @@ impl ActivityLog {
         self.ensure_today()?;
         let conn = self.conn.lock().unwrap();
         let mut stmt = conn.prepare(
-            "SELECT id, timestamp, summary, entry_count
+            "SELECT id, timestamp, summary, entry_count, provider
              FROM llm_batches ORDER BY timestamp ASC",
         )?;
         let batches = stmt
@@ -177,6 +431,7 @@ impl ActivityLog {
                     timestamp: row.get(1)?,
                     summary: row.get(2)?,
                     entry_count: row.get(3)?,
+                    provider: row.get(4)?,
                 })
             })?
             .collect::<std::result::Result<Vec<_>, _>>()?;
@@ -187,7 +442,7 @@ impl ActivityLog {
     pub fn get_batches_for_date(&self, date: &str) -> Result<Vec<BatchSummary>> {
         let conn = self.open_for_date(date)?;
         let mut stmt = conn.prepare(
-            "SELECT id, timestamp, summary, entry_count
+            "SELECT id, timestamp, summary, entry_count, provider
              FROM llm_batches ORDER BY timestamp ASC",
         )?;
         let batches = stmt
@@ -197,12 +452,108 @@ impl ActivityLog {
                     timestamp: row.get(1)?,
                     summary: row.get(2)?,
                     entry_count: row.get(3)?,
+                    provider: row.get(4)?,
                 })
             })?
             .collect::<std::result::Result<Vec<_>, _>>()?;
         Ok(batches)
     }
 
+    /// Get structured observations from a specific date's database.
+    pub fn get_observations_for_date(&self, date: &str) -> Result<Vec<Observation>> {
+        let conn = self.open_for_date(date)?;
+        let mut stmt = conn.prepare(
+            "SELECT activity_id, timestamp, app_name, window_title, description
+             FROM observations ORDER BY activity_id ASC, id ASC",
+        )?;
+        let observations = stmt
+            .query_map([], |row| {
+                Ok(Observation {
+                    activity_id: row.get(0)?,
+                    timestamp: row.get(1)?,
+                    app_name: row.get(2)?,
+                    window_title: row.get(3)?,
+                    description: row.get(4)?,
+                })
+            })?
+            .collect::<std::result::Result<Vec<_>, _>>()?;
+        Ok(observations)
+    }
+
+    /// Placeholder mapping used when redacting text of `date`, in assignment order.
+    pub fn get_redactions_for_date(&self, date: &str) -> Result<Vec<(String, String)>> {
+        let conn = self.open_for_date(date)?;
+        let mut stmt = conn.prepare("SELECT placeholder, value FROM redactions ORDER BY rowid ASC")?;
+        let mapping = stmt
+            .query_map([], |row| Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?)))?
+            .collect::<std::result::Result<Vec<_>, _>>()?;
+        Ok(mapping)
+    }
+
+    pub fn add_redactions_for_date(&self, date: &str, mapping: &[(String, String)]) -> Result<()> {
+        if mapping.is_empty() {
+            return Ok(());
+        }
+        let conn = self.open_for_date(date)?;
+        for (placeholder, value) in mapping {
+            conn.execute(
+                "INSERT OR IGNORE INTO redactions (placeholder, value) VALUES (?1, ?2)",
+                params![placeholder, value],
+            )?;
+        }
+        Ok(())
+    }
+
+    /// Assign a project to entries that were not matched by a rule at capture time.
+    pub fn set_project_for_entries(&self, entry_ids: &[i64], project_id: &str) -> Result<()> {
+        let conn = self.conn.lock().unwrap();
+        for id in entry_ids {
+            conn.execute(
+                "UPDATE activity_log SET project_id = ?1 WHERE id = ?2 AND project_id IS NULL",
+                params![project_id, id],
+            )?;
+        }
+        Ok(())
+    }
+
+    /// Get per-project screenshot counts from a specific date's database.
+    /// Entries without a project are reported under `None`.
+    pub fn get_project_usage_for_date(&self, date: &str) -> Result<Vec<(Option<String>, i64)>> {
+        let conn = self.open_for_date(date)?;
+        let mut stmt = conn.prepare(
+            "SELECT project_id, COUNT(*) as count
+             FROM activity_log GROUP BY project_id ORDER BY count DESC",
+        )?;
+        let usage = stmt
+            .query_map([], |row| {
+                Ok((row.get::<_, Option<String>>(0)?, row.get::<_, i64>(1)?))
+            })?
+            .collect::<std::result::Result<Vec<_>, _>>()?;
+        Ok(usage)
+    }
+
+    /// Get the repositories and branches of a specific date's database with
+    /// the last commit seen on each.
+    pub fn get_repo_usage_for_date(&self, date: &str) -> Result<Vec<RepoUsage>> {
+        let conn = self.open_for_date(date)?;
+        // With MAX(), SQLite takes commit_subject from the latest row of each group
+        let mut stmt = conn.prepare(
+            "SELECT repo_path, git_branch, commit_subject, MAX(timestamp)
+             FROM activity_log WHERE repo_path != ''
+             GROUP BY repo_path, git_branch",
+        )?;
+        let usage = stmt
+            .query_map([], |row| {
+                Ok(RepoUsage {
+                    repo_path: row.get(0)?,
+                    branch: row.get(1)?,
+                    commit_subject: row.get(2)?,
+                })
+            })?
+            .collect::<std::result::Result<Vec<_>, _>>()?;
+        Ok(usage)
+    }
+
     pub fn get_app_usage(&self) -> Result<Vec<(String, i64)>> {
         self.ensure_today()?;
         let conn = self.conn.lock().unwrap();
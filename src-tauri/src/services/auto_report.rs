@@ -1,3 +1,4 @@
+use std::path::PathBuf;
 use std::sync::Arc;
 
 use chrono::{Local, NaiveTime, Timelike};
@@ -10,28 +11,49 @@ use tauri::Manager;
 
 use crate::services::activity_log::ActivityLog;
 use crate::services::digest_generator::DigestGenerator;
+use crate::services::jobs::{Cancelled, JobKind};
 use crate::services::llm_client::LlmClient;
-use crate::storage::config::AppConfig;
+use crate::storage::config::{AppConfig, LlmStep};
+use crate::storage::profiles::DigestProfile;
 use crate::storage::screenshot_store::ScreenshotStore;
 use crate::AppState;
 
 pub struct AutoReportScheduler;
 
 impl AutoReportScheduler {
+    /// Start one schedule per profile with an automatic report time.
     pub fn start(
         config: AppConfig,
         activity_log: Arc<ActivityLog>,
+        stop_rx: watch::Receiver<bool>,
+        app_handle: tauri::AppHandle,
+    ) {
+        for profile in config.scheduled_profiles() {
+            Self::start_profile(
+                profile,
+                config.data_path(),
+                activity_log.clone(),
+                stop_rx.clone(),
+                app_handle.clone(),
+            );
+        }
+    }
+
+    fn start_profile(
+        profile: DigestProfile,
+        data_dir: PathBuf,
+        activity_log: Arc<ActivityLog>,
         mut stop_rx: watch::Receiver<bool>,
         app_handle: tauri::AppHandle,
     ) {
-        let target_time = parse_time(&config.auto_report_time);
-        let data_dir = config.data_path();
+        let target_time = parse_time(&profile.auto_report_time);
 
         tauri::async_runtime::spawn(async move {
             loop {
                 let wait = duration_until_next(target_time);
                 log::info!(
-                    "Auto-report scheduled in {} seconds (target {:02}:{:02})",
+                    "Auto-report ({} profile) scheduled in {} seconds (target {:02}:{:02})",
+                    profile.name,
                     wait.as_secs(),
                     target_time.hour(),
                     target_time.minute()
@@ -56,15 +78,40 @@ impl AutoReportScheduler {
 
                 // Generate the digest
                 let store = ScreenshotStore::new(data_dir.clone());
-                let config = app_handle.state::<AppState>().config.lock().unwrap().clone();
-                let llm = LlmClient::new(
-                    &config.llm_provider,
-                    &config.api_key,
-                    &config.model,
-                    &config.api_endpoint,
-                    Some(config.data_path()),
-                );
-                match DigestGenerator::generate_daily_digest(&activity_log, &store, &llm).await {
+                let state = app_handle.state::<AppState>();
+                let config = state.config.lock().unwrap().clone();
+                // Pick up edits made since the scheduler started
+                let profile = config
+                    .digest_profile(Some(&profile.name))
+                    .unwrap_or_else(|_| profile.clone());
+                let today = Local::now().format("%Y-%m-%d").to_string();
+                let job = state.jobs.start(JobKind::Digest, &today);
+                crate::refresh_tray_menu(&app_handle);
+                let llm = LlmClient::for_step(&config, LlmStep::Extraction)
+                    .with_cancel(job.token().clone());
+                let result =
+                    DigestGenerator::generate_daily_digest(
+                        &activity_log,
+                        &store,
+                        &llm,
+                        &config,
+                        &profile,
+                        job.token(),
+                    )
+                    .await;
+                drop(job);
+                crate::refresh_tray_menu(&app_handle);
+                match result {
+                    Ok(path) if !profile.is_default() => {
+                        let _ = app_handle.emit("digest-ready", path.to_string_lossy().to_string());
+                        log::info!("Auto-report ({} profile) generated: {:?}", profile.name, path);
+
+                        let _ = app_handle.notification()
+                            .builder()
+                            .title("Diaroo")
+                            .body(format!("{} report generated.", profile.name))
+                            .show();
+                    }
                     Ok(path) => {
                         let _ = app_handle.emit("digest-ready", path.to_string_lossy().to_string());
                         log::info!("Auto-report generated: {:?}", path);
@@ -95,8 +142,16 @@ impl AutoReportScheduler {
                             .body("Daily report generated. Monitoring has been stopped.")
                             .show();
                     }
+                    Err(e) if e.is::<Cancelled>() => {
+                        log::info!("Auto-report generation cancelled");
+                    }
                     Err(e) => {
                         log::error!("Auto-report generation failed: {}", e);
+                        let _ = app_handle.notification()
+                            .builder()
+                            .title("Diaroo")
+                            .body(format!("Report generation failed: {}", e))
+                            .show();
                     }
                 }
 
@@ -0,0 +1,240 @@
+use anyhow::{anyhow, Result};
+use serde::Deserialize;
+use std::sync::{Arc, Mutex};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::watch;
+use tokio::time::{timeout, Duration, Instant};
+
+use crate::storage::projects::{host_in_domain, url_host};
+
+/// Requests larger than this are rejected; a tab report is a few hundred bytes.
+const MAX_REQUEST_BYTES: usize = 16 * 1024;
+/// Header the extension sends. Web pages cannot add it without a CORS
+/// preflight, which this server never approves.
+const EXTENSION_HEADER: &str = "x-diaroo-extension";
+/// Without a usable window title, a report older than this is not trusted.
+const UNTITLED_MAX_AGE: Duration = Duration::from_secs(120);
+/// App names of browsers (lowercase, without ".exe"); editions such as
+/// "Firefox Nightly" match by prefix.
+const BROWSER_APPS: &[&str] = &[
+    "google chrome",
+    "chrome",
+    "chromium",
+    "chromium-browser",
+    "firefox",
+    "firefox-esr",
+    "librewolf",
+    "safari",
+    "microsoft edge",
+    "msedge",
+    "brave browser",
+    "brave",
+    "brave-browser",
+    "opera",
+    "vivaldi",
+    "arc",
+    "zen",
+];
+
+/// Active tab reported by the browser extension.
+#[derive(Debug, Clone, Deserialize)]
+pub struct TabReport {
+    /// Empty when no browser window has focus.
+    #[serde(default)]
+    pub url: String,
+    #[serde(default)]
+    pub title: String,
+}
+
+/// The URL and domain attached to an activity row.
+#[derive(Debug, Clone)]
+pub struct BrowserTab {
+    pub url: String,
+    /// Host of the URL without a leading "www.".
+    pub domain: String,
+}
+
+/// Latest tab reported by the extension, shared between the local server and
+/// the capture task.
+#[derive(Default)]
+pub struct TabTracker {
+    latest: Mutex<Option<(TabReport, Instant)>>,
+}
+
+impl TabTracker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn record(&self, report: TabReport) {
+        *self.latest.lock().unwrap() = Some((report, Instant::now()));
+    }
+
+    /// The reported tab if it belongs to the focused window: the browser puts
+    /// the page title in its window title. When the window title is unknown,
+    /// a recent report is used as is.
+    pub fn current(&self, window_title: &str) -> Option<BrowserTab> {
+        let latest = self.latest.lock().unwrap();
+        let (report, received) = latest.as_ref()?;
+        let domain = domain(&report.url)?;
+        let title_known = !window_title.is_empty() && window_title != "Unknown";
+        let matches = if title_known {
+            !report.title.is_empty() && window_title.contains(report.title.trim())
+        } else {
+            received.elapsed() <= UNTITLED_MAX_AGE
+        };
+        matches.then(|| BrowserTab {
+            url: report.url.clone(),
+            domain,
+        })
+    }
+}
+
+/// Host of `url` without a leading "www.".
+pub fn domain(url: &str) -> Option<String> {
+    url_host(url).map(|host| host.trim_start_matches("www.").to_string())
+}
+
+/// Whether `app_name` is a browser, whose tab the extension should report.
+pub fn is_browser(app_name: &str) -> bool {
+    let name = app_name.trim().to_lowercase();
+    let name = name.strip_suffix(".exe").unwrap_or(&name);
+    BROWSER_APPS
+        .iter()
+        .any(|app| name == *app || name.starts_with(&format!("{} ", app)))
+}
+
+/// Whether the domain of `tab` is one of `excluded` (or a subdomain of one).
+pub fn is_excluded(tab: &BrowserTab, excluded: &[String]) -> bool {
+    excluded.iter().any(|domain| {
+        let domain = domain.trim().trim_start_matches("*.").to_lowercase();
+        !domain.is_empty() && host_in_domain(&tab.domain, &domain)
+    })
+}
+
+/// Accept tab reports from the browser extension on `127.0.0.1:port` until
+/// `stop_rx` turns true.
+pub async fn serve(port: u16, tracker: Arc<TabTracker>, mut stop_rx: watch::Receiver<bool>) -> Result<()> {
+    let listener = TcpListener::bind(("127.0.0.1", port))
+        .await
+        .map_err(|e| anyhow!("Failed to listen on 127.0.0.1:{}: {}", port, e))?;
+    log::info!("Browser integration listening on 127.0.0.1:{}", port);
+
+    loop {
+        tokio::select! {
+            accepted = listener.accept() => {
+                let (stream, _) = match accepted {
+                    Ok(conn) => conn,
+                    Err(e) => {
+                        log::warn!("Browser integration: failed to accept connection: {}", e);
+                        continue;
+                    }
+                };
+                let tracker = tracker.clone();
+                tokio::spawn(async move {
+                    if let Err(e) = handle(stream, &tracker).await {
+                        log::debug!("Browser integration: bad request: {}", e);
+                    }
+                });
+            }
+            _ = stop_rx.changed() => {
+                if *stop_rx.borrow() {
+                    log::info!("Browser integration stopped");
+                    return Ok(());
+                }
+            }
+        }
+    }
+}
+
+async fn handle(mut stream: TcpStream, tracker: &TabTracker) -> Result<()> {
+    let result = match timeout(Duration::from_secs(5), read_request(&mut stream)).await {
+        Ok(request) => request,
+        Err(_) => Err(anyhow!("request timed out")),
+    };
+    let status = match &result {
+        Ok(Request { extension: false, .. }) => "403 Forbidden",
+        Ok(Request { path, .. }) if path != "/tab" => "404 Not Found",
+        Ok(Request { method, .. }) if method != "POST" => "405 Method Not Allowed",
+        Ok(request) => match serde_json::from_slice::<TabReport>(&request.body) {
+            Ok(report) => {
+                tracker.record(report);
+                "204 No Content"
+            }
+            Err(_) => "400 Bad Request",
+        },
+        Err(_) => "400 Bad Request",
+    };
+    let response = format!("HTTP/1.1 {}\r\nContent-Length: 0\r\nConnection: close\r\n\r\n", status);
+    stream.write_all(response.as_bytes()).await?;
+    result.map(|_| ())
+}
+
+struct Request {
+    method: String,
+    path: String,
+    /// Sent by the extension: has the extension header and no web page origin.
+    extension: bool,
+    body: Vec<u8>,
+}
+
+async fn read_request(stream: &mut TcpStream) -> Result<Request> {
+    let mut buf = Vec::new();
+    let mut chunk = [0u8; 4096];
+    let header_end = loop {
+        if let Some(pos) = buf.windows(4).position(|w| w == b"\r\n\r\n") {
+            break pos + 4;
+        }
+        if buf.len() > MAX_REQUEST_BYTES {
+            return Err(anyhow!("headers too large"));
+        }
+        let n = stream.read(&mut chunk).await?;
+        if n == 0 {
+            return Err(anyhow!("connection closed"));
+        }
+        buf.extend_from_slice(&chunk[..n]);
+    };
+
+    let head = String::from_utf8_lossy(&buf[..header_end]).to_string();
+    let mut lines = head.lines();
+    let mut request_line = lines.next().unwrap_or_default().split_whitespace();
+    let method = request_line.next().unwrap_or_default().to_string();
+    let path = request_line.next().unwrap_or_default().to_string();
+
+    let mut content_length = 0;
+    let mut has_header = false;
+    let mut web_origin = false;
+    for line in lines {
+        let Some((name, value)) = line.split_once(':') else {
+            continue;
+        };
+        let (name, value) = (name.trim().to_ascii_lowercase(), value.trim());
+        match name.as_str() {
+            "content-length" => content_length = value.parse().map_err(|_| anyhow!("bad length"))?,
+            EXTENSION_HEADER => has_header = true,
+            "origin" => web_origin = value.starts_with("http://") || value.starts_with("https://"),
+            _ => {}
+        }
+    }
+    if content_length > MAX_REQUEST_BYTES {
+        return Err(anyhow!("body too large"));
+    }
+
+    let mut body = buf[header_end..].to_vec();
+    while body.len() < content_length {
+        let n = stream.read(&mut chunk).await?;
+        if n == 0 {
+            return Err(anyhow!("connection closed"));
+        }
+        body.extend_from_slice(&chunk[..n]);
+    }
+    body.truncate(content_length);
+
+    Ok(Request {
+        method,
+        path,
+        extension: has_header && !web_origin,
+        body,
+    })
+}
@@ -0,0 +1,53 @@
+use std::collections::HashMap;
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, Instant};
+
+/// Cooldown after the first failure; doubles with every further failure in a row.
+const BASE_COOLDOWN: Duration = Duration::from_secs(60);
+const MAX_COOLDOWN: Duration = Duration::from_secs(30 * 60);
+
+struct Breaker {
+    failures: u32,
+    open_until: Instant,
+}
+
+/// Shared across clients: a new `LlmClient` is built for every batch tick, but a
+/// provider that just failed should stay skipped.
+fn breakers() -> &'static Mutex<HashMap<String, Breaker>> {
+    static BREAKERS: OnceLock<Mutex<HashMap<String, Breaker>>> = OnceLock::new();
+    BREAKERS.get_or_init(|| Mutex::new(HashMap::new()))
+}
+
+/// Remaining cooldown of `key`, or `None` if requests may be sent.
+pub fn cooldown(key: &str) -> Option<Duration> {
+    let breakers = breakers().lock().unwrap();
+    let breaker = breakers.get(key)?;
+    breaker
+        .open_until
+        .checked_duration_since(Instant::now())
+        .filter(|d| !d.is_zero())
+}
+
+/// Whether the last request to `key` failed, in cooldown or not.
+pub fn is_failing(key: &str) -> bool {
+    breakers().lock().unwrap().contains_key(key)
+}
+
+pub fn record_success(key: &str) {
+    breakers().lock().unwrap().remove(key);
+}
+
+/// Put `key` into cooldown and return how long it lasts.
+pub fn record_failure(key: &str) -> Duration {
+    let mut breakers = breakers().lock().unwrap();
+    let breaker = breakers.entry(key.to_string()).or_insert(Breaker {
+        failures: 0,
+        open_until: Instant::now(),
+    });
+    breaker.failures += 1;
+    let cooldown = BASE_COOLDOWN
+        .saturating_mul(1 << (breaker.failures - 1).min(5))
+        .min(MAX_COOLDOWN);
+    breaker.open_until = Instant::now() + cooldown;
+    cooldown
+}
@@ -0,0 +1,236 @@
+use anyhow::Result;
+use chrono::DateTime;
+use std::path::Path;
+
+use crate::services::dev_context;
+use crate::services::git;
+use crate::storage::config::AppConfig;
+
+/// Separates commits and fields in the `git log` output.
+const RECORD_SEPARATOR: char = '\u{1e}';
+const FIELD_SEPARATOR: char = '\u{1f}';
+
+/// A commit made on the digest's day in one of the configured repositories.
+#[derive(Debug, Clone)]
+pub struct Commit {
+    /// Folder name of the repository.
+    pub repo: String,
+    pub hash: String,
+    /// Local author time, "HH:MM".
+    pub time: String,
+    pub subject: String,
+    pub files_changed: u32,
+    pub insertions: u32,
+    pub deletions: u32,
+}
+
+/// Commits of `date` by the configured author in every configured repository,
+/// oldest first. Repositories that cannot be read are skipped with a warning.
+pub async fn for_date(config: &AppConfig, date: &str) -> Vec<Commit> {
+    let mut commits = Vec::new();
+    for repo in config.git_repos.iter().map(|r| r.trim()).filter(|r| !r.is_empty()) {
+        match repo_commits(Path::new(repo), &config.git_author, date).await {
+            Ok(found) => commits.extend(found),
+            Err(e) => log::warn!("Failed to read commits of {}: {}", repo, e),
+        }
+    }
+    commits.sort_by(|a, b| a.time.cmp(&b.time));
+    commits
+}
+
+async fn repo_commits(repo: &Path, author: &str, date: &str) -> Result<Vec<Commit>> {
+    // Without a configured author, use the one this repository commits as
+    let author = match author.trim() {
+        "" => git::run(repo, &["config", "user.email"])
+            .await
+            .map(|email| email.trim().to_string())
+            .unwrap_or_default(),
+        author => author.to_string(),
+    };
+    // --since filters on the committer date, which is never before the author
+    // date of a normal commit: it only narrows the search. Commits rebased or
+    // amended later are still included, so the day is checked on the author
+    // time when parsing.
+    let since = format!("--since={} 00:00:00", date);
+    let format = format!(
+        "--format={}%h{}%aI{}%s",
+        RECORD_SEPARATOR, FIELD_SEPARATOR, FIELD_SEPARATOR
+    );
+    let mut args = vec![
+        "log",
+        "--all",
+        "--no-merges",
+        "--shortstat",
+        since.as_str(),
+        format.as_str(),
+    ];
+    let author_arg = format!("--author={}", author);
+    if !author.is_empty() {
+        args.push(&author_arg);
+    }
+    let output = git::run(repo, &args).await?;
+    let name = dev_context::repo_name(&repo.to_string_lossy()).to_string();
+    Ok(parse_log(&output, &name, date))
+}
+
+/// Parse `git log --shortstat` output written with the separators above,
+/// keeping the commits authored on `date` (local time).
+fn parse_log(output: &str, repo: &str, date: &str) -> Vec<Commit> {
+    output
+        .split(RECORD_SEPARATOR)
+        .filter_map(|record| {
+            let mut lines = record.lines();
+            let mut fields = lines.next()?.splitn(3, FIELD_SEPARATOR);
+            let hash = fields.next()?.to_string();
+            let time = DateTime::parse_from_rfc3339(fields.next()?)
+                .ok()?
+                .with_timezone(&chrono::Local);
+            if time.format("%Y-%m-%d").to_string() != date {
+                return None;
+            }
+            let subject = fields.next()?.to_string();
+            let mut commit = Commit {
+                repo: repo.to_string(),
+                hash,
+                time: time.format("%H:%M").to_string(),
+                subject,
+                files_changed: 0,
+                insertions: 0,
+                deletions: 0,
+            };
+            // " 3 files changed, 10 insertions(+), 2 deletions(-)"
+            if let Some(stat) = lines.find(|line| line.contains("changed")) {
+                for part in stat.split(',') {
+                    let part = part.trim();
+                    let count = part
+                        .split_whitespace()
+                        .next()
+                        .and_then(|n| n.parse().ok())
+                        .unwrap_or(0);
+                    if part.contains("changed") {
+                        commit.files_changed = count;
+                    } else if part.contains("insertion") {
+                        commit.insertions = count;
+                    } else if part.contains("deletion") {
+                        commit.deletions = count;
+                    }
+                }
+            }
+            Some(commit)
+        })
+        .collect()
+}
+
+/// One bullet per commit for `{commits}`; empty without commits.
+pub fn to_text(commits: &[Commit]) -> String {
+    commits
+        .iter()
+        .map(|c| {
+            format!(
+                "- {} {} {}: {} ({} files, +{} -{})\n",
+                c.time, c.repo, c.hash, c.subject, c.files_changed, c.insertions, c.deletions
+            )
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    /// Commit a file change in `repo` with fixed author and committer dates.
+    fn commit(repo: &Path, email: &str, authored: &str, committed: &str, subject: &str) {
+        std::fs::write(repo.join("file.txt"), subject).unwrap();
+        let git = |args: &[&str]| {
+            let status = std::process::Command::new("git")
+                .arg("-C")
+                .arg(repo)
+                .args(args)
+                .env("GIT_AUTHOR_NAME", "Test")
+                .env("GIT_AUTHOR_EMAIL", email)
+                .env("GIT_AUTHOR_DATE", authored)
+                .env("GIT_COMMITTER_NAME", "Test")
+                .env("GIT_COMMITTER_EMAIL", email)
+                .env("GIT_COMMITTER_DATE", committed)
+                .status()
+                .unwrap();
+            assert!(status.success());
+        };
+        git(&["add", "file.txt"]);
+        git(&["commit", "-q", "-m", subject]);
+    }
+
+    fn test_repo() -> PathBuf {
+        let repo = std::env::temp_dir().join(format!("diaroo-test-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&repo).unwrap();
+        let status = std::process::Command::new("git")
+            .arg("-C")
+            .arg(&repo)
+            .args(["init", "-q"])
+            .status()
+            .unwrap();
+        assert!(status.success());
+        repo
+    }
+
+    #[tokio::test]
+    async fn for_date_uses_author_time_and_author_filter() {
+        let repo = test_repo();
+        // (author, authored, committed, subject)
+        for (email, authored, committed, subject) in [
+            ("me@example.com", "2024-03-04T18:00:00", "2024-03-05T09:00:00", "Day before"),
+            ("me@example.com", "2024-03-05T10:30:00", "2024-03-05T10:30:00", "Same day"),
+            ("me@example.com", "2024-03-05T16:00:00", "2024-03-07T08:00:00", "Rebased later"),
+            ("other@example.com", "2024-03-05T11:00:00", "2024-03-05T11:00:00", "Someone else"),
+        ] {
+            commit(&repo, email, authored, committed, subject);
+        }
+
+        let mut config = AppConfig {
+            git_repos: vec![repo.to_string_lossy().to_string()],
+            git_author: "me@example.com".to_string(),
+            ..Default::default()
+        };
+        let commits = for_date(&config, "2024-03-05").await;
+        let subjects: Vec<&str> = commits.iter().map(|c| c.subject.as_str()).collect();
+        assert_eq!(subjects, ["Same day", "Rebased later"]);
+        assert_eq!(commits[0].time, "10:30");
+        assert_eq!(commits[0].files_changed, 1);
+
+        config.git_author = "other@example.com".to_string();
+        let commits = for_date(&config, "2024-03-05").await;
+        assert_eq!(commits.len(), 1);
+        assert_eq!(commits[0].subject, "Someone else");
+
+        let _ = std::fs::remove_dir_all(&repo);
+    }
+
+    fn stat(commit: &Commit) -> (u32, u32, u32) {
+        (commit.files_changed, commit.insertions, commit.deletions)
+    }
+
+    #[test]
+    fn parse_log_reads_shortstat() {
+        let output = format!(
+            "{r}abc1234{f}2024-03-05T11:00:00+00:00{f}Add parser\n\n 3 files changed, 10 insertions(+), 2 deletions(-)\n\
+             {r}def5678{f}2024-03-05T11:00:00+00:00{f}Remove dead code\n\n 1 file changed, 4 deletions(-)\n\
+             {r}0123abc{f}2024-03-05T11:00:00+00:00{f}Empty commit\n\
+             {r}4567def{f}2024-03-09T11:00:00+00:00{f}Another day\n",
+            r = RECORD_SEPARATOR,
+            f = FIELD_SEPARATOR,
+        );
+        let date = DateTime::parse_from_rfc3339("2024-03-05T11:00:00+00:00")
+            .unwrap()
+            .with_timezone(&chrono::Local)
+            .format("%Y-%m-%d")
+            .to_string();
+        let commits = parse_log(&output, "repo", &date);
+        assert_eq!(commits.len(), 3);
+        assert_eq!(stat(&commits[0]), (3, 10, 2));
+        assert_eq!(stat(&commits[1]), (1, 0, 4));
+        assert_eq!(stat(&commits[2]), (0, 0, 0));
+        assert!(commits.iter().all(|c| c.repo == "repo"));
+        assert_eq!(commits[1].subject, "Remove dead code");
+    }
+}
@@ -0,0 +1,198 @@
+use std::path::{Path, PathBuf};
+use xcap::Window;
+
+use crate::services::git;
+use crate::storage::config::AppConfig;
+
+/// How long the process table, read to find deeper descendants of the
+/// focused process, is reused.
+#[cfg(target_os = "linux")]
+const PROCESS_TABLE_TTL: std::time::Duration = std::time::Duration::from_secs(30);
+
+/// Repository the focused terminal or editor is working in.
+#[derive(Debug, Clone)]
+pub struct DevContext {
+    pub repo_path: String,
+    pub branch: String,
+    /// Subject of the last commit on the branch.
+    pub commit_subject: String,
+}
+
+/// Adds the git repository, branch and last commit of the focused terminal or
+/// IDE window to a capture. The repository is found from the working
+/// directory of the window's process or, for terminals, of the most recently
+/// started process inside it (the shell or the editor running in it).
+pub struct DevContextEnricher {
+    /// Lowercase app names treated as terminals and IDEs.
+    apps: Vec<String>,
+}
+
+impl DevContextEnricher {
+    /// `None` when developer context is disabled.
+    pub fn new(config: &AppConfig) -> Option<Self> {
+        config.dev_context_enabled.then(|| Self {
+            apps: config
+                .dev_context_apps
+                .iter()
+                .map(|app| app.trim().to_lowercase())
+                .filter(|app| !app.is_empty())
+                .collect(),
+        })
+    }
+
+    pub async fn enrich(&self) -> Option<DevContext> {
+        let (app_name, pid) = focused_process()?;
+        let app_name = app_name.to_lowercase();
+        if !self.apps.iter().any(|app| app_name.contains(app.as_str())) {
+            return None;
+        }
+
+        let root = repo_root(pid)?;
+        let branch = git::current_branch(&root).unwrap_or_default();
+        let commit_subject = match git::run(&root, &["log", "-1", "--format=%s"]).await {
+            Ok(subject) => subject.trim().to_string(),
+            Err(e) => {
+                log::debug!("Failed to read last commit of {}: {}", root.display(), e);
+                String::new()
+            }
+        };
+        Some(DevContext {
+            repo_path: root.to_string_lossy().to_string(),
+            branch,
+            commit_subject,
+        })
+    }
+}
+
+/// App name and process id of the focused window.
+fn focused_process() -> Option<(String, u32)> {
+    let window = Window::all()
+        .ok()?
+        .into_iter()
+        .find(|w| w.is_focused().unwrap_or(false) && !w.is_minimized().unwrap_or(false))?;
+    Some((window.app_name().ok()?, window.pid().ok()?))
+}
+
+/// Repository the process `pid` works in: from the working directory of its
+/// direct children (most recently started first), then its own, then of its
+/// deeper descendants.
+#[cfg(target_os = "linux")]
+fn repo_root(pid: u32) -> Option<PathBuf> {
+    let in_repo = |id: u32| {
+        std::fs::read_link(format!("/proc/{}/cwd", id))
+            .ok()
+            .and_then(|dir| git::find_repo_root(&dir))
+    };
+    let mut children = direct_children(pid);
+    children.sort_unstable_by(|a, b| b.cmp(a));
+    children
+        .into_iter()
+        .map(|(_, id)| id)
+        .chain(std::iter::once(pid))
+        .find_map(in_repo)
+        .or_else(|| descendants(pid).into_iter().find_map(|(_, id)| in_repo(id)))
+}
+
+/// (start time, pid) of the direct children of `pid`, read from its tasks'
+/// `children` files instead of scanning every process.
+#[cfg(target_os = "linux")]
+fn direct_children(pid: u32) -> Vec<(u64, u32)> {
+    let Ok(tasks) = std::fs::read_dir(format!("/proc/{}/task", pid)) else {
+        return Vec::new();
+    };
+    tasks
+        .flatten()
+        .filter_map(|task| std::fs::read_to_string(task.path().join("children")).ok())
+        .flat_map(|children| {
+            children
+                .split_whitespace()
+                .filter_map(|id| id.parse::<u32>().ok())
+                .collect::<Vec<_>>()
+        })
+        .filter_map(|id| {
+            let stat = std::fs::read_to_string(format!("/proc/{}/stat", id)).ok()?;
+            Some((parse_stat(&stat)?.1, id))
+        })
+        .collect()
+}
+
+/// (start time, pid) of every descendant of `pid`, most recently started
+/// first. The process table is read at most every `PROCESS_TABLE_TTL`.
+#[cfg(target_os = "linux")]
+fn descendants(pid: u32) -> Vec<(u64, u32)> {
+    use std::sync::Mutex;
+    use std::time::Instant;
+
+    static TABLE: Mutex<Option<(Instant, ProcessTable)>> = Mutex::new(None);
+    let mut table = TABLE.lock().unwrap();
+    let stale = table
+        .as_ref()
+        .map_or(true, |(read, _)| read.elapsed() >= PROCESS_TABLE_TTL);
+    if stale {
+        *table = Some((Instant::now(), read_process_table()));
+    }
+    let Some((_, children)) = table.as_ref() else {
+        return Vec::new();
+    };
+
+    let mut found = Vec::new();
+    let mut queue = vec![pid];
+    while let Some(parent) = queue.pop() {
+        for &(started, id) in children.get(&parent).into_iter().flatten() {
+            found.push((started, id));
+            queue.push(id);
+        }
+    }
+    found.sort_unstable_by(|a, b| b.cmp(a));
+    found
+}
+
+/// Children of every process, by parent pid.
+#[cfg(target_os = "linux")]
+type ProcessTable = std::collections::HashMap<u32, Vec<(u64, u32)>>;
+
+#[cfg(target_os = "linux")]
+fn read_process_table() -> ProcessTable {
+    let mut children = ProcessTable::new();
+    let Ok(entries) = std::fs::read_dir("/proc") else {
+        return children;
+    };
+    for entry in entries.flatten() {
+        let Some(id) = entry
+            .file_name()
+            .to_str()
+            .and_then(|n| n.parse::<u32>().ok())
+        else {
+            continue;
+        };
+        if let Some((ppid, started)) = std::fs::read_to_string(entry.path().join("stat"))
+            .ok()
+            .and_then(|stat| parse_stat(&stat))
+        {
+            children.entry(ppid).or_default().push((started, id));
+        }
+    }
+    children
+}
+
+/// Parent pid and start time from `/proc/<pid>/stat`. The command name may
+/// contain spaces, so fields are counted after its closing parenthesis.
+#[cfg(target_os = "linux")]
+fn parse_stat(stat: &str) -> Option<(u32, u64)> {
+    let fields: Vec<&str> = stat[stat.rfind(')')? + 1..].split_whitespace().collect();
+    Some((fields.get(1)?.parse().ok()?, fields.get(19)?.parse().ok()?))
+}
+
+/// Reading another process's working directory is only supported on Linux.
+#[cfg(not(target_os = "linux"))]
+fn repo_root(_pid: u32) -> Option<PathBuf> {
+    None
+}
+
+/// Short name of a repository for prompts: its folder name.
+pub fn repo_name(repo_path: &str) -> &str {
+    Path::new(repo_path)
+        .file_name()
+        .and_then(|name| name.to_str())
+        .unwrap_or(repo_path)
+}
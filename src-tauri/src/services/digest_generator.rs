@@ -1,15 +1,52 @@
 use anyhow::Result;
 use chrono::Local;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
 
-use crate::services::activity_log::{ActivityEntry, ActivityLog};
+use crate::services::activity_log::{ActivityEntry, ActivityLog, Observation, RepoUsage};
+use crate::services::browser_tab;
+use crate::services::commits;
+use crate::services::dev_context;
+use crate::services::extraction::{self, STRUCTURED_OUTPUT_INSTRUCTIONS};
+use crate::services::jobs::{CancelToken, Cancelled};
+use crate::services::keyframes;
 use crate::services::llm_client::LlmClient;
-use crate::storage::config::AppConfig;
+use crate::services::meetings;
+use crate::services::note_export::{self, NoteMetadata};
+use crate::services::prompt_template::{self, TemplateKind, Variables};
+use crate::services::redaction::{self, Redactor};
+use crate::services::summarization::{self, Section};
+use crate::services::timesheet;
+use crate::storage::config::{AppConfig, LlmStep};
+use crate::storage::profiles::DigestProfile;
+use crate::storage::projects::Project;
+use crate::storage::report_history::{self, ReportMeta};
 use crate::storage::screenshot_store::ScreenshotStore;
 
 /// OpenRouter free models limit image uploads to 10 per request.
-const MAX_IMAGES_PER_REQUEST: usize = 10;
+pub const MAX_IMAGES_PER_REQUEST: usize = 10;
+
+/// Entries per request when only the most distinct frames are sent as images.
+const SAMPLED_CHUNK_SIZE: usize = 120;
+
+/// Longer gaps between screenshots are shown as "15+ min": the user was
+/// probably away or monitoring was paused.
+const MAX_FOCUS_MINUTES: i64 = 15;
+/// Websites listed in the digest usage section, most visited first.
+const MAX_WEBSITES: usize = 15;
+
+/// Screen text beyond this many characters per screenshot is cut off.
+const MAX_SCREEN_TEXT_CHARS: usize = 2000;
+
+/// Batch summaries in a Q&A prompt are cut to this many characters, keeping
+/// the most recent ones.
+const MAX_QA_SUMMARY_CHARS: usize = 60_000;
+
+/// Added to the extract prompt when screen text is sent instead of images.
+pub const SCREEN_TEXT_ONLY_NOTE: &str = "\
+NOTE: The screenshots are not attached. Each screenshot in the activity log is \
+followed by the text read from it by OCR; base your answer on that text, the \
+window titles and the app names.";
 
 pub const DEFAULT_EXTRACT_PROMPT: &str = "\
 You are analyzing screenshots from a computer activity monitoring system.
@@ -29,6 +66,23 @@ After extracting information from all screenshots, create a timeline summary (2-
 
 CRITICAL: Focus on reading visible text accurately. If you cannot confidently identify an application, describe what you see instead (e.g., \"code editor with dark theme\" rather than guessing \"VSCode\").";
 
+/// Extract prompt for providers that cannot see images (the CLIs).
+pub const TEXT_EXTRACT_PROMPT: &str = "\
+You are summarizing computer activity from a monitoring system. No screenshots are available: work only from the activity log below. Each line gives the time, application and window title of a screen change and how long it stayed in focus; \"Screen text\" lines hold text read from that screen, when available.
+
+Activity log for this batch:
+{activity_log}
+
+TASK: For each screenshot in the log, identify:
+1. Application name
+2. Window title or document name
+3. Timestamp (from activity log)
+4. What the user was most likely doing (1 sentence max)
+
+Then write a timeline summary (2-4 sentences) showing the sequence of applications used and main activities, giving more weight to what stayed in focus longer.
+
+CRITICAL: Only state what the log supports. Do not describe screen content that is not in the window titles or screen text.";
+
 pub const DEFAULT_DIGEST_PROMPT: &str = "\
 Generate a comprehensive daily activity digest report in markdown format based on the provided batch summaries and app usage statistics.
 
@@ -38,6 +92,15 @@ Generate a comprehensive daily activity digest report in markdown format based o
 ## App Usage
 {app_usage}
 
+## Projects
+{projects}
+
+## Commits
+{commits}
+
+## Meetings
+{meetings}
+
 ## Report Requirements
 
 ### Structure
@@ -57,6 +120,8 @@ Format the report with the following sections:
    - Group related activities that occurred within the same time window
    - Include tool/application names and specific tasks (e.g., \"Tauri Pet development in Terminal\")
    - Mention concrete details like error codes, file types, or specific features being worked on
+   - Place commits from the Commits section at their time, next to the work they came from
+   - Show meetings from the Meetings section as time ranges with their duration
 
 3. **Focus Analysis**
    - Divide into clear subsections with bold headers:
@@ -80,63 +145,401 @@ Format the report with the following sections:
 - Synthesize information across both batch summaries and app usage data
 - Identify patterns and themes rather than listing every action
 - Highlight context-switching behavior when present
-- Note communication platforms used for meetings
+- Note communication platforms used for meetings and the total time spent in them
 - Reference specific technical work (coding, debugging, API integration)
 - Acknowledge breaks and leisure activities naturally
 - Provide actionable insights in the Focus Analysis section
 
 Date: {date}";
 
+/// `provider` of batches summarized without an LLM.
+const TEXT_ONLY_PROVIDER: &str = "text-only";
+
+const QA_PROMPT: &str = "\
+You answer questions about the user's computer activity. Use only the reports and activity summaries below; say so when they do not contain the answer.
+
+Date: {date}
+
+{context}
+
+Question: {question}";
+
+const PROJECT_CLASSIFY_PROMPT: &str = "\
+You are assigning a block of computer activity to one of the user's projects.
+
+Projects (id: name):
+{projects}
+
+Activity log:
+{activity_log}
+
+Activity summary:
+{summary}
+
+Reply with only the id of the matching project, or \"none\" if no project clearly fits.";
+
 pub struct DigestGenerator;
 
 impl DigestGenerator {
-    fn load_digest_prompt() -> String {
-        let path = AppConfig::prompt_path();
-        if !path.exists() {
-            if let Some(parent) = path.parent() {
-                let _ = std::fs::create_dir_all(parent);
-            }
-            let _ = std::fs::write(&path, DEFAULT_DIGEST_PROMPT);
+    /// Wait for the processing lock of `date`. Callers that arrive while another
+    /// batch or digest run holds it queue up behind it and then see its results.
+    async fn lock_day(
+        activity_log: &Arc<ActivityLog>,
+        date: &str,
+        cancel: &CancelToken,
+    ) -> Result<tokio::sync::OwnedMutexGuard<()>> {
+        let lock = activity_log.day_lock(date);
+        if let Ok(guard) = lock.clone().try_lock_owned() {
+            return Ok(guard);
         }
-        match std::fs::read_to_string(&path) {
-            Ok(content) if !content.trim().is_empty() => content,
-            _ => DEFAULT_DIGEST_PROMPT.to_string(),
+        log::info!("Waiting for in-progress processing of {} to finish", date);
+        tokio::select! {
+            guard = lock.lock_owned() => Ok(guard),
+            _ = cancel.cancelled() => Err(Cancelled.into()),
         }
     }
 
-    fn load_extract_prompt() -> String {
-        let path = AppConfig::extract_prompt_path();
-        if !path.exists() {
-            if let Some(parent) = path.parent() {
-                let _ = std::fs::create_dir_all(parent);
+    /// Template variables for a batch extract prompt. `frames[i]` tells whether
+    /// the screenshot of `entries[i]` is sent (as an image, or as its screen text
+    /// in OCR "instead" mode); in structured mode those lines are numbered so
+    /// observations can be mapped back. With `screen_text`, OCR text follows
+    /// each entry.
+    pub fn extract_variables(
+        entries: &[ActivityEntry],
+        frames: &[bool],
+        structured: bool,
+        screen_text: bool,
+    ) -> Variables {
+        let mut context_lines: Vec<String> = Vec::new();
+        let mut items = Vec::new();
+        let mut image_index = 0;
+        for (i, entry) in entries.iter().enumerate() {
+            let focus = Self::focus_duration(entry, entries.get(i + 1));
+            let mut line = format!(
+                "[{}] {} - {}",
+                entry.timestamp, entry.app_name, entry.window_title
+            );
+            if !entry.url.is_empty() {
+                line.push_str(&format!(" <{}>", Self::short_url(&entry.url)));
+            }
+            if !entry.repo_path.is_empty() {
+                line.push_str(&format!(
+                    " [repo {} on {}]",
+                    dev_context::repo_name(&entry.repo_path),
+                    entry.git_branch
+                ));
+            }
+            if !focus.is_empty() {
+                line.push_str(&format!(" (in focus {})", focus));
             }
-            let _ = std::fs::write(&path, DEFAULT_EXTRACT_PROMPT);
+            if frames[i] {
+                image_index += 1;
+            }
+            if structured && frames[i] {
+                context_lines.push(format!("Screenshot {}: {}", image_index, line));
+            } else {
+                context_lines.push(line);
+            }
+            let text = if screen_text {
+                Self::screen_text(&entry.ocr_text)
+            } else {
+                String::new()
+            };
+            if !text.is_empty() {
+                context_lines.push(format!("  Screen text: {}", text));
+            }
+
+            let mut item = Variables::new();
+            item.set("index", (i + 1).to_string())
+                .set("timestamp", entry.timestamp.clone())
+                .set("app", entry.app_name.clone())
+                .set("title", entry.window_title.clone())
+                .set("url", entry.url.clone())
+                .set("repo", dev_context::repo_name(&entry.repo_path))
+                .set("branch", entry.git_branch.clone())
+                .set("focus", focus)
+                .set("screen_text", text);
+            items.push(item);
+        }
+
+        let now = Local::now();
+        let mut vars = Variables::new();
+        vars.set("activity_log", context_lines.join("\n"))
+            .set("date", now.format("%Y-%m-%d").to_string())
+            .set("weekday", now.format("%A").to_string())
+            .set("time", now.format("%H:%M").to_string())
+            .set("screenshot_count", image_index.to_string())
+            .set_list("entries", items);
+        vars
+    }
+
+    /// URL without its query and fragment, which are long and rarely useful.
+    fn short_url(url: &str) -> &str {
+        url.split(['?', '#']).next().unwrap_or(url)
+    }
+
+    /// Time until the next screenshot ("40s", "3 min"); empty for the last one.
+    fn focus_duration(entry: &ActivityEntry, next: Option<&ActivityEntry>) -> String {
+        let Some(next) = next else {
+            return String::new();
+        };
+        let (Ok(start), Ok(end)) = (
+            chrono::DateTime::parse_from_rfc3339(&entry.timestamp),
+            chrono::DateTime::parse_from_rfc3339(&next.timestamp),
+        ) else {
+            return String::new();
+        };
+        let secs = (end - start).num_seconds();
+        if secs <= 0 {
+            String::new()
+        } else if secs < 60 {
+            format!("{}s", secs)
+        } else if secs / 60 >= MAX_FOCUS_MINUTES {
+            format!("{}+ min", MAX_FOCUS_MINUTES)
+        } else {
+            format!("{} min", secs / 60)
+        }
+    }
+
+    /// OCR text of one screenshot on a single line, cut to `MAX_SCREEN_TEXT_CHARS`.
+    fn screen_text(ocr_text: &str) -> String {
+        let text = ocr_text
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty())
+            .collect::<Vec<_>>()
+            .join(" / ");
+        match text.char_indices().nth(MAX_SCREEN_TEXT_CHARS) {
+            Some((end, _)) => format!("{}...", &text[..end]),
+            None => text,
         }
-        match std::fs::read_to_string(&path) {
-            Ok(content) if !content.trim().is_empty() => content,
-            _ => DEFAULT_EXTRACT_PROMPT.to_string(),
+    }
+
+    /// Render the configured extract template, plus the JSON schema in structured mode.
+    pub fn build_extract_prompt(config: &AppConfig, vars: &Variables) -> Result<String> {
+        let mut vars = vars.clone();
+        vars.set("has_images", Self::has_images(config.ocr_mode != "instead"));
+        let mut prompt = prompt_template::render_named(
+            TemplateKind::Extract,
+            &config.extract_template,
+            DEFAULT_EXTRACT_PROMPT,
+            &vars,
+        )?
+        .text;
+        if config.ocr_mode == "instead" {
+            prompt.push_str("\n\n");
+            prompt.push_str(SCREEN_TEXT_ONLY_NOTE);
         }
+        if config.extraction_mode == "structured" {
+            prompt.push_str("\n\n");
+            prompt.push_str(STRUCTURED_OUTPUT_INSTRUCTIONS);
+        }
+        Ok(prompt)
+    }
+
+    /// Extract prompt for providers without image input, from the same variables.
+    /// The configured template is rendered with `has_images` empty; the built-in
+    /// default is worded for text only.
+    pub fn build_text_extract_prompt(config: &AppConfig, vars: &Variables) -> Result<String> {
+        let mut vars = vars.clone();
+        vars.set("has_images", Self::has_images(false));
+        let mut prompt = prompt_template::render_named_or_builtin(
+            TemplateKind::Extract,
+            &config.extract_template,
+            TEXT_EXTRACT_PROMPT,
+            &vars,
+        )?
+        .text;
+        if config.extraction_mode == "structured" {
+            prompt.push_str("\n\n");
+            prompt.push_str(STRUCTURED_OUTPUT_INSTRUCTIONS);
+        }
+        Ok(prompt)
+    }
+
+    /// Value of the `has_images` template variable: non-empty when screenshots
+    /// are attached, so templates can branch with `{#if has_images}`.
+    pub fn has_images(sent: bool) -> &'static str {
+        if sent {
+            "yes"
+        } else {
+            ""
+        }
+    }
+
+    /// Template variables for the digest of `date`.
+    pub fn digest_variables(
+        activity_log: &Arc<ActivityLog>,
+        config: &AppConfig,
+        date: &str,
+    ) -> Result<Variables> {
+        let batches = activity_log.get_batches_for_date(date)?;
+        let entries = activity_log.get_entries_for_date(date)?;
+        let app_usage = timesheet::time_by(&entries, |e| Some(e.app_name.clone()));
+
+        let mut batch_text = String::new();
+        let mut batch_items = Vec::new();
+        for (i, batch) in batches.iter().enumerate() {
+            batch_text.push_str(&format!(
+                "## Batch at {}\n{}\n\n",
+                batch.timestamp, batch.summary
+            ));
+            let mut item = Variables::new();
+            item.set("index", (i + 1).to_string())
+                .set("timestamp", batch.timestamp.clone())
+                .set("summary", batch.summary.clone());
+            batch_items.push(item);
+        }
+
+        let mut usage_text = String::new();
+        let mut app_items = Vec::new();
+        for (i, (app, secs)) in app_usage.iter().enumerate() {
+            let minutes = secs / 60;
+            usage_text.push_str(&format!("- {}: ~{} min\n", app, minutes));
+            let mut item = Variables::new();
+            item.set("index", (i + 1).to_string())
+                .set("name", app.clone())
+                .set("minutes", minutes.to_string());
+            app_items.push(item);
+        }
+
+        let project_text = Self::project_usage_text(activity_log, config, date)?;
+        if !project_text.is_empty() {
+            usage_text.push_str("\nBy project:\n");
+            usage_text.push_str(&project_text);
+        }
+
+        let mut website_text = String::new();
+        for (domain, secs) in timesheet::time_by(&entries, |e| browser_tab::domain(&e.url))
+            .iter()
+            .take(MAX_WEBSITES)
+        {
+            website_text.push_str(&format!("- {}: ~{} min\n", domain, secs / 60));
+        }
+        if !website_text.is_empty() {
+            usage_text.push_str("\nBy website:\n");
+            usage_text.push_str(&website_text);
+        }
+
+        let repo_text =
+            Self::repo_usage_text(&entries, &activity_log.get_repo_usage_for_date(date)?);
+        if !repo_text.is_empty() {
+            usage_text.push_str("\nBy repository:\n");
+            usage_text.push_str(&repo_text);
+        }
+
+        let tracked_secs: i64 = app_usage.iter().map(|(_, secs)| secs).sum();
+        let top_apps = app_usage
+            .iter()
+            .filter(|(app, _)| !app.is_empty())
+            .take(3)
+            .map(|(app, _)| app.as_str())
+            .collect::<Vec<_>>()
+            .join(", ");
+        let weekday = chrono::NaiveDate::parse_from_str(date, "%Y-%m-%d")
+            .map(|d| d.format("%A").to_string())
+            .unwrap_or_default();
+
+        let mut vars = Variables::new();
+        vars.set("batch_summaries", batch_text)
+            .set("app_usage", usage_text)
+            .set("projects", project_text)
+            .set("websites", website_text)
+            .set("repos", repo_text)
+            .set(
+                "meetings",
+                meetings::to_text(&meetings::for_date(activity_log, config, date)?),
+            )
+            .set("date", date)
+            .set("weekday", weekday)
+            .set("hours_tracked", format!("{:.1}", tracked_secs as f64 / 3600.0))
+            .set("top_apps", top_apps)
+            .set("batch_count", batches.len().to_string())
+            .set_list("batches", batch_items)
+            .set_list("apps", app_items);
+        Ok(vars)
+    }
+
+    /// Replace the batch variables with condensed sections. `batch_count` keeps
+    /// the number of real batches.
+    fn set_condensed_batches(vars: &mut Variables, sections: &[Section]) {
+        let mut text = String::new();
+        let mut items = Vec::new();
+        for (i, section) in sections.iter().enumerate() {
+            text.push_str(&format!("## Activity {}\n{}\n\n", section.label(), section.text));
+            let mut item = Variables::new();
+            item.set("index", (i + 1).to_string())
+                .set("timestamp", section.label())
+                .set("summary", section.text.clone());
+            items.push(item);
+        }
+        vars.set("batch_summaries", text).set_list("batches", items);
     }
 
     /// Process unbatched screenshots in chunks of MAX_IMAGES_PER_REQUEST,
     /// sending each chunk as its own LLM request.
+    /// On cancellation, chunks already committed are kept; the in-flight chunk is
+    /// left unbatched so the next run picks it up again.
     pub async fn process_batch(
         activity_log: &Arc<ActivityLog>,
         screenshot_store: &ScreenshotStore,
         llm_client: &LlmClient,
+        config: &AppConfig,
+        cancel: &CancelToken,
+    ) -> Result<Option<String>> {
+        // Unbatched entries live in the current day's database
+        let date = activity_log.current_date()?;
+        let _guard = Self::lock_day(activity_log, &date, cancel).await?;
+        Self::process_batch_locked(
+            activity_log,
+            screenshot_store,
+            llm_client,
+            config,
+            &date,
+            cancel,
+        )
+        .await
+    }
+
+    /// Body of `process_batch`; the caller must hold the processing lock of
+    /// `date`, the day of the current database.
+    async fn process_batch_locked(
+        activity_log: &Arc<ActivityLog>,
+        screenshot_store: &ScreenshotStore,
+        llm_client: &LlmClient,
+        config: &AppConfig,
+        date: &str,
+        cancel: &CancelToken,
     ) -> Result<Option<String>> {
+        // Read entries only after taking the lock so a run that just finished
+        // is not processed again.
         let entries = activity_log.get_unbatched_entries()?;
         if entries.is_empty() {
             return Ok(None);
         }
+        // Past midnight the entries belong to a day this run does not hold
+        if activity_log.current_date()? != date {
+            log::info!("Day rolled over, leaving new entries to the next batch run");
+            return Ok(None);
+        }
 
-        let chunks: Vec<&[ActivityEntry]> = entries.chunks(MAX_IMAGES_PER_REQUEST).collect();
+        let chunks: Vec<&[ActivityEntry]> = entries.chunks(Self::chunk_size(config)).collect();
         let total_chunks = chunks.len();
         let mut last_summary = None;
 
         for (i, chunk) in chunks.into_iter().enumerate() {
+            cancel.check()?;
             log::info!("Processing chunk {}/{} ({} entries)", i + 1, total_chunks, chunk.len());
-            let summary = Self::process_chunk(activity_log, screenshot_store, llm_client, chunk).await?;
+            let summary = Self::process_chunk(
+                activity_log,
+                screenshot_store,
+                llm_client,
+                config,
+                date,
+                chunk,
+                cancel,
+            )
+            .await?;
             last_summary = Some(summary);
         }
 
@@ -144,61 +547,146 @@ impl DigestGenerator {
     }
 
     /// Process a single chunk of activity entries: load images, call LLM, store summary,
-    /// and delete the chunk's screenshots.
+    /// and delete the chunk's screenshots. `date` is the day the entries belong to.
     async fn process_chunk(
         activity_log: &Arc<ActivityLog>,
         screenshot_store: &ScreenshotStore,
         llm_client: &LlmClient,
+        config: &AppConfig,
+        date: &str,
         entries: &[ActivityEntry],
+        cancel: &CancelToken,
     ) -> Result<String> {
         let batch_id = uuid::Uuid::new_v4().to_string();
         let entry_count = entries.len() as i64;
+        let structured = config.extraction_mode == "structured";
 
-        // Load all images — each entry passed dedup so every screenshot is a valid keyframe
+        // Load all images — each entry passed dedup so every screenshot is a valid keyframe.
+        // In OCR "instead" mode the screen text stands in for the image.
+        // `frame_entries[i]` is the activity row behind screenshot i + 1.
+        // With keyframes_per_batch, only the most distinct frames are loaded.
+        let send_images = config.ocr_mode != "instead";
+        let mut frames = Self::chunk_frames(entries, config);
         let mut images: Vec<Vec<u8>> = Vec::new();
-        for entry in entries {
-            let path = PathBuf::from(&entry.screenshot_path);
-            if path.exists() {
-                if let Ok(data) = std::fs::read(&path) {
-                    images.push(data);
+        let mut frame_entries: Vec<&ActivityEntry> = Vec::new();
+        for (entry, is_frame) in entries.iter().zip(frames.iter_mut()) {
+            if !*is_frame {
+                continue;
+            }
+            if send_images {
+                match std::fs::read(&entry.screenshot_path) {
+                    Ok(data) => images.push(data),
+                    Err(_) => {
+                        *is_frame = false;
+                        continue;
+                    }
                 }
             }
+            frame_entries.push(entry);
         }
+        // Context built from this chunk's window titles (and screen text)
+        let vars =
+            Self::extract_variables(entries, &frames, structured, config.ocr_mode != "off");
+        let mut redactor = Self::redactor(activity_log, config, date)?;
+        let context = redactor.redact(vars.text("activity_log"));
+        let prompt = redactor.redact(&Self::build_extract_prompt(config, &vars)?);
+        let text_prompt = redactor.redact(&Self::build_text_extract_prompt(config, &vars)?);
+        activity_log.add_redactions_for_date(date, &redactor.take_new())?;
 
-        // Build context from this chunk's window titles
-        let mut context_lines: Vec<String> = Vec::new();
-        for entry in entries {
-            context_lines.push(format!(
-                "[{}] {} - {}",
-                entry.timestamp, entry.app_name, entry.window_title
-            ));
-        }
-        let context = context_lines.join("\n");
-
-        let prompt_template = Self::load_extract_prompt();
-        let prompt = prompt_template.replace("{activity_log}", &context);
-
-        let summary = if !images.is_empty() && !llm_client.api_key_is_empty() {
-            llm_client.send_multimodal(&prompt, &images).await?
+        llm_client.set_activity_ids(entries.iter().map(|e| e.id).collect());
+        let mut observations: Vec<Observation> = Vec::new();
+        let llm_result = if frame_entries.is_empty() || llm_client.api_key_is_empty() {
+            None
+        } else if structured {
+            let result = llm_client
+                .send_extraction(&prompt, &text_prompt, &images, true)
+                .await;
+            Some(result.map(|raw| match extraction::parse_extraction(&raw, frame_entries.len()) {
+                Ok(parsed) => {
+                    observations = parsed
+                        .observations
+                        .iter()
+                        .map(|obs| {
+                            let entry = frame_entries[obs.screenshot - 1];
+                            Observation {
+                                activity_id: entry.id,
+                                // The activity log is authoritative for capture time
+                                timestamp: entry.timestamp.clone(),
+                                app_name: obs.app_name.clone(),
+                                window_title: obs.window_title.clone(),
+                                description: obs.description.clone(),
+                            }
+                        })
+                        .collect();
+                    extraction::render_summary(&parsed)
+                }
+                Err(e) => {
+                    log::warn!("Keeping raw batch summary, structured extraction failed: {}", e);
+                    raw
+                }
+            }))
         } else {
-            format!(
-                "Batch of {} screenshots. Apps used: {}",
-                entry_count,
-                entries
-                    .iter()
-                    .map(|e| e.app_name.clone())
-                    .collect::<std::collections::HashSet<_>>()
-                    .into_iter()
-                    .collect::<Vec<_>>()
-                    .join(", ")
+            Some(
+                llm_client
+                    .send_extraction(&prompt, &text_prompt, &images, false)
+                    .await,
             )
         };
+        let (summary, provider) = match llm_result {
+            Some(Ok(summary)) => (summary, llm_client.answered_by().unwrap_or_default()),
+            Some(Err(e)) if e.is::<Cancelled>() || !config.llm_text_fallback => return Err(e),
+            Some(Err(e)) => {
+                log::warn!("All LLM providers failed, storing a text-only summary: {}", e);
+                (Self::text_only_summary(entries), TEXT_ONLY_PROVIDER.to_string())
+            }
+            None => (Self::text_only_summary(entries), TEXT_ONLY_PROVIDER.to_string()),
+        };
 
-        let timestamp = Local::now().to_rfc3339();
-        activity_log.insert_batch_summary(&batch_id, &timestamp, &summary, entry_count)?;
+        let unmatched_ids: Vec<i64> = entries
+            .iter()
+            .filter(|e| e.project_id.is_none())
+            .map(|e| e.id)
+            .collect();
+        let classified_project = if config.project_llm_classification
+            && !config.projects.is_empty()
+            && !unmatched_ids.is_empty()
+            && provider != TEXT_ONLY_PROVIDER
+        {
+            match Self::classify_project(llm_client, &config.projects, &context, &summary).await {
+                Ok(project) => project,
+                Err(e) if e.is::<Cancelled>() => return Err(e),
+                Err(e) => {
+                    log::warn!("Project classification failed: {}", e);
+                    None
+                }
+            }
+        } else {
+            None
+        };
+
+        // Last chance to back out before anything is written
+        cancel.check()?;
 
+        let timestamp = Local::now().to_rfc3339();
         let entry_ids: Vec<i64> = entries.iter().map(|e| e.id).collect();
-        activity_log.mark_entries_batched(&entry_ids, &batch_id)?;
+        activity_log.commit_batch(
+            &batch_id,
+            &timestamp,
+            &summary,
+            &entry_ids,
+            &observations,
+            &provider,
+        )?;
+
+        if let Some(project_id) = classified_project {
+            activity_log.set_project_for_entries(&unmatched_ids, &project_id)?;
+            log::info!(
+                "Batch {}: {} unmatched entries classified as project {}",
+                batch_id,
+                unmatched_ids.len(),
+                project_id
+            );
+        }
 
         // Delete this chunk's screenshots
         for entry in entries {
@@ -210,70 +698,324 @@ impl DigestGenerator {
         Ok(summary)
     }
 
-    /// Generate the daily digest for today: process any remaining screenshots first,
-    /// then summarize all batches into report.md.
+    /// Entries per batch request.
+    pub fn chunk_size(config: &AppConfig) -> usize {
+        if config.keyframes_per_batch > 0 {
+            SAMPLED_CHUNK_SIZE
+        } else {
+            MAX_IMAGES_PER_REQUEST
+        }
+    }
+
+    /// Which entries of a chunk are sent as frames: screenshots still on disk,
+    /// only the sampled ones with keyframes_per_batch, or in OCR "instead" mode
+    /// the entries with screen text.
+    pub fn chunk_frames(entries: &[ActivityEntry], config: &AppConfig) -> Vec<bool> {
+        if config.ocr_mode == "instead" {
+            return entries
+                .iter()
+                .map(|e| !e.ocr_text.trim().is_empty())
+                .collect();
+        }
+        entries
+            .iter()
+            .zip(Self::sample_frames(entries, config))
+            .map(|(e, sampled)| sampled && Path::new(&e.screenshot_path).exists())
+            .collect()
+    }
+
+    /// Which entries of a chunk may be sent as screenshots: all of them, or the
+    /// `keyframes_per_batch` most distinct ones (at most MAX_IMAGES_PER_REQUEST).
+    fn sample_frames(entries: &[ActivityEntry], config: &AppConfig) -> Vec<bool> {
+        if config.keyframes_per_batch == 0 {
+            return vec![true; entries.len()];
+        }
+        let hashes: Vec<Option<u64>> = entries
+            .iter()
+            .map(|e| {
+                Path::new(&e.screenshot_path)
+                    .exists()
+                    .then(|| u64::from_str_radix(&e.image_hash, 16).ok())
+                    .flatten()
+            })
+            .collect();
+        keyframes::most_distinct(
+            &hashes,
+            config.keyframes_per_batch.min(MAX_IMAGES_PER_REQUEST),
+        )
+    }
+
+    /// Q&A prompt for HTTP providers, which cannot read the data folder like the
+    /// claude CLI does: the question with the reports and batch summaries of
+    /// `date`, redacted. Returns the prompt and the placeholder mapping to
+    /// restore the answer with.
+    pub fn qa_prompt(
+        activity_log: &Arc<ActivityLog>,
+        config: &AppConfig,
+        date: &str,
+        question: &str,
+    ) -> Result<(String, Vec<(String, String)>)> {
+        let mut context = String::new();
+        let date_dir = config.data_path().join(date);
+        for profile in config.all_profiles() {
+            let file = profile.report_file();
+            if let Ok(report) = std::fs::read_to_string(date_dir.join(&file)) {
+                context.push_str(&format!("## Report: {}\n{}\n\n", file, report.trim()));
+            }
+        }
+
+        let mut summaries: Vec<String> = Vec::new();
+        let mut chars = 0;
+        for batch in activity_log.get_batches_for_date(date)?.iter().rev() {
+            let text = format!("### Batch at {}\n{}\n\n", batch.timestamp, batch.summary);
+            chars += text.len();
+            if chars > MAX_QA_SUMMARY_CHARS {
+                break;
+            }
+            summaries.push(text);
+        }
+        if !summaries.is_empty() {
+            context.push_str("## Activity summaries\n");
+            context.extend(summaries.into_iter().rev());
+        }
+        if context.is_empty() {
+            context.push_str("No activity has been recorded for this date.");
+        }
+
+        let mut vars = Variables::new();
+        vars.set("date", date)
+            .set("context", context.trim_end())
+            .set("question", question);
+        let prompt = prompt_template::render(QA_PROMPT, &vars)?;
+
+        let mut redactor = Self::redactor(activity_log, config, date)?;
+        let prompt = redactor.redact(&prompt);
+        activity_log.add_redactions_for_date(date, &redactor.take_new())?;
+        Ok((prompt, redactor.mapping().to_vec()))
+    }
+
+    /// Redactor for text of `date`, seeded with the placeholders already assigned
+    /// that day so batches and the digest agree on them.
+    fn redactor(activity_log: &Arc<ActivityLog>, config: &AppConfig, date: &str) -> Result<Redactor> {
+        let known = activity_log.get_redactions_for_date(date)?;
+        Ok(Redactor::new(&config.redaction, known))
+    }
+
+    /// Summary stored when no LLM answered: just the apps seen in the chunk.
+    fn text_only_summary(entries: &[ActivityEntry]) -> String {
+        format!(
+            "Batch of {} screenshots. Apps used: {}",
+            entries.len(),
+            entries
+                .iter()
+                .map(|e| e.app_name.clone())
+                .collect::<std::collections::HashSet<_>>()
+                .into_iter()
+                .collect::<Vec<_>>()
+                .join(", ")
+        )
+    }
+
+    /// Ask the LLM which project a batch belongs to. Returns `None` when it picks
+    /// no project or answers with something that is not a known project.
+    async fn classify_project(
+        llm_client: &LlmClient,
+        projects: &[Project],
+        activity_context: &str,
+        summary: &str,
+    ) -> Result<Option<String>> {
+        let project_list = projects
+            .iter()
+            .map(|p| format!("- {}: {}", p.id, p.display_name()))
+            .collect::<Vec<_>>()
+            .join("\n");
+        let prompt = PROJECT_CLASSIFY_PROMPT
+            .replace("{projects}", &project_list)
+            .replace("{activity_log}", activity_context)
+            .replace("{summary}", summary);
+
+        let answer = llm_client.send_multimodal(&prompt, &[]).await?;
+        let answer = answer.trim().trim_matches(|c| c == '"' || c == '`' || c == '.');
+        let project = projects.iter().find(|p| {
+            p.id.eq_ignore_ascii_case(answer) || p.name.eq_ignore_ascii_case(answer)
+        });
+        Ok(project.map(|p| p.id.clone()))
+    }
+
+    /// Generate today's digest for `profile`: process any remaining screenshots first,
+    /// then summarize all batches into the profile's report file.
     pub async fn generate_daily_digest(
         activity_log: &Arc<ActivityLog>,
         screenshot_store: &ScreenshotStore,
         llm_client: &LlmClient,
+        config: &AppConfig,
+        profile: &DigestProfile,
+        cancel: &CancelToken,
     ) -> Result<PathBuf> {
         let date = Local::now().format("%Y-%m-%d").to_string();
-        Self::generate_digest_for_date(activity_log, screenshot_store, llm_client, &date).await
+        Self::generate_digest_for_date(
+            activity_log,
+            screenshot_store,
+            llm_client,
+            config,
+            &date,
+            profile,
+            cancel,
+        )
+        .await
+    }
+
+    /// Client for the digest request of `profile`: the digest step settings with
+    /// the profile's overrides on top.
+    fn digest_client(profile: &DigestProfile, config: &AppConfig, cancel: &CancelToken) -> LlmClient {
+        let settings = config
+            .llm_settings(LlmStep::Digest)
+            .with_overrides(&profile.llm_overrides());
+        LlmClient::from_settings(&settings, Some(config.data_path()))
+            .with_fallbacks(&config.llm_fallbacks)
+            .with_audit(config)
+            .with_cancel(cancel.clone())
     }
 
-    /// Generate the daily digest for a specific date: process remaining screenshots first,
-    /// then summarize all batches into report.md.
+    /// Generate the digest of `profile` for a specific date: process remaining
+    /// screenshots first, then summarize all batches into the profile's report file.
+    /// `llm_client` is the extraction client; the digest request uses the digest
+    /// step settings.
     pub async fn generate_digest_for_date(
         activity_log: &Arc<ActivityLog>,
         screenshot_store: &ScreenshotStore,
         llm_client: &LlmClient,
+        config: &AppConfig,
         date: &str,
+        profile: &DigestProfile,
+        cancel: &CancelToken,
     ) -> Result<PathBuf> {
+        // Held for the whole run so batch ticks and other digests for this date wait
+        let _guard = Self::lock_day(activity_log, date, cancel).await?;
+
         // Only process remaining unbatched screenshots when generating for today
-        let today = Local::now().format("%Y-%m-%d").to_string();
-        if date == today {
-            if let Some(_) = Self::process_batch(activity_log, screenshot_store, llm_client).await?
+        if date == activity_log.current_date()? {
+            if let Some(_) =
+                Self::process_batch_locked(
+                    activity_log,
+                    screenshot_store,
+                    llm_client,
+                    config,
+                    date,
+                    cancel,
+                )
+                .await?
             {
                 log::info!("Processed remaining screenshots before generating digest");
             }
         }
 
-        let batches = activity_log.get_batches_for_date(date)?;
-        let app_usage = activity_log.get_app_usage_for_date(date)?;
-
-        let mut batch_text = String::new();
-        for batch in &batches {
-            batch_text.push_str(&format!(
-                "## Batch at {}\n{}\n\n",
-                batch.timestamp, batch.summary
-            ));
-        }
+        // Calendar events of the day are stored with each digest run
+        meetings::import_calendar(activity_log, config, date)?;
 
-        let mut usage_text = String::new();
-        for (app, count) in &app_usage {
-            let minutes = count * 5 / 60;
-            usage_text.push_str(&format!("- {}: ~{} min\n", app, minutes));
-        }
+        let mut vars = Self::digest_variables(activity_log, config, date)?;
+        vars.set("commits", commits::to_text(&commits::for_date(config, date).await));
+        let mut prompt = prompt_template::render_named(
+            TemplateKind::Digest,
+            &profile.template,
+            DEFAULT_DIGEST_PROMPT,
+            &vars,
+        )?;
 
-        let prompt_template = Self::load_digest_prompt();
-        let prompt = prompt_template
-            .replace("{batch_summaries}", &batch_text)
-            .replace("{app_usage}", &usage_text)
-            .replace("{date}", date);
+        let digest_client = Self::digest_client(profile, config, cancel);
+        let llm_client = &digest_client;
+        let used_llm = !llm_client.api_key_is_empty();
+        let mut redactor = Self::redactor(activity_log, config, date)?;
 
-        let report = if !llm_client.api_key_is_empty() {
-            llm_client.send_multimodal(&prompt, &[]).await?
+        // Long days: summarize hour by hour first so the prompt fits the model
+        let threshold = config.digest_token_threshold_for(llm_client.model());
+        let estimated = summarization::estimate_tokens(&prompt.text);
+        if used_llm && threshold > 0 && estimated > threshold {
+            log::info!(
+                "Digest prompt is ~{} tokens, above the {} token threshold of {}",
+                estimated,
+                threshold,
+                llm_client.model()
+            );
+            let batches: Vec<_> = activity_log
+                .get_batches_for_date(date)?
+                .into_iter()
+                .map(|mut batch| {
+                    batch.summary = redactor.redact(&batch.summary);
+                    batch
+                })
+                .collect();
+            let overhead =
+                estimated.saturating_sub(summarization::estimate_tokens(vars.text("batch_summaries")));
+            let budget = threshold.saturating_sub(overhead).max(threshold / 4);
+            // Half the threshold per condense request leaves room for its answer
+            let sections = summarization::condense_batches(
+                llm_client,
+                &batches,
+                budget,
+                threshold / 2,
+                cancel,
+            )
+            .await?;
+            Self::set_condensed_batches(&mut vars, &sections);
+            prompt = prompt_template::render_named(
+                TemplateKind::Digest,
+                &profile.template,
+                DEFAULT_DIGEST_PROMPT,
+                &vars,
+            )?;
+        }
+        let report = if used_llm {
+            let text = redactor.redact(&prompt.text);
+            activity_log.add_redactions_for_date(date, &redactor.take_new())?;
+            llm_client.send_multimodal(&text, &[]).await?
         } else {
             format!(
                 "# Daily Activity Report - {}\n\n## Summary\nTracked {} activity batches.\n\n## App Usage\n{}\n\n## Batch Details\n{}",
                 date,
-                batches.len(),
-                usage_text,
-                batch_text
+                vars.text("batch_count"),
+                vars.text("app_usage"),
+                vars.text("batch_summaries")
             )
         };
 
-        let report_path = screenshot_store.save_report_for_date(&report, date)?;
+        // Keep the previous report if we were cancelled while the LLM was answering
+        cancel.check()?;
+
+        let report = if config.redaction.restore_in_report {
+            redaction::restore(&report, redactor.mapping())
+        } else {
+            report
+        };
+
+        let meta = if used_llm {
+            let usage = llm_client.last_usage();
+            let (provider, model) = llm_client.answered_provider_model();
+            ReportMeta {
+                provider,
+                model,
+                prompt_hash: report_history::prompt_hash(&prompt.body),
+                prompt_tokens: usage.map(|u| u.prompt_tokens),
+                completion_tokens: usage.map(|u| u.completion_tokens),
+            }
+        } else {
+            ReportMeta {
+                prompt_hash: report_history::prompt_hash(&prompt.body),
+                ..Default::default()
+            }
+        };
+        let report_path =
+            screenshot_store.save_report_for_date(&report, date, &profile.report_file(), meta)?;
+
+        // Daily notes hold one report; only the default profile is exported
+        if config.note_export_enabled && profile.is_default() {
+            let meta = Self::note_metadata(activity_log, config, date)?;
+            match note_export::export_report(config, &report, &meta) {
+                Ok(Some(path)) => log::info!("Digest exported to note {:?}", path),
+                Ok(None) => {}
+                Err(e) => log::warn!("Failed to export digest to notes vault: {}", e),
+            }
+        }
 
         // Clean up any remaining screenshot files for this date
         match screenshot_store.cleanup_screenshots_for_date(date) {
@@ -282,8 +1024,216 @@ impl DigestGenerator {
             _ => {}
         }
 
-        log::info!("Daily digest saved to {:?}", report_path);
+        log::info!("Daily digest ({} profile) saved to {:?}", profile.name, report_path);
         Ok(report_path)
     }
+
+    /// Frontmatter values for the exported daily note.
+    fn note_metadata(
+        activity_log: &Arc<ActivityLog>,
+        config: &AppConfig,
+        date: &str,
+    ) -> Result<NoteMetadata> {
+        let entries = activity_log.get_entries_for_date(date)?;
+        let app_usage = timesheet::time_by(&entries, |e| Some(e.app_name.clone()));
+        let tracked_secs: i64 = app_usage.iter().map(|(_, secs)| secs).sum();
+        let projects = activity_log
+            .get_project_usage_for_date(date)?
+            .into_iter()
+            .filter_map(|(project_id, _)| project_id)
+            .filter_map(|id| config.projects.iter().find(|p| p.id == id))
+            .map(|p| p.display_name())
+            .collect();
+
+        Ok(NoteMetadata {
+            date: date.to_string(),
+            apps: app_usage
+                .iter()
+                .filter(|(app, _)| !app.is_empty())
+                .map(|(app, _)| app.clone())
+                .collect(),
+            projects,
+            hours_tracked: tracked_secs as f64 / 3600.0,
+        })
+    }
+
+    /// Per-project durations for `{projects}`, one bullet per project.
+    /// Empty when no entry of the day is attributed to a project.
+    fn project_usage_text(
+        activity_log: &Arc<ActivityLog>,
+        config: &AppConfig,
+        date: &str,
+    ) -> Result<String> {
+        let entries = activity_log.get_entries_for_date(date)?;
+        let usage = timesheet::time_by(&entries, |e| Some(e.project_id.clone()));
+        if usage.iter().all(|(project_id, _)| project_id.is_none()) {
+            return Ok(String::new());
+        }
+
+        let mut text = String::new();
+        for (project_id, secs) in &usage {
+            let minutes = secs / 60;
+            let name = match project_id {
+                Some(id) => config
+                    .projects
+                    .iter()
+                    .find(|p| &p.id == id)
+                    .map(|p| p.display_name())
+                    .unwrap_or_else(|| id.clone()),
+                None => "Unassigned".to_string(),
+            };
+            text.push_str(&format!("- {}: ~{} min\n", name, minutes));
+        }
+        Ok(text)
+    }
+
+    /// Per-repository durations for `{repos}`: one bullet per repository with
+    /// its branches and their last commit. Empty without developer context.
+    fn repo_usage_text(entries: &[ActivityEntry], usage: &[RepoUsage]) -> String {
+        let branch_secs = timesheet::time_by(entries, |e| {
+            (!e.repo_path.is_empty()).then(|| (e.repo_path.clone(), e.git_branch.clone()))
+        });
+        // (path, seconds, branches with their seconds)
+        type RepoTime<'a> = (&'a str, i64, Vec<(&'a str, i64)>);
+        let mut repos: Vec<RepoTime> = Vec::new();
+        for ((path, branch), secs) in &branch_secs {
+            match repos.iter_mut().find(|(repo, _, _)| repo == path) {
+                Some((_, total, branches)) => {
+                    *total += secs;
+                    branches.push((branch, *secs));
+                }
+                None => repos.push((path, *secs, vec![(branch, *secs)])),
+            }
+        }
+        repos.sort_by_key(|(_, secs, _)| std::cmp::Reverse(*secs));
+
+        let mut text = String::new();
+        for (path, secs, branches) in repos {
+            text.push_str(&format!(
+                "- {} ({}): ~{} min\n",
+                dev_context::repo_name(path),
+                path,
+                secs / 60
+            ));
+            for (branch, secs) in branches {
+                text.push_str(&format!("  - {}: ~{} min", branch, secs / 60));
+                let last_commit = usage
+                    .iter()
+                    .find(|u| u.repo_path == path && u.branch == branch)
+                    .map(|u| u.commit_subject.as_str())
+                    .unwrap_or_default();
+                if !last_commit.is_empty() {
+                    text.push_str(&format!(", last commit \"{}\"", last_commit));
+                }
+                text.push('\n');
+            }
+        }
+        text
+    }
 }
 
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::services::activity_log::CaptureContext;
+    use crate::services::jobs::{JobKind, JobRegistry};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+
+    /// OpenAI-compatible endpoint that counts requests and answers each one
+    /// after a short delay, so overlapping runs would both reach it.
+    async fn fake_llm(calls: Arc<AtomicUsize>) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let endpoint = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(async move {
+            loop {
+                let (mut stream, _) = listener.accept().await.unwrap();
+                let calls = calls.clone();
+                tokio::spawn(async move {
+                    let mut request = Vec::new();
+                    let mut chunk = [0u8; 4096];
+                    loop {
+                        let n = stream.read(&mut chunk).await.unwrap();
+                        request.extend_from_slice(&chunk[..n]);
+                        let text = String::from_utf8_lossy(&request);
+                        if let Some(end) = text.find("\r\n\r\n") {
+                            let length = text[..end]
+                                .lines()
+                                .find_map(|line| {
+                                    let (name, value) = line.split_once(':')?;
+                                    name.eq_ignore_ascii_case("content-length")
+                                        .then(|| value.trim().parse::<usize>().ok())?
+                                })
+                                .unwrap_or(0);
+                            if request.len() >= end + 4 + length {
+                                break;
+                            }
+                        }
+                        if n == 0 {
+                            return;
+                        }
+                    }
+                    calls.fetch_add(1, Ordering::SeqCst);
+                    tokio::time::sleep(std::time::Duration::from_millis(200)).await;
+                    let body = r#"{"choices":[{"message":{"content":"Edited notes"},"finish_reason":"stop"}]}"#;
+                    let response = format!(
+                        "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                        body.len(),
+                        body
+                    );
+                    let _ = stream.write_all(response.as_bytes()).await;
+                });
+            }
+        });
+        endpoint
+    }
+
+    #[tokio::test]
+    async fn concurrent_batch_runs_send_entries_once() {
+        let dir = std::env::temp_dir().join(format!("diaroo-test-{}", uuid::Uuid::new_v4()));
+        let activity_log = Arc::new(ActivityLog::new(&dir).unwrap());
+        let store = ScreenshotStore::new(dir.clone());
+        for i in 0..3 {
+            activity_log
+                .insert_activity(
+                    &Local::now().to_rfc3339(),
+                    &dir.join(format!("{}.jpg", i)).to_string_lossy(),
+                    "notes.txt - Editor",
+                    "Editor",
+                    "0000000000000000",
+                    &CaptureContext {
+                        ocr_text: "Meeting notes",
+                        ..Default::default()
+                    },
+                )
+                .unwrap();
+        }
+
+        let calls = Arc::new(AtomicUsize::new(0));
+        let endpoint = fake_llm(calls.clone()).await;
+        let llm = LlmClient::new("ollama", "", "fake", &endpoint, None);
+        let config = AppConfig {
+            ocr_mode: "instead".to_string(),
+            ..AppConfig::default()
+        };
+        let jobs = Arc::new(JobRegistry::new());
+        let job = jobs.start(JobKind::Batch, "test");
+
+        let (first, second) = tokio::join!(
+            DigestGenerator::process_batch(&activity_log, &store, &llm, &config, job.token()),
+            DigestGenerator::process_batch(&activity_log, &store, &llm, &config, job.token()),
+        );
+        let summaries: Vec<String> = [first.unwrap(), second.unwrap()]
+            .into_iter()
+            .flatten()
+            .collect();
+
+        assert_eq!(summaries, vec!["Edited notes".to_string()]);
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+        assert_eq!(activity_log.get_batches().unwrap().len(), 1);
+        assert!(activity_log.get_unbatched_entries().unwrap().is_empty());
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,170 @@
+use anyhow::{anyhow, Result};
+use serde_json::Value;
+
+/// Appended to the extract prompt in structured mode. Screenshots are referenced
+/// by their 1-based position so observations can be linked back to activity rows.
+pub const STRUCTURED_OUTPUT_INSTRUCTIONS: &str = "\
+OUTPUT FORMAT: Respond with a single JSON object and nothing else, matching this schema:
+{
+  \"observations\": [
+    {
+      \"screenshot\": <number of the screenshot, starting at 1>,
+      \"app\": \"<application name>\",
+      \"window_title\": \"<window title or document name, empty if not visible>\",
+      \"timestamp\": \"<timestamp from the activity log>\",
+      \"description\": \"<what is visible on screen, 1 sentence>\"
+    }
+  ],
+  \"summary\": \"<timeline summary, 2-4 sentences>\"
+}
+Include one observation per screenshot, in order.";
+
+/// One validated observation, `screenshot` being the 1-based image index.
+#[derive(Debug, Clone)]
+pub struct ParsedObservation {
+    pub screenshot: usize,
+    pub app_name: String,
+    pub window_title: String,
+    pub timestamp: String,
+    pub description: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct ParsedExtraction {
+    pub observations: Vec<ParsedObservation>,
+    pub summary: String,
+}
+
+/// Parse and validate a structured extraction response, repairing common LLM
+/// mistakes (surrounding prose, trailing commas) first. Observations that point
+/// outside `1..=screenshot_count` are dropped.
+pub fn parse_extraction(text: &str, screenshot_count: usize) -> Result<ParsedExtraction> {
+    let value: Value = match serde_json::from_str(text.trim()) {
+        Ok(v) => v,
+        Err(_) => {
+            let repaired = repair_json(text);
+            serde_json::from_str(&repaired)
+                .map_err(|e| anyhow!("Structured extraction is not valid JSON: {}", e))?
+        }
+    };
+
+    let obj = value
+        .as_object()
+        .ok_or_else(|| anyhow!("Structured extraction is not a JSON object"))?;
+
+    let summary = obj
+        .get("summary")
+        .and_then(Value::as_str)
+        .unwrap_or_default()
+        .trim()
+        .to_string();
+
+    let mut observations = Vec::new();
+    if let Some(items) = obj.get("observations").and_then(Value::as_array) {
+        for (i, item) in items.iter().enumerate() {
+            // Fall back to array position when the model omits the index
+            let screenshot = item
+                .get("screenshot")
+                .and_then(as_index)
+                .unwrap_or(i + 1);
+            if screenshot == 0 || screenshot > screenshot_count {
+                log::warn!("Dropping observation for unknown screenshot {}", screenshot);
+                continue;
+            }
+            observations.push(ParsedObservation {
+                screenshot,
+                app_name: string_field(item, "app"),
+                window_title: string_field(item, "window_title"),
+                timestamp: string_field(item, "timestamp"),
+                description: string_field(item, "description"),
+            });
+        }
+    }
+
+    if summary.is_empty() && observations.is_empty() {
+        return Err(anyhow!("Structured extraction has neither observations nor a summary"));
+    }
+
+    Ok(ParsedExtraction {
+        observations,
+        summary,
+    })
+}
+
+/// Render a parsed extraction as the prose stored in `llm_batches.summary`,
+/// so digests keep working from batch summaries.
+pub fn render_summary(extraction: &ParsedExtraction) -> String {
+    let mut text = String::new();
+    for obs in &extraction.observations {
+        text.push_str(&format!(
+            "- [{}] {} - {}: {}\n",
+            obs.timestamp, obs.app_name, obs.window_title, obs.description
+        ));
+    }
+    if !extraction.summary.is_empty() {
+        if !text.is_empty() {
+            text.push('\n');
+        }
+        text.push_str(&extraction.summary);
+    }
+    text.trim_end().to_string()
+}
+
+fn string_field(item: &Value, key: &str) -> String {
+    match item.get(key) {
+        Some(Value::String(s)) => s.trim().to_string(),
+        Some(Value::Null) | None => String::new(),
+        Some(other) => other.to_string(),
+    }
+}
+
+fn as_index(value: &Value) -> Option<usize> {
+    match value {
+        Value::Number(n) => n.as_u64().map(|n| n as usize),
+        Value::String(s) => s.trim().trim_start_matches('#').parse().ok(),
+        _ => None,
+    }
+}
+
+/// Cut the text down to the outermost `{ ... }` and drop trailing commas
+/// before `}` / `]`, leaving string contents untouched.
+fn repair_json(text: &str) -> String {
+    let start = text.find('{');
+    let end = text.rfind('}');
+    let body = match (start, end) {
+        (Some(s), Some(e)) if e > s => &text[s..=e],
+        _ => text,
+    };
+
+    let chars: Vec<char> = body.chars().collect();
+    let mut out = String::with_capacity(body.len());
+    let mut in_string = false;
+    let mut escaped = false;
+    for (i, &c) in chars.iter().enumerate() {
+        if in_string {
+            out.push(c);
+            if escaped {
+                escaped = false;
+            } else if c == '\\' {
+                escaped = true;
+            } else if c == '"' {
+                in_string = false;
+            }
+            continue;
+        }
+        match c {
+            '"' => {
+                in_string = true;
+                out.push(c);
+            }
+            ',' => {
+                let next = chars[i + 1..].iter().find(|c| !c.is_whitespace());
+                if !matches!(next, Some('}') | Some(']')) {
+                    out.push(c);
+                }
+            }
+            _ => out.push(c),
+        }
+    }
+    out
+}
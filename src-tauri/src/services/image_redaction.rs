@@ -0,0 +1,282 @@
+use anyhow::{anyhow, Result};
+use image::imageops::{self, FilterType};
+use image::{DynamicImage, GenericImage, ImageFormat, Rgba};
+use xcap::Window;
+
+use crate::services::ocr::{self, OcrWord};
+use crate::services::redaction::Redactor;
+use crate::services::screenshot::{self, ScreenArea};
+use crate::storage::config::{AppConfig, ImageRedactionSettings, MaskRegion};
+
+/// Blurred windows are scaled down by this factor and back up.
+const BLUR_FACTOR: u32 = 16;
+/// Pixels added around OCR word boxes so glyph edges are covered too.
+const WORD_PADDING: u32 = 2;
+const FILL: Rgba<u8> = Rgba([0, 0, 0, 255]);
+
+#[derive(Debug, Clone, Copy)]
+struct Rect {
+    x: u32,
+    y: u32,
+    width: u32,
+    height: u32,
+}
+
+/// Clip a rectangle in image pixels to `image`; `None` if nothing is left.
+fn clip(x: f64, y: f64, width: f64, height: f64, image: &DynamicImage) -> Option<Rect> {
+    let left = x.max(0.0).round() as u32;
+    let top = y.max(0.0).round() as u32;
+    let right = ((x + width).round().max(0.0) as u32).min(image.width());
+    let bottom = ((y + height).round().max(0.0) as u32).min(image.height());
+    (right > left && bottom > top).then_some(Rect {
+        x: left,
+        y: top,
+        width: right - left,
+        height: bottom - top,
+    })
+}
+
+/// Image pixels per screen unit, and the conversion of a rectangle in screen
+/// coordinates to pixels of `image`, a capture of `area`.
+struct Mapping {
+    area: ScreenArea,
+    scale_x: f64,
+    scale_y: f64,
+}
+
+impl Mapping {
+    fn new(image: &DynamicImage, area: &ScreenArea) -> Self {
+        Self {
+            area: *area,
+            scale_x: image.width() as f64 / area.width.max(1) as f64,
+            scale_y: image.height() as f64 / area.height.max(1) as f64,
+        }
+    }
+
+    fn rect(&self, x: f64, y: f64, width: f64, height: f64, image: &DynamicImage) -> Option<Rect> {
+        clip(
+            (x - self.area.x as f64) * self.scale_x,
+            (y - self.area.y as f64) * self.scale_y,
+            width * self.scale_x,
+            height * self.scale_y,
+            image,
+        )
+    }
+}
+
+/// A region given in percent of `screen`, so it stays on the same part of
+/// the monitor when the capture is cropped to a window.
+fn region_rect(
+    region: &MaskRegion,
+    screen: &ScreenArea,
+    mapping: &Mapping,
+    image: &DynamicImage,
+) -> Option<Rect> {
+    let (w, h) = (screen.width as f64, screen.height as f64);
+    mapping.rect(
+        screen.x as f64 + region.x / 100.0 * w,
+        screen.y as f64 + region.y / 100.0 * h,
+        region.width / 100.0 * w,
+        region.height / 100.0 * h,
+        image,
+    )
+}
+
+fn fill(image: &mut DynamicImage, rect: Rect) {
+    for y in rect.y..rect.y + rect.height {
+        for x in rect.x..rect.x + rect.width {
+            image.put_pixel(x, y, FILL);
+        }
+    }
+}
+
+fn blur(image: &mut DynamicImage, rect: Rect) {
+    let crop = image.crop_imm(rect.x, rect.y, rect.width, rect.height);
+    let small = crop.resize_exact(
+        (rect.width / BLUR_FACTOR).max(1),
+        (rect.height / BLUR_FACTOR).max(1),
+        FilterType::Triangle,
+    );
+    let blurred = small.resize_exact(rect.width, rect.height, FilterType::Triangle);
+    imageops::replace(image, &blurred, rect.x as i64, rect.y as i64);
+}
+
+/// A redacted capture: how many areas were hidden and, when text was read,
+/// the OCR words still visible on it.
+pub struct Redaction {
+    pub masked: usize,
+    pub words: Option<Vec<OcrWord>>,
+}
+
+/// Hides sensitive parts of a capture before it is saved: fixed regions, the
+/// windows of listed apps and, with OCR, text matching the redaction patterns.
+pub struct ImageRedactor {
+    settings: ImageRedactionSettings,
+    patterns: Redactor,
+}
+
+impl ImageRedactor {
+    pub fn new(config: &AppConfig) -> Self {
+        Self {
+            settings: config.image_redaction.clone(),
+            patterns: Redactor::new(&config.redaction, Vec::new()),
+        }
+    }
+
+    fn masks_text(&self) -> bool {
+        self.settings.enabled && self.settings.ocr && self.patterns.is_active()
+    }
+
+    fn blurs_apps(&self) -> bool {
+        self.settings.enabled && self.settings.blur_apps.iter().any(|app| !app.trim().is_empty())
+    }
+
+    /// Fail if a tool the enabled masks rely on is unavailable, since every
+    /// capture would then be dropped.
+    pub async fn check(&self) -> Result<()> {
+        if self.masks_text() {
+            ocr::check_installed()
+                .await
+                .map_err(|e| anyhow!("Text redaction needs tesseract: {}", e))?;
+        }
+        if self.blurs_apps() {
+            Window::all().map_err(|e| {
+                anyhow!("Blurring app windows is not supported on this system: {}", e)
+            })?;
+        }
+        Ok(())
+    }
+
+    /// Mask `image`, a capture of `area` on the monitor `screen`, in place.
+    /// With `read_text`, the words left visible are returned too; OCR runs
+    /// once for both. Errors mean the frame could not be redacted and must
+    /// not be stored.
+    pub async fn apply(
+        &self,
+        image: &mut DynamicImage,
+        screen: &ScreenArea,
+        area: &ScreenArea,
+        read_text: bool,
+    ) -> Result<Redaction> {
+        let mut masked = 0;
+        if self.settings.enabled {
+            let mapping = Mapping::new(image, area);
+            let regions: Vec<Rect> = self
+                .settings
+                .regions
+                .iter()
+                .filter_map(|region| region_rect(region, screen, &mapping, image))
+                .collect();
+            for rect in regions {
+                fill(image, rect);
+                masked += 1;
+            }
+
+            if self.blurs_apps() {
+                for rect in self.app_windows(image, &mapping)? {
+                    blur(image, rect);
+                    masked += 1;
+                }
+            }
+        }
+
+        let mask_text = self.masks_text();
+        if !mask_text && !read_text {
+            return Ok(Redaction { masked, words: None });
+        }
+        // Lossless input reads better than a compressed frame
+        let png = screenshot::encode(image, ImageFormat::Png, 0)?;
+        let words = match ocr::recognize(&png).await {
+            Ok(words) => words,
+            Err(e) if !mask_text => {
+                log::warn!("OCR failed, saving screenshot without text: {}", e);
+                return Ok(Redaction { masked, words: None });
+            }
+            Err(e) => return Err(e),
+        };
+
+        let sensitive = if mask_text {
+            self.sensitive_words(&words)
+        } else {
+            vec![false; words.len()]
+        };
+        let mut visible = Vec::new();
+        for (word, sensitive) in words.into_iter().zip(sensitive) {
+            if !sensitive {
+                visible.push(word);
+                continue;
+            }
+            let rect = clip(
+                word.left as f64 - WORD_PADDING as f64,
+                word.top as f64 - WORD_PADDING as f64,
+                (word.width + 2 * WORD_PADDING) as f64,
+                (word.height + 2 * WORD_PADDING) as f64,
+                image,
+            );
+            if let Some(rect) = rect {
+                fill(image, rect);
+                masked += 1;
+            }
+        }
+        Ok(Redaction {
+            masked,
+            words: read_text.then_some(visible),
+        })
+    }
+
+    /// On-screen windows of the blurred apps, in image pixels.
+    fn app_windows(&self, image: &DynamicImage, mapping: &Mapping) -> Result<Vec<Rect>> {
+        let mut rects = Vec::new();
+        for window in Window::all()? {
+            if window.is_minimized().unwrap_or(false) {
+                continue;
+            }
+            let app_name = window.app_name().unwrap_or_default();
+            if !self
+                .settings
+                .blur_apps
+                .iter()
+                .any(|app| app.trim().eq_ignore_ascii_case(&app_name))
+            {
+                continue;
+            }
+            let rect = mapping.rect(
+                window.x()? as f64,
+                window.y()? as f64,
+                window.width()? as f64,
+                window.height()? as f64,
+                image,
+            );
+            rects.extend(rect);
+        }
+        Ok(rects)
+    }
+
+    /// Which OCR words are part of a redaction pattern match. Lines are matched
+    /// as a whole so patterns spanning words (phone numbers) are found.
+    fn sensitive_words(&self, words: &[OcrWord]) -> Vec<bool> {
+        let mut sensitive = vec![false; words.len()];
+        let mut offset = 0;
+        for line in ocr::lines(words) {
+            let mut text = String::new();
+            let mut spans = Vec::new();
+            for word in line {
+                if !text.is_empty() {
+                    text.push(' ');
+                }
+                let start = text.len();
+                text.push_str(&word.text);
+                spans.push(start..text.len());
+            }
+            for range in self.patterns.sensitive_ranges(&text) {
+                for (i, span) in spans.iter().enumerate() {
+                    if span.start < range.end && range.start < span.end {
+                        sensitive[offset + i] = true;
+                    }
+                }
+            }
+            offset += line.len();
+        }
+        sensitive
+    }
+}
@@ -0,0 +1,169 @@
+use chrono::Local;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use tokio::sync::watch;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum JobKind {
+    Digest,
+    Batch,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct JobInfo {
+    pub id: String,
+    pub kind: JobKind,
+    pub date: String,
+    pub started_at: String,
+}
+
+/// Error returned by work that stopped because its job was cancelled.
+/// Callers can detect it with `err.is::<Cancelled>()` to avoid logging it as a failure.
+#[derive(Debug)]
+pub struct Cancelled;
+
+impl std::fmt::Display for Cancelled {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Job cancelled")
+    }
+}
+
+impl std::error::Error for Cancelled {}
+
+/// Cheap, cloneable view of a job's cancellation flag.
+#[derive(Clone)]
+pub struct CancelToken {
+    rx: watch::Receiver<bool>,
+}
+
+impl CancelToken {
+    pub fn is_cancelled(&self) -> bool {
+        *self.rx.borrow()
+    }
+
+    /// Return `Err(Cancelled)` if the job has been cancelled. Call this before
+    /// writing anything that should not outlive a cancelled job.
+    pub fn check(&self) -> anyhow::Result<()> {
+        if self.is_cancelled() {
+            return Err(Cancelled.into());
+        }
+        Ok(())
+    }
+
+    /// Resolve once the job is cancelled. Never resolves if it is not.
+    pub async fn cancelled(&self) {
+        let mut rx = self.rx.clone();
+        loop {
+            if *rx.borrow_and_update() {
+                return;
+            }
+            if rx.changed().await.is_err() {
+                // Registry entry is gone without a cancel — wait forever.
+                std::future::pending::<()>().await;
+            }
+        }
+    }
+}
+
+struct JobEntry {
+    info: JobInfo,
+    cancel_tx: watch::Sender<bool>,
+}
+
+/// Tracks long-running digest and batch jobs so they can be listed and cancelled.
+pub struct JobRegistry {
+    jobs: Mutex<HashMap<String, JobEntry>>,
+}
+
+impl Default for JobRegistry {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl JobRegistry {
+    pub fn new() -> Self {
+        Self {
+            jobs: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Register a new job. The job is removed from the registry when the
+    /// returned handle is dropped.
+    pub fn start(self: &Arc<Self>, kind: JobKind, date: &str) -> JobHandle {
+        let id = uuid::Uuid::new_v4().to_string();
+        let (cancel_tx, cancel_rx) = watch::channel(false);
+        let info = JobInfo {
+            id: id.clone(),
+            kind,
+            date: date.to_string(),
+            started_at: Local::now().to_rfc3339(),
+        };
+        self.jobs
+            .lock()
+            .unwrap()
+            .insert(id.clone(), JobEntry { info, cancel_tx });
+        log::info!("Job {} started ({:?} for {})", id, kind, date);
+
+        JobHandle {
+            registry: self.clone(),
+            id,
+            token: CancelToken { rx: cancel_rx },
+        }
+    }
+
+    pub fn list(&self) -> Vec<JobInfo> {
+        let jobs = self.jobs.lock().unwrap();
+        let mut infos: Vec<JobInfo> = jobs.values().map(|j| j.info.clone()).collect();
+        infos.sort_by(|a, b| a.started_at.cmp(&b.started_at));
+        infos
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.jobs.lock().unwrap().is_empty()
+    }
+
+    /// Signal cancellation for a job. Returns false if no such job is running.
+    pub fn cancel(&self, id: &str) -> bool {
+        let jobs = self.jobs.lock().unwrap();
+        match jobs.get(id) {
+            Some(job) => {
+                let _ = job.cancel_tx.send(true);
+                log::info!("Job {} cancellation requested", id);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Signal cancellation for every running job. Returns how many were signalled.
+    pub fn cancel_all(&self) -> usize {
+        let jobs = self.jobs.lock().unwrap();
+        for (id, job) in jobs.iter() {
+            let _ = job.cancel_tx.send(true);
+            log::info!("Job {} cancellation requested", id);
+        }
+        jobs.len()
+    }
+}
+
+pub struct JobHandle {
+    registry: Arc<JobRegistry>,
+    id: String,
+    token: CancelToken,
+}
+
+impl JobHandle {
+    pub fn token(&self) -> &CancelToken {
+        &self.token
+    }
+}
+
+impl Drop for JobHandle {
+    fn drop(&mut self) {
+        self.registry.jobs.lock().unwrap().remove(&self.id);
+        log::info!("Job {} finished", self.id);
+    }
+}
@@ -0,0 +1,104 @@
+use std::time::{Duration, Instant};
+
+use crate::services::screenshot::DHash;
+use crate::storage::config::AppConfig;
+
+struct Keyframe {
+    hash: DHash,
+    app_name: String,
+    window_title: String,
+    at: Instant,
+}
+
+/// Result of checking a capture against the last keyframe.
+pub struct Decision {
+    /// dHash distance to the last keyframe (0 for the first one).
+    pub distance: u32,
+    pub keep: bool,
+}
+
+/// Decides which captures become keyframes: the screen changed enough, or the
+/// focused window changed, but never more often than the minimum interval. A
+/// keyframe is also forced once the maximum interval has passed so small
+/// changes (a new line in a terminal) are not missed forever.
+pub struct KeyframeSelector {
+    dedup_threshold: u32,
+    on_title_change: bool,
+    min_interval: Duration,
+    /// `None` never forces a keyframe.
+    max_interval: Option<Duration>,
+    last: Option<Keyframe>,
+}
+
+impl KeyframeSelector {
+    pub fn new(config: &AppConfig) -> Self {
+        Self {
+            dedup_threshold: config.dedup_threshold,
+            on_title_change: config.keyframe_on_title_change,
+            min_interval: Duration::from_secs(config.keyframe_min_interval_secs),
+            max_interval: (config.keyframe_max_interval_secs > 0)
+                .then(|| Duration::from_secs(config.keyframe_max_interval_secs)),
+            last: None,
+        }
+    }
+
+    /// Check a capture and remember it as the last keyframe if it is kept.
+    pub fn select(&mut self, hash: &DHash, app_name: &str, window_title: &str) -> Decision {
+        let now = Instant::now();
+        let (distance, keep) = match &self.last {
+            None => (0, true),
+            Some(last) => {
+                let distance = last.hash.distance(hash);
+                let elapsed = now.duration_since(last.at);
+                let changed = distance >= self.dedup_threshold
+                    || (self.on_title_change
+                        && (last.app_name != app_name || last.window_title != window_title));
+                let overdue = self.max_interval.is_some_and(|max| elapsed >= max);
+                (distance, overdue || (changed && elapsed >= self.min_interval))
+            }
+        };
+
+        if keep {
+            self.last = Some(Keyframe {
+                hash: hash.clone(),
+                app_name: app_name.to_string(),
+                window_title: window_title.to_string(),
+                at: now,
+            });
+        }
+        Decision { distance, keep }
+    }
+}
+
+/// Pick up to `n` frames that differ most from each other (farthest-point
+/// sampling on dHash distance), starting from the first candidate. `hashes[i]`
+/// is `None` for entries that cannot be sent. Returns one flag per entry.
+pub fn most_distinct(hashes: &[Option<u64>], n: usize) -> Vec<bool> {
+    let mut selected = vec![false; hashes.len()];
+    let Some(first) = hashes.iter().position(Option::is_some) else {
+        return selected;
+    };
+    selected[first] = true;
+    // Distance from each candidate to its nearest selected frame
+    let mut nearest: Vec<u32> = hashes
+        .iter()
+        .map(|h| h.map_or(0, |h| (h ^ hashes[first].unwrap()).count_ones()))
+        .collect();
+
+    for _ in 1..n {
+        let next = (0..hashes.len())
+            .filter(|&i| hashes[i].is_some() && !selected[i])
+            .max_by_key(|&i| (nearest[i], std::cmp::Reverse(i)));
+        let Some(next) = next else {
+            break;
+        };
+        selected[next] = true;
+        let picked = hashes[next].unwrap();
+        for (i, hash) in hashes.iter().enumerate() {
+            if let Some(h) = hash {
+                nearest[i] = nearest[i].min((h ^ picked).count_ones());
+            }
+        }
+    }
+    selected
+}
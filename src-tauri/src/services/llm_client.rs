@@ -3,15 +3,29 @@ use base64::Engine;
 use serde::{Deserialize, Serialize};
 use std::error::Error;
 use std::path::PathBuf;
+use std::sync::Mutex;
 use std::time::Duration;
 use tokio::io::AsyncWriteExt;
 use tokio::process::Command;
 
+use crate::services::circuit_breaker;
+use crate::services::jobs::{CancelToken, Cancelled};
+use crate::storage::audit_log::{self, AuditRecord, AuditSettings};
+use crate::storage::config::{AppConfig, LlmSettings, LlmStep};
+
 #[derive(Debug, Serialize)]
 struct OpenRouterRequest {
     model: String,
     messages: Vec<Message>,
     max_tokens: u32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    response_format: Option<ResponseFormat>,
+}
+
+#[derive(Debug, Serialize)]
+struct ResponseFormat {
+    #[serde(rename = "type")]
+    format_type: String,
 }
 
 #[derive(Debug, Serialize)]
@@ -38,6 +52,16 @@ struct ImageUrl {
 struct OpenRouterResponse {
     choices: Option<Vec<Choice>>,
     error: Option<ApiError>,
+    usage: Option<TokenUsage>,
+}
+
+/// Token counts reported by OpenAI-compatible APIs.
+#[derive(Debug, Clone, Copy, Deserialize)]
+pub struct TokenUsage {
+    #[serde(default)]
+    pub prompt_tokens: u64,
+    #[serde(default)]
+    pub completion_tokens: u64,
 }
 
 #[derive(Debug, Deserialize)]
@@ -97,6 +121,19 @@ fn resolve_codex_path() -> String {
     "codex".to_string()
 }
 
+/// "ollama:llava", or just the provider for CLIs that pick their own model.
+fn provider_label(provider: &str, model: &str) -> String {
+    match provider {
+        "claude-code" | "codex" => provider.to_string(),
+        _ => format!("{}:{}", provider, model),
+    }
+}
+
+/// Path where `codex exec --output-last-message` writes its answer for this process.
+fn codex_output_file() -> PathBuf {
+    std::env::temp_dir().join(format!("diaroo_codex_{}.txt", std::process::id()))
+}
+
 pub struct LlmClient {
     client: reqwest::Client,
     provider: String,
@@ -104,6 +141,15 @@ pub struct LlmClient {
     model: String,
     endpoint: String,
     workspace_dir: Option<PathBuf>,
+    cancel: Option<CancelToken>,
+    last_usage: Mutex<Option<TokenUsage>>,
+    /// Tried in order when this client fails or is cooling down.
+    fallbacks: Vec<LlmClient>,
+    /// Provider and model of the client that answered the last request.
+    answered_by: Mutex<Option<(String, String)>>,
+    audit: Option<AuditSettings>,
+    /// Activity rows behind the next requests, for the audit log.
+    activity_ids: Mutex<Vec<i64>>,
 }
 
 impl LlmClient {
@@ -149,7 +195,78 @@ impl LlmClient {
             model: model.to_string(),
             endpoint: resolved_endpoint,
             workspace_dir,
+            cancel: None,
+            last_usage: Mutex::new(None),
+            fallbacks: Vec::new(),
+            answered_by: Mutex::new(None),
+            audit: None,
+            activity_ids: Mutex::new(Vec::new()),
+        }
+    }
+
+    pub fn from_settings(settings: &LlmSettings, workspace_dir: Option<PathBuf>) -> Self {
+        Self::new(
+            &settings.llm_provider,
+            &settings.api_key,
+            &settings.model,
+            &settings.api_endpoint,
+            workspace_dir,
+        )
+    }
+
+    /// Client for `step`, using its overrides or the main LLM settings, with the
+    /// configured fallback providers behind it.
+    pub fn for_step(config: &AppConfig, step: LlmStep) -> Self {
+        Self::from_settings(&config.llm_settings(step), Some(config.data_path()))
+            .with_fallbacks(&config.llm_fallbacks)
+            .with_audit(config)
+    }
+
+    /// Record every request (including fallback attempts) in the audit log.
+    pub fn with_audit(mut self, config: &AppConfig) -> Self {
+        self.audit = AuditSettings::from_config(config);
+        self
+    }
+
+    /// Activity rows the following requests are about; recorded in the audit log.
+    pub fn set_activity_ids(&self, ids: Vec<i64>) {
+        *self.activity_ids.lock().unwrap() = ids;
+    }
+
+    /// Providers to try, in order, when this one fails. Entries identical to this
+    /// client are skipped.
+    pub fn with_fallbacks(mut self, fallbacks: &[LlmSettings]) -> Self {
+        self.fallbacks = fallbacks
+            .iter()
+            .filter(|s| !s.llm_provider.trim().is_empty())
+            .map(|s| Self::from_settings(s, self.workspace_dir.clone()))
+            .filter(|c| c.breaker_key() != self.breaker_key())
+            .collect();
+        if let Some(token) = &self.cancel {
+            self.fallbacks = std::mem::take(&mut self.fallbacks)
+                .into_iter()
+                .map(|c| c.with_cancel(token.clone()))
+                .collect();
         }
+        self
+    }
+
+    /// Abort in-flight requests (and kill spawned CLI children) when `token` is cancelled.
+    pub fn with_cancel(mut self, token: CancelToken) -> Self {
+        self.fallbacks = std::mem::take(&mut self.fallbacks)
+            .into_iter()
+            .map(|c| c.with_cancel(token.clone()))
+            .collect();
+        self.cancel = Some(token);
+        self
+    }
+
+    fn breaker_key(&self) -> String {
+        format!("{}|{}|{}", self.provider, self.model, self.endpoint)
+    }
+
+    fn label(&self) -> String {
+        provider_label(&self.provider, &self.model)
     }
 
     fn prepare_workspace_dir(&self) -> Option<PathBuf> {
@@ -161,17 +278,237 @@ impl LlmClient {
         Some(dir)
     }
 
-    pub fn api_key_is_empty(&self) -> bool {
+    pub fn model(&self) -> &str {
+        &self.model
+    }
+
+    /// Token usage of the most recent request, if the provider reported it.
+    /// CLI providers never do.
+    pub fn last_usage(&self) -> Option<TokenUsage> {
+        *self.last_usage.lock().unwrap()
+    }
+
+    /// Provider and model that answered the last request, formatted for the
+    /// batch row ("ollama:llava", "claude-code"). `None` if it failed.
+    pub fn answered_by(&self) -> Option<String> {
+        self.answered_by
+            .lock()
+            .unwrap()
+            .as_ref()
+            .map(|(provider, model)| provider_label(provider, model))
+    }
+
+    /// Provider and model that answered the last request, falling back to the
+    /// configured ones.
+    pub fn answered_provider_model(&self) -> (String, String) {
+        self.answered_by
+            .lock()
+            .unwrap()
+            .clone()
+            .unwrap_or_else(|| (self.provider.clone(), self.model.clone()))
+    }
+
+    /// The CLI providers only receive the prompt text.
+    fn supports_images(&self) -> bool {
+        !matches!(self.provider.as_str(), "claude-code" | "codex")
+    }
+
+    fn lacks_key(&self) -> bool {
         if self.provider == "ollama" || self.provider == "claude-code" || self.provider == "codex" {
             return false;
         }
         self.api_key.is_empty()
     }
 
+    /// Whether this provider or a fallback can be reached. CLI providers run
+    /// locally and always count; for HTTP providers any response to a short
+    /// HEAD request, even an error status, means the host is up.
+    pub async fn is_reachable(&self) -> bool {
+        for client in std::iter::once(self).chain(&self.fallbacks) {
+            if client.lacks_key() {
+                continue;
+            }
+            if client.endpoint.is_empty() {
+                return true;
+            }
+            match client
+                .client
+                .head(&client.endpoint)
+                .timeout(Duration::from_secs(5))
+                .send()
+                .await
+            {
+                Ok(_) => return true,
+                Err(e) => log::debug!("{} unreachable: {}", client.label(), e),
+            }
+        }
+        false
+    }
+
+    /// Whether the last request to this provider or a fallback failed. Until
+    /// one of them answers again, the endpoint may be down.
+    pub fn has_failing_provider(&self) -> bool {
+        std::iter::once(self)
+            .chain(&self.fallbacks)
+            .any(|client| circuit_breaker::is_failing(&client.breaker_key()))
+    }
+
+    /// True when neither this provider nor any fallback can be called.
+    pub fn api_key_is_empty(&self) -> bool {
+        self.lacks_key() && self.fallbacks.iter().all(|c| c.lacks_key())
+    }
+
     pub async fn send_multimodal(
         &self,
         prompt: &str,
         images: &[Vec<u8>],
+    ) -> Result<String> {
+        self.send(prompt, None, images, false).await
+    }
+
+    /// Send a batch extraction: `prompt` with `images` to providers that accept
+    /// images, `text_prompt` alone to those that do not, so the CLIs are never
+    /// asked about screenshots they cannot see. `json_mode` asks HTTP providers
+    /// for a JSON object response (`response_format: json_object`); CLI
+    /// providers only get the prompt's instructions.
+    pub async fn send_extraction(
+        &self,
+        prompt: &str,
+        text_prompt: &str,
+        images: &[Vec<u8>],
+        json_mode: bool,
+    ) -> Result<String> {
+        self.send(prompt, Some(text_prompt), images, json_mode).await
+    }
+
+    /// Try this provider, then each fallback. Failures put a provider into a
+    /// circuit-breaker cooldown so later requests skip it for a while; when
+    /// every provider is cooling down, the one closest to the end of its
+    /// cooldown is still tried.
+    async fn send(
+        &self,
+        prompt: &str,
+        text_prompt: Option<&str>,
+        images: &[Vec<u8>],
+        json_mode: bool,
+    ) -> Result<String> {
+        *self.answered_by.lock().unwrap() = None;
+        *self.last_usage.lock().unwrap() = None;
+        let mut ready = Vec::new();
+        let mut cooling = Vec::new();
+        for client in std::iter::once(self).chain(&self.fallbacks) {
+            if client.lacks_key() {
+                continue;
+            }
+            match circuit_breaker::cooldown(&client.breaker_key()) {
+                Some(remaining) => {
+                    log::info!(
+                        "Skipping {} ({}s of cooldown left)",
+                        client.label(),
+                        remaining.as_secs()
+                    );
+                    cooling.push((remaining, client));
+                }
+                None => ready.push(client),
+            }
+        }
+        if ready.is_empty() {
+            if let Some((_, client)) = cooling.into_iter().min_by_key(|(remaining, _)| *remaining) {
+                log::info!("All LLM providers are cooling down, probing {}", client.label());
+                ready.push(client);
+            }
+        }
+
+        let mut last_error = None;
+        for client in ready {
+            let key = client.breaker_key();
+            let (prompt, images) = match text_prompt {
+                Some(text_prompt) if !client.supports_images() => (text_prompt, &[][..]),
+                _ => (prompt, images),
+            };
+            let result = client.send_once(prompt, images, json_mode).await;
+            self.audit(client, prompt, images, &result);
+            match result {
+                Ok(text) => {
+                    circuit_breaker::record_success(&key);
+                    if !std::ptr::eq(client, self) {
+                        log::info!("Request answered by fallback {}", client.label());
+                        *self.last_usage.lock().unwrap() = client.last_usage();
+                    }
+                    *self.answered_by.lock().unwrap() =
+                        Some((client.provider.clone(), client.model.clone()));
+                    return Ok(text);
+                }
+                Err(e) if e.is::<Cancelled>() => return Err(e),
+                Err(e) => {
+                    let cooldown = circuit_breaker::record_failure(&key);
+                    log::warn!(
+                        "{} failed, skipping it for {}s: {}",
+                        client.label(),
+                        cooldown.as_secs(),
+                        e
+                    );
+                    last_error = Some(e);
+                }
+            }
+        }
+        Err(last_error.unwrap_or_else(|| anyhow!("No LLM provider has an API key configured")))
+    }
+
+    fn audit(&self, client: &LlmClient, prompt: &str, images: &[Vec<u8>], result: &Result<String>) {
+        let Some(settings) = &self.audit else {
+            return;
+        };
+        let activity_ids = self.activity_ids.lock().unwrap().clone();
+        let secrets = [self.api_key.as_str(), client.api_key.as_str()];
+        let record = AuditRecord {
+            provider: &client.provider,
+            endpoint: &client.endpoint,
+            model: &client.model,
+            prompt,
+            images: if client.supports_images() { images } else { &[] },
+            response: match result {
+                Ok(text) => Ok(text.len()),
+                Err(e) => Err(e.to_string()),
+            },
+            activity_ids: &activity_ids,
+            secrets: &secrets,
+        };
+        if let Err(e) = audit_log::record(settings, record) {
+            log::warn!("Failed to write LLM audit entry: {}", e);
+        }
+    }
+
+    async fn send_once(
+        &self,
+        prompt: &str,
+        images: &[Vec<u8>],
+        json_mode: bool,
+    ) -> Result<String> {
+        let Some(token) = &self.cancel else {
+            return self.send_multimodal_inner(prompt, images, json_mode).await;
+        };
+        token.check()?;
+
+        // Dropping the inner future aborts the HTTP request, and CLI children
+        // are spawned with kill_on_drop so they die with it.
+        tokio::select! {
+            result = self.send_multimodal_inner(prompt, images, json_mode) => result,
+            _ = token.cancelled() => {
+                log::info!("Request to {} cancelled", self.provider);
+                if self.provider == "codex" {
+                    let _ = tokio::fs::remove_file(codex_output_file()).await;
+                }
+                Err(Cancelled.into())
+            }
+        }
+    }
+
+    async fn send_multimodal_inner(
+        &self,
+        prompt: &str,
+        images: &[Vec<u8>],
+        json_mode: bool,
     ) -> Result<String> {
         log::info!(
             "Sending request to {} ({}): model={}, images={}, key_len={}",
@@ -181,6 +518,7 @@ impl LlmClient {
             images.len(),
             self.api_key.len()
         );
+        *self.last_usage.lock().unwrap() = None;
 
         if self.provider == "claude-code" {
             return self.send_via_claude_cli(prompt).await;
@@ -196,9 +534,12 @@ impl LlmClient {
 
         for img in images {
             let b64 = base64::engine::general_purpose::STANDARD.encode(img);
+            let mime = image::guess_format(img)
+                .map(|format| format.to_mime_type())
+                .unwrap_or("image/jpeg");
             content_parts.push(ContentPart::ImageUrl {
                 image_url: ImageUrl {
-                    url: format!("data:image/jpeg;base64,{}", b64),
+                    url: format!("data:{};base64,{}", mime, b64),
                 },
             });
         }
@@ -210,6 +551,9 @@ impl LlmClient {
                 content: content_parts,
             }],
             max_tokens: 16384,
+            response_format: json_mode.then(|| ResponseFormat {
+                format_type: "json_object".to_string(),
+            }),
         };
 
         let mut req = self.client.post(&self.endpoint).json(&request);
@@ -251,6 +595,7 @@ impl LlmClient {
         if let Some(err) = body.error {
             return Err(anyhow!("API error: {}", err.message));
         }
+        *self.last_usage.lock().unwrap() = body.usage;
 
         let first_choice = body.choices.and_then(|c| c.into_iter().next());
 
@@ -285,6 +630,7 @@ impl LlmClient {
         }
 
         cmd
+            .kill_on_drop(true)
             .stdin(std::process::Stdio::piped())
             .stdout(std::process::Stdio::piped())
             .stderr(std::process::Stdio::piped());
@@ -328,7 +674,7 @@ impl LlmClient {
     async fn send_via_codex_cli(&self, prompt: &str) -> Result<String> {
         log::info!("Sending prompt to codex CLI ({} bytes)", prompt.len());
 
-        let output_file = std::env::temp_dir().join(format!("diaroo_codex_{}.txt", std::process::id()));
+        let output_file = codex_output_file();
         let output_path = output_file.to_string_lossy().to_string();
 
         let codex_bin = resolve_codex_path();
@@ -347,6 +693,7 @@ impl LlmClient {
             .arg("--output-last-message")
             .arg(&output_path)
             .arg("-")
+            .kill_on_drop(true)
             .stdin(std::process::Stdio::piped())
             .stdout(std::process::Stdio::piped())
             .stderr(std::process::Stdio::piped());
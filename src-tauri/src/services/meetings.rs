@@ -0,0 +1,921 @@
+use anyhow::{anyhow, Result};
+use chrono::{
+    DateTime, Datelike, Local, Months, NaiveDate, NaiveDateTime, TimeZone, Utc, Weekday,
+};
+use chrono_tz::Tz;
+use regex::Regex;
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+use crate::services::activity_log::ActivityLog;
+use crate::storage::config::AppConfig;
+
+/// `source` of intervals found while capturing.
+pub const SOURCE_DETECTED: &str = "detected";
+/// `source` of events imported from the calendar file.
+pub const SOURCE_CALENDAR: &str = "calendar";
+/// Platform of a detected interval when only the microphone or camera showed
+/// a call.
+const UNKNOWN_PLATFORM: &str = "Call";
+/// A meeting ends after this long without a signal; covers short looks at
+/// other windows during a call.
+const MEETING_GAP_SECS: i64 = 120;
+/// Detected intervals shorter than this are a glance at a meeting window,
+/// not a meeting.
+const MIN_MEETING_SECS: i64 = 60;
+/// Finding an open camera means reading every process's file descriptors,
+/// so the result is reused for this long.
+const CAMERA_CHECK_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Known conferencing apps: (platform, app name substring or "" for any app,
+/// window title pattern).
+const KNOWN_PLATFORMS: &[(&str, &str, &str)] = &[
+    ("Zoom", "zoom", r"(?i)\bzoom (meeting|webinar)\b|^meeting$"),
+    ("Google Meet", "", r"^Meet [-\u{2013}] "),
+    (
+        "Microsoft Teams",
+        "",
+        r"(?i)\b(meeting|call)\b.*\|\s*microsoft teams",
+    ),
+    ("Slack huddle", "slack", r"(?i)\bhuddle\b"),
+    ("Slack huddle", "", r"(?i)\bhuddle\b.*\bslack\b"),
+];
+
+/// Meeting URLs, for browsers whose window title hides the page title.
+const KNOWN_URLS: &[(&str, &str)] = &[
+    (
+        "Google Meet",
+        r"^https://meet\.google\.com/[a-z]{3}-[a-z]{4}-[a-z]{3}",
+    ),
+    (
+        "Microsoft Teams",
+        r"^https://teams\.microsoft\.com/.*(meetup-join|/call)",
+    ),
+];
+
+/// A meeting interval of the day, detected or imported from the calendar.
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct Meeting {
+    /// RFC 3339 local time.
+    pub start: String,
+    pub end: String,
+    /// Conferencing platform, or empty for calendar events.
+    pub platform: String,
+    /// Calendar event summary; empty for detected intervals.
+    pub title: String,
+    /// `SOURCE_DETECTED` or `SOURCE_CALENDAR`.
+    pub source: String,
+}
+
+impl Meeting {
+    fn times(&self) -> Option<(DateTime<Local>, DateTime<Local>)> {
+        let start = DateTime::parse_from_rfc3339(&self.start).ok()?;
+        let end = DateTime::parse_from_rfc3339(&self.end).ok()?;
+        Some((start.with_timezone(&Local), end.with_timezone(&Local)))
+    }
+}
+
+struct Pattern {
+    platform: String,
+    /// Lowercase app name substring; empty matches any app.
+    app: String,
+    title: Regex,
+}
+
+/// Decides from the focused window, the active tab and (on Linux) microphone
+/// and camera use whether the user is in a meeting.
+pub struct MeetingDetector {
+    patterns: Vec<Pattern>,
+    urls: Vec<(String, Regex)>,
+    devices: bool,
+    /// Last camera check and its result.
+    camera: Option<(Instant, bool)>,
+}
+
+impl MeetingDetector {
+    /// `None` when meeting detection is disabled.
+    pub fn new(config: &AppConfig) -> Option<Self> {
+        if !config.meeting_detection_enabled {
+            return None;
+        }
+        let mut patterns: Vec<Pattern> = KNOWN_PLATFORMS
+            .iter()
+            .map(|(platform, app, title)| Pattern {
+                platform: platform.to_string(),
+                app: app.to_string(),
+                title: Regex::new(title).unwrap(),
+            })
+            .collect();
+        for pattern in config.meeting_title_patterns.iter().map(|p| p.trim()) {
+            if pattern.is_empty() {
+                continue;
+            }
+            match Regex::new(pattern) {
+                Ok(title) => patterns.push(Pattern {
+                    platform: String::new(),
+                    app: String::new(),
+                    title,
+                }),
+                Err(e) => log::warn!("Ignoring invalid meeting pattern {:?}: {}", pattern, e),
+            }
+        }
+        Some(Self {
+            patterns,
+            urls: KNOWN_URLS
+                .iter()
+                .map(|(platform, url)| (platform.to_string(), Regex::new(url).unwrap()))
+                .collect(),
+            devices: config.meeting_device_detection,
+            camera: None,
+        })
+    }
+
+    /// Platform of the meeting the focused window shows. Custom patterns are
+    /// named after the app they matched.
+    pub fn match_window(
+        &self,
+        app_name: &str,
+        window_title: &str,
+        url: Option<&str>,
+    ) -> Option<String> {
+        let app = app_name.to_lowercase();
+        let by_title = self.patterns.iter().find(|p| {
+            (p.app.is_empty() || app.contains(p.app.as_str())) && p.title.is_match(window_title)
+        });
+        if let Some(pattern) = by_title {
+            return Some(match pattern.platform.as_str() {
+                "" if app_name.is_empty() => UNKNOWN_PLATFORM.to_string(),
+                "" => app_name.to_string(),
+                platform => platform.to_string(),
+            });
+        }
+        let url = url?;
+        self.urls
+            .iter()
+            .find(|(_, pattern)| pattern.is_match(url))
+            .map(|(platform, _)| platform.clone())
+    }
+
+    /// Whether the microphone or camera is in use, when device detection is
+    /// enabled and the OS exposes it.
+    pub async fn devices_in_use(&mut self) -> bool {
+        if !self.devices {
+            return false;
+        }
+        if microphone_in_use().await {
+            return true;
+        }
+        match self.camera {
+            Some((checked, in_use)) if checked.elapsed() < CAMERA_CHECK_INTERVAL => in_use,
+            _ => {
+                let in_use = camera_in_use();
+                self.camera = Some((Instant::now(), in_use));
+                in_use
+            }
+        }
+    }
+}
+
+/// Whether an application is recording from a microphone: a PulseAudio (or
+/// PipeWire's pulse server) source output on a source that is not the
+/// monitor of an output device.
+#[cfg(target_os = "linux")]
+async fn microphone_in_use() -> bool {
+    let Some(outputs) = pactl(&["list", "short", "source-outputs"]).await else {
+        return false;
+    };
+    if outputs.trim().is_empty() {
+        return false;
+    }
+    let Some(sources) = pactl(&["list", "short", "sources"]).await else {
+        return false;
+    };
+    // "<index>\t<name>\t..." for both lists; outputs name their source by index
+    let monitors: Vec<&str> = sources
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.split('\t');
+            let index = fields.next()?;
+            fields.next()?.ends_with(".monitor").then_some(index)
+        })
+        .collect();
+    outputs
+        .lines()
+        .filter_map(|line| line.split('\t').nth(1))
+        .any(|source| !monitors.contains(&source))
+}
+
+#[cfg(target_os = "linux")]
+async fn pactl(args: &[&str]) -> Option<String> {
+    let mut cmd = tokio::process::Command::new("pactl");
+    super::shell_path::apply_shell_path(&mut cmd);
+    cmd.args(args)
+        .kill_on_drop(true)
+        .stdin(std::process::Stdio::null())
+        .stdout(std::process::Stdio::piped())
+        .stderr(std::process::Stdio::null());
+    match tokio::time::timeout(Duration::from_secs(2), cmd.output()).await {
+        Ok(Ok(output)) if output.status.success() => {
+            Some(String::from_utf8_lossy(&output.stdout).to_string())
+        }
+        Ok(Ok(_)) => None,
+        Ok(Err(e)) => {
+            log::debug!("Failed to run pactl: {}", e);
+            None
+        }
+        Err(_) => None,
+    }
+}
+
+/// Whether a process has a video device open. Only processes of the current
+/// user are visible, which includes the conferencing app.
+#[cfg(target_os = "linux")]
+fn camera_in_use() -> bool {
+    let Ok(processes) = std::fs::read_dir("/proc") else {
+        return false;
+    };
+    processes.flatten().any(|process| {
+        std::fs::read_dir(process.path().join("fd")).is_ok_and(|fds| {
+            fds.flatten().any(|fd| {
+                std::fs::read_link(fd.path())
+                    .is_ok_and(|target| target.to_string_lossy().starts_with("/dev/video"))
+            })
+        })
+    })
+}
+
+/// Microphone and camera use are only read on Linux.
+#[cfg(not(target_os = "linux"))]
+async fn microphone_in_use() -> bool {
+    false
+}
+
+#[cfg(not(target_os = "linux"))]
+fn camera_in_use() -> bool {
+    false
+}
+
+/// Turns per-tick meeting signals into intervals in the activity database.
+#[derive(Default)]
+pub struct MeetingTracker {
+    current: Option<OpenMeeting>,
+}
+
+struct OpenMeeting {
+    id: i64,
+    date: NaiveDate,
+    platform: String,
+    last_seen: DateTime<Local>,
+}
+
+impl MeetingTracker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Record one capture tick. `platform` is the meeting the focused window
+    /// shows; `devices` whether the microphone or camera is in use, which
+    /// keeps a meeting going while another window has focus.
+    pub fn update(
+        &mut self,
+        activity_log: &ActivityLog,
+        platform: Option<String>,
+        devices: bool,
+    ) -> Result<()> {
+        let now = Local::now();
+        let open = self.current.take().filter(|meeting| {
+            meeting.date == now.date_naive()
+                && (now - meeting.last_seen).num_seconds() <= MEETING_GAP_SECS
+        });
+        if platform.is_none() && !devices {
+            self.current = open;
+            return Ok(());
+        }
+
+        let timestamp = now.to_rfc3339();
+        self.current = Some(match open {
+            Some(mut meeting) => {
+                // A call first seen through the microphone gets its platform later
+                if let Some(platform) = platform.filter(|_| meeting.platform == UNKNOWN_PLATFORM) {
+                    meeting.platform = platform;
+                }
+                activity_log.extend_meeting(meeting.id, &timestamp, &meeting.platform)?;
+                meeting.last_seen = now;
+                meeting
+            }
+            None => {
+                let platform = platform.unwrap_or_else(|| UNKNOWN_PLATFORM.to_string());
+                let id = activity_log.start_meeting(&timestamp, &platform)?;
+                log::debug!("Meeting started: {}", platform);
+                OpenMeeting {
+                    id,
+                    date: now.date_naive(),
+                    platform,
+                    last_seen: now,
+                }
+            }
+        });
+        Ok(())
+    }
+}
+
+/// Meetings of `date`: the detected intervals and, when a calendar file is
+/// configured, its events of that day read afresh. Nothing is written; the
+/// digest job stores the events with `import_calendar`.
+pub fn for_date(
+    activity_log: &ActivityLog,
+    config: &AppConfig,
+    date: &str,
+) -> Result<Vec<Meeting>> {
+    let mut meetings = activity_log.get_meetings_for_date(date)?;
+    if let Some(events) = read_calendar(config, date) {
+        meetings.retain(|meeting| meeting.source != SOURCE_CALENDAR);
+        meetings.extend(events);
+        meetings.sort_by(|a, b| a.start.cmp(&b.start));
+    }
+    Ok(meetings)
+}
+
+/// Store the calendar events of `date` in its database, replacing the ones
+/// imported before.
+pub fn import_calendar(activity_log: &ActivityLog, config: &AppConfig, date: &str) -> Result<()> {
+    if let Some(events) = read_calendar(config, date) {
+        activity_log.replace_calendar_meetings(date, &events)?;
+    }
+    Ok(())
+}
+
+/// Events of `date` in the configured calendar file; `None` without one or
+/// when it cannot be read.
+fn read_calendar(config: &AppConfig, date: &str) -> Option<Vec<Meeting>> {
+    let path = config.meeting_calendar_path.trim();
+    if path.is_empty() {
+        return None;
+    }
+    match calendar_events(Path::new(path), date) {
+        Ok(events) => Some(events),
+        Err(e) => {
+            log::warn!("Failed to import calendar {}: {}", path, e);
+            None
+        }
+    }
+}
+
+/// Timed events of `date` in an iCalendar file. All-day and cancelled events
+/// are skipped. Times with a TZID are read in that IANA time zone; other zone
+/// names (such as Windows ones) are read as local time. Recurring events are
+/// expanded for FREQ=DAILY, WEEKLY, MONTHLY and YEARLY rules with INTERVAL,
+/// COUNT, UNTIL and weekly BYDAY, minus EXDATEs and moved occurrences; other
+/// rules only count on the day of their first occurrence.
+pub fn calendar_events(path: &Path, date: &str) -> Result<Vec<Meeting>> {
+    let content = std::fs::read_to_string(path)?;
+    let date =
+        NaiveDate::parse_from_str(date, "%Y-%m-%d").map_err(|e| anyhow!("bad date: {}", e))?;
+    Ok(events_on(&parse_calendar(&content), date))
+}
+
+fn events_on(events: &[CalendarEvent], date: NaiveDate) -> Vec<Meeting> {
+    // Moved, edited or cancelled occurrences are events of their own with the
+    // UID of the series and the original start as RECURRENCE-ID
+    let overridden: Vec<(&str, DateTime<Local>)> = events
+        .iter()
+        .filter_map(|event| Some((event.uid.as_str(), event.recurrence_id?)))
+        .collect();
+    events
+        .iter()
+        .filter(|event| !event.cancelled)
+        .filter_map(|event| {
+            let start = event.occurrence_on(date)?;
+            if event.rule.is_some() && overridden.contains(&(event.uid.as_str(), start)) {
+                return None;
+            }
+            Some(Meeting {
+                start: start.to_rfc3339(),
+                end: (start + event.length).to_rfc3339(),
+                platform: String::new(),
+                title: event.summary.clone(),
+                source: SOURCE_CALENDAR.to_string(),
+            })
+        })
+        .collect()
+}
+
+/// Zone of a calendar time.
+#[derive(Debug, Clone, Copy)]
+enum Zone {
+    Utc,
+    Named(Tz),
+    Local,
+}
+
+/// A DTSTART, DTEND or EXDATE value: wall-clock time in its zone, so
+/// recurrences keep their time across daylight saving changes.
+#[derive(Debug, Clone, Copy)]
+struct CalendarTime {
+    time: NaiveDateTime,
+    zone: Zone,
+}
+
+impl CalendarTime {
+    fn resolve(&self) -> Option<DateTime<Local>> {
+        self.at(self.time)
+    }
+
+    /// The instant of wall-clock `time` in this time's zone.
+    fn at(&self, time: NaiveDateTime) -> Option<DateTime<Local>> {
+        match self.zone {
+            Zone::Utc => Some(Utc.from_utc_datetime(&time).with_timezone(&Local)),
+            Zone::Named(tz) => tz
+                .from_local_datetime(&time)
+                .earliest()
+                .map(|time| time.with_timezone(&Local)),
+            Zone::Local => Local.from_local_datetime(&time).earliest(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Frequency {
+    Daily,
+    Weekly,
+    Monthly,
+    Yearly,
+}
+
+/// The parts of an RRULE that are expanded.
+#[derive(Debug)]
+struct Rule {
+    frequency: Frequency,
+    interval: i64,
+    count: Option<usize>,
+    until: Option<DateTime<Local>>,
+    /// Days of a weekly rule; empty for the weekday of the first occurrence.
+    weekdays: Vec<Weekday>,
+}
+
+impl Rule {
+    /// `None` for rules with parts that are not expanded.
+    fn parse(value: &str) -> Option<Self> {
+        let mut rule = Rule {
+            frequency: Frequency::Daily,
+            interval: 1,
+            count: None,
+            until: None,
+            weekdays: Vec::new(),
+        };
+        let mut frequency = None;
+        for part in value.split(';').filter(|part| !part.is_empty()) {
+            let (name, value) = part.split_once('=')?;
+            match name.to_ascii_uppercase().as_str() {
+                "FREQ" => {
+                    frequency = Some(match value.to_ascii_uppercase().as_str() {
+                        "DAILY" => Frequency::Daily,
+                        "WEEKLY" => Frequency::Weekly,
+                        "MONTHLY" => Frequency::Monthly,
+                        "YEARLY" => Frequency::Yearly,
+                        _ => return None,
+                    })
+                }
+                "INTERVAL" => rule.interval = value.parse::<i64>().ok().filter(|n| *n > 0)?,
+                "COUNT" => rule.count = Some(value.parse().ok()?),
+                "UNTIL" => {
+                    rule.until = match parse_date_time(value, None) {
+                        Some(until) => until.resolve(),
+                        // A date: occurrences on that day still count
+                        None => Local
+                            .from_local_datetime(
+                                &NaiveDate::parse_from_str(value, "%Y%m%d")
+                                    .ok()?
+                                    .and_hms_opt(23, 59, 59)?,
+                            )
+                            .earliest(),
+                    }
+                }
+                "BYDAY" => {
+                    rule.weekdays = value.split(',').map(parse_weekday).collect::<Option<_>>()?
+                }
+                "WKST" => {}
+                _ => return None,
+            }
+        }
+        rule.frequency = frequency?;
+        if !rule.weekdays.is_empty() && rule.frequency != Frequency::Weekly {
+            return None;
+        }
+        Some(rule)
+    }
+
+    /// Whether the rule has an occurrence on `day` (in the event's zone) for a
+    /// series starting on `first`. COUNT and UNTIL are not checked.
+    fn matches(&self, first: NaiveDate, day: NaiveDate) -> bool {
+        if day < first {
+            return false;
+        }
+        match self.frequency {
+            Frequency::Daily => (day - first).num_days() % self.interval == 0,
+            Frequency::Weekly => {
+                self.weekdays(first).contains(&day.weekday().num_days_from_monday())
+                    && weeks_between(first, day) % self.interval == 0
+            }
+            Frequency::Monthly => {
+                day.day() == first.day() && months_between(first, day) % self.interval == 0
+            }
+            Frequency::Yearly => {
+                day.month() == first.month()
+                    && day.day() == first.day()
+                    && months_between(first, day) % (12 * self.interval) == 0
+            }
+        }
+    }
+
+    /// Zero-based number of the occurrence on `day`, a day the rule matches,
+    /// for checking COUNT.
+    fn index(&self, first: NaiveDate, day: NaiveDate) -> usize {
+        match self.frequency {
+            Frequency::Daily => ((day - first).num_days() / self.interval) as usize,
+            Frequency::Weekly => {
+                let weekdays = self.weekdays(first);
+                let earlier = |d: NaiveDate| {
+                    let weekday = d.weekday().num_days_from_monday();
+                    weekdays.iter().filter(|w| **w < weekday).count()
+                };
+                let weeks = (weeks_between(first, day) / self.interval) as usize;
+                weeks * weekdays.len() + earlier(day) - earlier(first)
+            }
+            // Months without the day of the first occurrence have none
+            Frequency::Monthly | Frequency::Yearly => {
+                let step = match self.frequency {
+                    Frequency::Yearly => 12 * self.interval,
+                    _ => self.interval,
+                };
+                (0..months_between(first, day) / step)
+                    .filter(|n| {
+                        first
+                            .checked_add_months(Months::new((n * step) as u32))
+                            .is_some_and(|d| d.day() == first.day())
+                    })
+                    .count()
+            }
+        }
+    }
+
+    /// Days of a weekly rule counted from Monday, sorted.
+    fn weekdays(&self, first: NaiveDate) -> Vec<u32> {
+        let mut days: Vec<u32> = match self.weekdays.is_empty() {
+            true => vec![first.weekday().num_days_from_monday()],
+            false => self.weekdays.iter().map(|d| d.num_days_from_monday()).collect(),
+        };
+        days.sort_unstable();
+        days.dedup();
+        days
+    }
+}
+
+/// Calendar weeks (Monday to Sunday) from the week of `from` to that of `to`.
+fn weeks_between(from: NaiveDate, to: NaiveDate) -> i64 {
+    let monday = |d: NaiveDate| d - chrono::Duration::days(d.weekday().num_days_from_monday() as i64);
+    (monday(to) - monday(from)).num_days() / 7
+}
+
+/// Calendar months from the month of `from` to that of `to`.
+fn months_between(from: NaiveDate, to: NaiveDate) -> i64 {
+    (to.year() - from.year()) as i64 * 12 + to.month() as i64 - from.month() as i64
+}
+
+fn parse_weekday(value: &str) -> Option<Weekday> {
+    Some(match value.trim().to_ascii_uppercase().as_str() {
+        "MO" => Weekday::Mon,
+        "TU" => Weekday::Tue,
+        "WE" => Weekday::Wed,
+        "TH" => Weekday::Thu,
+        "FR" => Weekday::Fri,
+        "SA" => Weekday::Sat,
+        "SU" => Weekday::Sun,
+        _ => return None,
+    })
+}
+
+struct CalendarEvent {
+    uid: String,
+    start: CalendarTime,
+    length: chrono::Duration,
+    summary: String,
+    cancelled: bool,
+    rule: Option<Rule>,
+    exdates: Vec<DateTime<Local>>,
+    /// Start of the series occurrence this event replaces.
+    recurrence_id: Option<DateTime<Local>>,
+}
+
+impl CalendarEvent {
+    /// Start of the event's occurrence on the local day `date`, if any.
+    fn occurrence_on(&self, date: NaiveDate) -> Option<DateTime<Local>> {
+        let first = self.start.resolve()?;
+        let Some(rule) = &self.rule else {
+            return (first.date_naive() == date).then_some(first);
+        };
+        let first_day = self.start.time.date();
+        // The event's zone can put an occurrence on the neighbouring local day
+        [date.pred_opt()?, date, date.succ_opt()?]
+            .into_iter()
+            .filter(|day| rule.matches(first_day, *day))
+            .filter(|day| rule.count.map_or(true, |count| rule.index(first_day, *day) < count))
+            .filter_map(|day| self.start.at(day.and_time(self.start.time.time())))
+            .find(|start| {
+                start.date_naive() == date
+                    && rule.until.map_or(true, |until| *start <= until)
+                    && !self.exdates.contains(start)
+            })
+    }
+}
+
+/// Properties of the VEVENT being read.
+#[derive(Default)]
+struct EventFields {
+    uid: String,
+    start: Option<CalendarTime>,
+    end: Option<CalendarTime>,
+    duration: Option<Duration>,
+    summary: String,
+    cancelled: bool,
+    rule: Option<String>,
+    exdates: Vec<CalendarTime>,
+    recurrence_id: Option<CalendarTime>,
+}
+
+impl EventFields {
+    /// All-day events have a date without a time, which does not parse: they
+    /// are not meetings.
+    fn into_event(self) -> Option<CalendarEvent> {
+        let start = self.start?;
+        let first = start.resolve()?;
+        let length = match (self.end.and_then(|end| end.resolve()), self.duration) {
+            (Some(end), _) => end - first,
+            (None, Some(duration)) => chrono::Duration::from_std(duration).ok()?,
+            (None, None) => chrono::Duration::zero(),
+        };
+        let rule = self.rule.as_deref().and_then(|value| {
+            let rule = Rule::parse(value);
+            if rule.is_none() {
+                log::debug!("Recurrence rule {:?} is not expanded", value);
+            }
+            rule
+        });
+        Some(CalendarEvent {
+            uid: self.uid,
+            start,
+            length: length.max(chrono::Duration::zero()),
+            summary: self.summary,
+            cancelled: self.cancelled,
+            rule,
+            exdates: self.exdates.iter().filter_map(CalendarTime::resolve).collect(),
+            recurrence_id: self.recurrence_id.and_then(|id| id.resolve()),
+        })
+    }
+}
+
+fn parse_calendar(content: &str) -> Vec<CalendarEvent> {
+    // Long lines are folded: continuation lines start with a space or tab
+    let mut lines: Vec<String> = Vec::new();
+    for line in content.lines() {
+        match (line.strip_prefix([' ', '\t']), lines.last_mut()) {
+            (Some(rest), Some(last)) => last.push_str(rest),
+            _ => lines.push(line.to_string()),
+        }
+    }
+
+    let mut events = Vec::new();
+    let mut event: Option<EventFields> = None;
+    for line in &lines {
+        let Some((name, value)) = line.split_once(':') else {
+            continue;
+        };
+        // Parameters such as ";TZID=Europe/Berlin" follow the property name
+        let mut params = name.split(';');
+        let name = params.next().unwrap_or_default().to_ascii_uppercase();
+        let tzid = params.find_map(|param| {
+            let (key, value) = param.split_once('=')?;
+            key.eq_ignore_ascii_case("TZID").then(|| value.trim_matches('"'))
+        });
+        match (name.as_str(), event.as_mut()) {
+            ("BEGIN", None) if value.eq_ignore_ascii_case("VEVENT") => {
+                event = Some(EventFields::default());
+            }
+            ("END", Some(_)) if value.eq_ignore_ascii_case("VEVENT") => {
+                events.extend(event.take().and_then(EventFields::into_event));
+            }
+            ("UID", Some(fields)) => fields.uid = value.to_string(),
+            ("DTSTART", Some(fields)) => fields.start = parse_date_time(value, tzid),
+            ("DTEND", Some(fields)) => fields.end = parse_date_time(value, tzid),
+            ("DURATION", Some(fields)) => fields.duration = parse_duration(value),
+            ("SUMMARY", Some(fields)) => fields.summary = unescape(value),
+            ("STATUS", Some(fields)) => fields.cancelled = value.eq_ignore_ascii_case("CANCELLED"),
+            ("RRULE", Some(fields)) => fields.rule = Some(value.to_string()),
+            ("EXDATE", Some(fields)) => fields
+                .exdates
+                .extend(value.split(',').filter_map(|v| parse_date_time(v, tzid))),
+            ("RECURRENCE-ID", Some(fields)) => fields.recurrence_id = parse_date_time(value, tzid),
+            _ => {}
+        }
+    }
+    events
+}
+
+/// "20261018T100000Z" (UTC) or "20261018T100000" (in the `tzid` zone, or
+/// local time).
+fn parse_date_time(value: &str, tzid: Option<&str>) -> Option<CalendarTime> {
+    if let Some(utc) = value.strip_suffix('Z') {
+        return Some(CalendarTime {
+            time: NaiveDateTime::parse_from_str(utc, "%Y%m%dT%H%M%S").ok()?,
+            zone: Zone::Utc,
+        });
+    }
+    let time = NaiveDateTime::parse_from_str(value, "%Y%m%dT%H%M%S").ok()?;
+    let zone = match tzid.map(|tzid| (tzid, tzid.parse::<Tz>())) {
+        Some((_, Ok(tz))) => Zone::Named(tz),
+        Some((tzid, Err(_))) => {
+            log::debug!("Unknown calendar time zone {:?}, using local time", tzid);
+            Zone::Local
+        }
+        None => Zone::Local,
+    };
+    Some(CalendarTime { time, zone })
+}
+
+/// "PT1H30M", "P1D" and similar; weeks and signs are not used by meetings.
+fn parse_duration(value: &str) -> Option<Duration> {
+    let mut secs = 0u64;
+    let mut number = String::new();
+    for c in value.strip_prefix('P')?.chars() {
+        match c {
+            '0'..='9' => number.push(c),
+            'T' => {}
+            unit => {
+                let n: u64 = number.parse().ok()?;
+                number.clear();
+                secs += n * match unit {
+                    'W' => 7 * 86400,
+                    'D' => 86400,
+                    'H' => 3600,
+                    'M' => 60,
+                    'S' => 1,
+                    _ => return None,
+                };
+            }
+        }
+    }
+    Some(Duration::from_secs(secs))
+}
+
+fn unescape(value: &str) -> String {
+    value
+        .replace("\\n", " ")
+        .replace("\\N", " ")
+        .replace("\\,", ",")
+        .replace("\\;", ";")
+        .replace("\\\\", "\\")
+}
+
+/// One bullet per meeting for `{meetings}`, oldest first, with the total time
+/// in meetings (overlapping detected and calendar intervals count once).
+/// Empty without meetings.
+pub fn to_text(meetings: &[Meeting]) -> String {
+    let mut intervals: Vec<(DateTime<Local>, DateTime<Local>, &Meeting)> = meetings
+        .iter()
+        .filter_map(|m| m.times().map(|(start, end)| (start, end, m)))
+        .filter(|(start, end, m)| {
+            m.source != SOURCE_DETECTED || (*end - *start).num_seconds() >= MIN_MEETING_SECS
+        })
+        .collect();
+    if intervals.is_empty() {
+        return String::new();
+    }
+    intervals.sort_by_key(|(start, _, _)| *start);
+
+    let mut text = String::new();
+    for (start, end, meeting) in &intervals {
+        let name = match (meeting.platform.as_str(), meeting.title.as_str()) {
+            (platform, "") => platform.to_string(),
+            ("", title) => title.to_string(),
+            (platform, title) => format!("{} ({})", title, platform),
+        };
+        text.push_str(&format!(
+            "- {}\u{2013}{} ({} min): {} [{}]\n",
+            start.format("%H:%M"),
+            end.format("%H:%M"),
+            (*end - *start).num_minutes(),
+            name,
+            meeting.source
+        ));
+    }
+
+    let mut total_secs = 0;
+    let mut covered_until: Option<DateTime<Local>> = None;
+    for (start, end, _) in &intervals {
+        let from = covered_until.map_or(*start, |until| until.max(*start));
+        if *end > from {
+            total_secs += (*end - from).num_seconds();
+            covered_until = Some(*end);
+        }
+    }
+    text.push_str(&format!("Total: ~{} min in meetings\n", total_secs / 60));
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Calendar events of `date` in a calendar made of `events`.
+    fn on(events: &str, date: &str) -> Vec<Meeting> {
+        let content = format!("BEGIN:VCALENDAR\r\n{}END:VCALENDAR\r\n", events);
+        events_on(&parse_calendar(&content), date.parse().unwrap())
+    }
+
+    fn local(time: &str) -> String {
+        let time = NaiveDateTime::parse_from_str(time, "%Y-%m-%d %H:%M").unwrap();
+        Local.from_local_datetime(&time).unwrap().to_rfc3339()
+    }
+
+    fn starts(meetings: &[Meeting]) -> Vec<String> {
+        meetings.iter().map(|m| m.start.clone()).collect()
+    }
+
+    #[test]
+    fn weekly_rule_with_days_and_interval() {
+        let events = "BEGIN:VEVENT\r\nUID:sync\r\nDTSTART:20261005T090000\r\nDTEND:20261005T093000\r\n\
+                      RRULE:FREQ=WEEKLY;INTERVAL=2;BYDAY=MO,TH\r\nSUMMARY:Sync\r\nEND:VEVENT\r\n";
+        for date in ["2026-10-05", "2026-10-08", "2026-10-19", "2026-10-22"] {
+            let meetings = on(events, date);
+            assert_eq!(starts(&meetings), vec![local(&format!("{} 09:00", date))]);
+            assert_eq!(meetings[0].end, local(&format!("{} 09:30", date)));
+        }
+        for date in ["2026-09-28", "2026-10-06", "2026-10-12", "2026-10-15"] {
+            assert!(on(events, date).is_empty(), "{}", date);
+        }
+    }
+
+    #[test]
+    fn count_and_until_end_the_series() {
+        let counted = "BEGIN:VEVENT\r\nUID:a\r\nDTSTART:20261005T090000\r\n\
+                       RRULE:FREQ=WEEKLY;BYDAY=MO,WE;COUNT=3\r\nSUMMARY:A\r\nEND:VEVENT\r\n";
+        assert_eq!(on(counted, "2026-10-12").len(), 1);
+        assert!(on(counted, "2026-10-14").is_empty());
+
+        // The 31st is missing in February and April and does not count
+        let monthly = "BEGIN:VEVENT\r\nUID:b\r\nDTSTART:20260131T090000\r\n\
+                       RRULE:FREQ=MONTHLY;COUNT=3\r\nSUMMARY:B\r\nEND:VEVENT\r\n";
+        assert_eq!(on(monthly, "2026-05-31").len(), 1);
+        assert!(on(monthly, "2026-02-28").is_empty());
+        assert!(on(monthly, "2026-07-31").is_empty());
+
+        let until = "BEGIN:VEVENT\r\nUID:c\r\nDTSTART:20261005T090000\r\n\
+                     RRULE:FREQ=DAILY;UNTIL=20261010\r\nSUMMARY:C\r\nEND:VEVENT\r\n";
+        assert_eq!(on(until, "2026-10-10").len(), 1);
+        assert!(on(until, "2026-10-11").is_empty());
+    }
+
+    #[test]
+    fn excluded_and_moved_occurrences() {
+        let events = "BEGIN:VEVENT\r\nUID:standup\r\nDTSTART:20261005T090000\r\nDURATION:PT15M\r\n\
+                      RRULE:FREQ=DAILY\r\nEXDATE:20261007T090000\r\nSUMMARY:Standup\r\nEND:VEVENT\r\n\
+                      BEGIN:VEVENT\r\nUID:standup\r\nRECURRENCE-ID:20261008T090000\r\n\
+                      DTSTART:20261008T140000\r\nDURATION:PT15M\r\nSUMMARY:Standup (moved)\r\nEND:VEVENT\r\n";
+        assert!(on(events, "2026-10-07").is_empty());
+        let moved = on(events, "2026-10-08");
+        assert_eq!(starts(&moved), vec![local("2026-10-08 14:00")]);
+        assert_eq!(moved[0].title, "Standup (moved)");
+        assert_eq!(starts(&on(events, "2026-10-09")), vec![local("2026-10-09 09:00")]);
+    }
+
+    #[test]
+    fn time_zone_can_move_an_event_to_another_local_day() {
+        // A zone far enough from the local one that the event changes day
+        let noon = NaiveDate::from_ymd_opt(2026, 10, 18).unwrap().and_hms_opt(12, 0, 0).unwrap();
+        let offset = Local.from_local_datetime(&noon).unwrap().offset().local_minus_utc();
+        let (tzid, start, zone_date, utc) = if offset >= 0 {
+            ("Pacific/Pago_Pago", "20261017T200000", "2026-10-17", "2026-10-18T07:00:00Z")
+        } else {
+            ("Pacific/Kiritimati", "20261019T040000", "2026-10-19", "2026-10-18T14:00:00Z")
+        };
+        let events = format!(
+            "BEGIN:VEVENT\r\nUID:tz\r\nDTSTART;TZID={}:{}\r\nSUMMARY:Call\r\nEND:VEVENT\r\n",
+            tzid, start
+        );
+        let expected = DateTime::parse_from_rfc3339(utc).unwrap().with_timezone(&Local);
+        assert_eq!(starts(&on(&events, "2026-10-18")), vec![expected.to_rfc3339()]);
+        assert!(on(&events, zone_date).is_empty());
+    }
+
+    #[test]
+    fn folded_summary_and_skipped_events() {
+        let events = "BEGIN:VEVENT\r\nUID:a\r\nDTSTART:20261018T100000\r\nDTEND:20261018T110000\r\n\
+                      SUMMARY:Quarterly planning\\, product\r\n  and design\r\nEND:VEVENT\r\n\
+                      BEGIN:VEVENT\r\nUID:b\r\nDTSTART;VALUE=DATE:20261018\r\nSUMMARY:Holiday\r\nEND:VEVENT\r\n\
+                      BEGIN:VEVENT\r\nUID:c\r\nDTSTART:20261018T130000\r\nSTATUS:CANCELLED\r\n\
+                      SUMMARY:Cancelled\r\nEND:VEVENT\r\n";
+        let meetings = on(events, "2026-10-18");
+        assert_eq!(meetings.len(), 1);
+        assert_eq!(meetings[0].title, "Quarterly planning, product and design");
+    }
+}
@@ -1,9 +1,26 @@
 pub mod activity_log;
 pub mod auto_report;
+pub mod browser_tab;
+pub mod circuit_breaker;
+pub mod commits;
+pub mod dev_context;
 pub mod digest_generator;
+pub mod extraction;
+pub mod git;
+pub mod image_redaction;
+pub mod jobs;
+pub mod keyframes;
 pub mod llm_client;
+pub mod meetings;
+pub mod note_export;
+pub mod ocr;
+pub mod offline_queue;
+pub mod prompt_template;
+pub mod redaction;
 pub mod scheduled_monitoring;
 pub mod scheduler;
 pub mod screenshot;
 pub mod shell_path;
+pub mod summarization;
+pub mod timesheet;
 pub mod window_info;
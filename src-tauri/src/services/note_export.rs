@@ -0,0 +1,210 @@
+use anyhow::{anyhow, Result};
+use std::path::{Component, Path, PathBuf};
+
+use crate::storage::config::AppConfig;
+
+/// Markers around the digest inside a note, so re-generating replaces exactly
+/// what was written last time and nothing else.
+const START_MARKER: &str = "<!-- diaroo:start -->";
+const END_MARKER: &str = "<!-- diaroo:end -->";
+
+/// Frontmatter keys owned by Diaroo; any other keys in the note are preserved.
+const OWNED_KEYS: &[&str] = &["date", "apps", "projects", "hours_tracked"];
+
+pub struct NoteMetadata {
+    pub date: String,
+    pub apps: Vec<String>,
+    pub projects: Vec<String>,
+    pub hours_tracked: f64,
+}
+
+/// Write the digest into the configured notes vault (Obsidian, Logseq, ...).
+/// Returns the note path, or `None` when note export is disabled.
+pub fn export_report(
+    config: &AppConfig,
+    report: &str,
+    meta: &NoteMetadata,
+) -> Result<Option<PathBuf>> {
+    if !config.note_export_enabled {
+        return Ok(None);
+    }
+    if config.note_export_vault_path.trim().is_empty() {
+        return Err(anyhow!("Note export is enabled but no vault path is set"));
+    }
+
+    let path = note_path(
+        Path::new(config.note_export_vault_path.trim()),
+        &config.note_export_filename_template,
+        &meta.date,
+    )?;
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+
+    let existing = std::fs::read_to_string(&path).ok();
+    let contents = match (config.note_export_mode.as_str(), existing) {
+        ("append_under_heading", Some(existing)) => {
+            merge_into_note(&existing, report, meta, &config.note_export_heading)
+        }
+        ("append_under_heading", None) => {
+            let body = format!(
+                "{}\n\n{}\n",
+                config.note_export_heading.trim(),
+                marked_block(report)
+            );
+            format!("{}\n{}", render_frontmatter(&[], meta), body)
+        }
+        _ => format!("{}\n{}\n", render_frontmatter(&[], meta), report.trim_end()),
+    };
+
+    let tmp_path = path.with_extension("md.tmp");
+    std::fs::write(&tmp_path, contents)?;
+    std::fs::rename(&tmp_path, &path)?;
+    Ok(Some(path))
+}
+
+/// Resolve `template` (e.g. `Journal/{date}.md`) inside the vault. The result
+/// must stay within the vault.
+fn note_path(vault: &Path, template: &str, date: &str) -> Result<PathBuf> {
+    let mut parts = date.splitn(3, '-');
+    let (year, month, day) = (
+        parts.next().unwrap_or_default(),
+        parts.next().unwrap_or_default(),
+        parts.next().unwrap_or_default(),
+    );
+    let template = if template.trim().is_empty() {
+        "{date}.md"
+    } else {
+        template.trim()
+    };
+    let relative = template
+        .replace("{date}", date)
+        .replace("{year}", year)
+        .replace("{month}", month)
+        .replace("{day}", day);
+
+    let relative = Path::new(&relative);
+    if relative
+        .components()
+        .any(|c| !matches!(c, Component::Normal(_) | Component::CurDir))
+    {
+        return Err(anyhow!(
+            "Note filename template must be a relative path inside the vault: {}",
+            template
+        ));
+    }
+    Ok(vault.join(relative))
+}
+
+fn marked_block(report: &str) -> String {
+    format!("{}\n{}\n{}", START_MARKER, report.trim(), END_MARKER)
+}
+
+fn yaml_string(value: &str) -> String {
+    format!("\"{}\"", value.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+fn yaml_list(items: &[String]) -> String {
+    let quoted: Vec<String> = items.iter().map(|i| yaml_string(i)).collect();
+    format!("[{}]", quoted.join(", "))
+}
+
+/// Render frontmatter from the user's own `kept_lines` followed by Diaroo's keys.
+fn render_frontmatter(kept_lines: &[&str], meta: &NoteMetadata) -> String {
+    let mut out = String::from("---\n");
+    for line in kept_lines {
+        out.push_str(line);
+        out.push('\n');
+    }
+    out.push_str(&format!("date: {}\n", meta.date));
+    out.push_str(&format!("apps: {}\n", yaml_list(&meta.apps)));
+    out.push_str(&format!("projects: {}\n", yaml_list(&meta.projects)));
+    out.push_str(&format!("hours_tracked: {:.1}\n", meta.hours_tracked));
+    out.push_str("---\n");
+    out
+}
+
+/// Split a note into its frontmatter lines (without `---` delimiters) and body.
+fn split_frontmatter(note: &str) -> (Vec<&str>, &str) {
+    let Some(rest) = note
+        .strip_prefix("---\n")
+        .or_else(|| note.strip_prefix("---\r\n"))
+    else {
+        return (Vec::new(), note);
+    };
+    let mut offset = 0;
+    for line in rest.split_inclusive('\n') {
+        if line.trim_end() == "---" {
+            let lines = rest[..offset].lines().collect();
+            return (lines, &rest[offset + line.len()..]);
+        }
+        offset += line.len();
+    }
+    (Vec::new(), note)
+}
+
+/// Drop Diaroo-owned keys (and their indented or list continuation lines).
+fn user_frontmatter_lines<'a>(lines: &[&'a str]) -> Vec<&'a str> {
+    let mut kept = Vec::new();
+    let mut skipping = false;
+    for line in lines {
+        let is_continuation =
+            line.starts_with(' ') || line.starts_with('\t') || line.starts_with("- ");
+        if skipping && is_continuation {
+            continue;
+        }
+        skipping = OWNED_KEYS.iter().any(|key| {
+            line.strip_prefix(key)
+                .is_some_and(|rest| rest.trim_start().starts_with(':'))
+        });
+        if !skipping {
+            kept.push(*line);
+        }
+    }
+    kept
+}
+
+/// Update frontmatter and replace (or add) the marked digest block under `heading`,
+/// leaving the rest of the user's note untouched.
+fn merge_into_note(existing: &str, report: &str, meta: &NoteMetadata, heading: &str) -> String {
+    let (fm_lines, body) = split_frontmatter(existing);
+    let kept = user_frontmatter_lines(&fm_lines);
+    let frontmatter = render_frontmatter(&kept, meta);
+    let block = marked_block(report);
+
+    let new_body = match (body.find(START_MARKER), body.find(END_MARKER)) {
+        (Some(start), Some(end)) if end > start => format!(
+            "{}{}{}",
+            &body[..start],
+            block,
+            &body[end + END_MARKER.len()..]
+        ),
+        _ => {
+            let heading = heading.trim();
+            let heading_line = body
+                .split_inclusive('\n')
+                .scan(0, |offset, line| {
+                    let start = *offset;
+                    *offset += line.len();
+                    Some((start, line))
+                })
+                .find(|(_, line)| line.trim_end() == heading);
+            match heading_line {
+                Some((start, line)) => {
+                    let insert_at = start + line.len();
+                    let sep = if line.ends_with('\n') { "" } else { "\n" };
+                    format!(
+                        "{}{}\n{}\n{}",
+                        &body[..insert_at],
+                        sep,
+                        block,
+                        &body[insert_at..]
+                    )
+                }
+                None => format!("{}\n\n{}\n\n{}\n", body.trim_end(), heading, block),
+            }
+        }
+    };
+
+    format!("{}{}", frontmatter, new_body)
+}
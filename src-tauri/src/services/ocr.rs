@@ -0,0 +1,105 @@
+use anyhow::{anyhow, Result};
+use std::time::Duration;
+use tokio::io::AsyncWriteExt;
+use tokio::process::Command;
+
+/// A word recognized by tesseract, in pixels of the image it was read from.
+#[derive(Debug, Clone)]
+pub struct OcrWord {
+    pub text: String,
+    /// (block, paragraph, line); words with the same key are on one line.
+    pub line: (u32, u32, u32),
+    pub left: u32,
+    pub top: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+/// Fail unless the `tesseract` CLI can be run.
+pub async fn check_installed() -> Result<()> {
+    let mut cmd = Command::new("tesseract");
+    super::shell_path::apply_shell_path(&mut cmd);
+    cmd.arg("--version")
+        .kill_on_drop(true)
+        .stdout(std::process::Stdio::null())
+        .stderr(std::process::Stdio::null());
+    #[cfg(target_os = "windows")]
+    cmd.creation_flags(0x08000000); // CREATE_NO_WINDOW
+    let status = tokio::time::timeout(Duration::from_secs(10), cmd.status())
+        .await
+        .map_err(|_| anyhow!("tesseract timed out after 10 seconds"))?
+        .map_err(|e| anyhow!("Failed to run tesseract: {}. Is it installed and in PATH?", e))?;
+    if !status.success() {
+        return Err(anyhow!("tesseract --version exited with {}", status));
+    }
+    Ok(())
+}
+
+/// Recognize the words of an encoded image with the local `tesseract` CLI.
+pub async fn recognize(image: &[u8]) -> Result<Vec<OcrWord>> {
+    let mut cmd = Command::new("tesseract");
+    super::shell_path::apply_shell_path(&mut cmd);
+    cmd.args(["stdin", "stdout", "tsv"])
+        .kill_on_drop(true)
+        .stdin(std::process::Stdio::piped())
+        .stdout(std::process::Stdio::piped())
+        .stderr(std::process::Stdio::piped());
+    #[cfg(target_os = "windows")]
+    cmd.creation_flags(0x08000000); // CREATE_NO_WINDOW
+    let mut child = cmd
+        .spawn()
+        .map_err(|e| anyhow!("Failed to spawn tesseract: {}. Is it installed and in PATH?", e))?;
+
+    let mut stdin = child.stdin.take().ok_or_else(|| anyhow!("Failed to open stdin for tesseract"))?;
+    let image_owned = image.to_vec();
+    tokio::spawn(async move {
+        let _ = stdin.write_all(&image_owned).await;
+        let _ = stdin.shutdown().await;
+    });
+
+    let output = tokio::time::timeout(Duration::from_secs(30), child.wait_with_output())
+        .await
+        .map_err(|_| anyhow!("tesseract timed out after 30 seconds"))?
+        .map_err(|e| anyhow!("tesseract process error: {}", e))?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(anyhow!("tesseract exited with {}: {}", output.status, stderr.trim()));
+    }
+    Ok(parse_tsv(&String::from_utf8_lossy(&output.stdout)))
+}
+
+/// Word rows (level 5) of tesseract's TSV output.
+fn parse_tsv(tsv: &str) -> Vec<OcrWord> {
+    tsv.lines()
+        .skip(1)
+        .filter_map(|row| {
+            let cols: Vec<&str> = row.splitn(12, '\t').collect();
+            if cols.len() < 12 || cols[0] != "5" || cols[11].trim().is_empty() {
+                return None;
+            }
+            let num = |i: usize| cols[i].parse::<u32>().ok();
+            Some(OcrWord {
+                text: cols[11].trim().to_string(),
+                line: (num(2)?, num(3)?, num(4)?),
+                left: num(6)?,
+                top: num(7)?,
+                width: num(8)?,
+                height: num(9)?,
+            })
+        })
+        .collect()
+}
+
+/// Recognized text with one line per OCR line.
+pub fn text(words: &[OcrWord]) -> String {
+    lines(words)
+        .iter()
+        .map(|line| line.iter().map(|w| w.text.as_str()).collect::<Vec<_>>().join(" "))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Consecutive words on the same line, in reading order.
+pub fn lines(words: &[OcrWord]) -> Vec<&[OcrWord]> {
+    words.chunk_by(|a, b| a.line == b.line).collect()
+}
@@ -0,0 +1,110 @@
+use anyhow::Result;
+use image::imageops::FilterType;
+use image::ImageFormat;
+use std::path::Path;
+
+use crate::services::activity_log::ActivityEntry;
+use crate::services::screenshot;
+
+/// Over budget, queued frames wider than this are re-encoded at half size.
+const DOWNSAMPLE_MIN_WIDTH: u32 = 960;
+const DOWNSAMPLE_QUALITY: u8 = 70;
+
+/// Screenshots waiting for an LLM batch.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct QueueStatus {
+    /// Unbatched activity entries, with or without a screenshot on disk.
+    pub depth: usize,
+    pub bytes: u64,
+}
+
+fn file_size(path: &Path) -> u64 {
+    std::fs::metadata(path).map(|m| m.len()).unwrap_or(0)
+}
+
+pub fn status(entries: &[ActivityEntry]) -> QueueStatus {
+    QueueStatus {
+        depth: entries.len(),
+        bytes: entries
+            .iter()
+            .map(|e| file_size(Path::new(&e.screenshot_path)))
+            .sum(),
+    }
+}
+
+/// Halve the resolution of a queued screenshot, keeping its format. Returns
+/// the bytes saved.
+fn downsample(path: &Path) -> Result<u64> {
+    let before = file_size(path);
+    let img = image::open(path)?;
+    if img.width() <= DOWNSAMPLE_MIN_WIDTH {
+        return Ok(0);
+    }
+    let small = img.resize(img.width() / 2, img.height() / 2, FilterType::Triangle);
+    let format = ImageFormat::from_path(path).unwrap_or(ImageFormat::Jpeg);
+    let buf = screenshot::encode(&small, format, DOWNSAMPLE_QUALITY)?;
+    std::fs::write(path, &buf)?;
+    Ok(before.saturating_sub(buf.len() as u64))
+}
+
+/// Keep the queued screenshots of `entries` (oldest first) under `budget` bytes.
+/// Large frames are downsampled first, oldest first; if that is not enough,
+/// every other remaining frame is deleted until the queue fits. Entries whose
+/// frame is dropped keep their app and window title for the batch summary.
+pub fn enforce_budget(entries: &[ActivityEntry], budget: u64) -> Result<QueueStatus> {
+    let mut current = status(entries);
+    if current.bytes <= budget {
+        return Ok(current);
+    }
+
+    let frames: Vec<&Path> = entries
+        .iter()
+        .map(|e| Path::new(e.screenshot_path.as_str()))
+        .filter(|p| p.exists())
+        .collect();
+
+    let mut downsampled = 0;
+    for path in &frames {
+        if current.bytes <= budget {
+            break;
+        }
+        match downsample(path) {
+            Ok(0) => {}
+            Ok(saved) => {
+                current.bytes = current.bytes.saturating_sub(saved);
+                downsampled += 1;
+            }
+            Err(e) => log::warn!("Failed to downsample queued screenshot {:?}: {}", path, e),
+        }
+    }
+    if downsampled > 0 {
+        log::info!("Offline queue over budget: downsampled {} screenshots", downsampled);
+    }
+
+    let mut remaining = frames;
+    let mut dropped = 0;
+    while current.bytes > budget && remaining.len() > 1 {
+        let mut kept = Vec::with_capacity(remaining.len() / 2 + 1);
+        for (i, path) in remaining.into_iter().enumerate() {
+            if i % 2 == 1 && current.bytes > budget {
+                // A frame that cannot be deleted is not tried again
+                let size = file_size(path);
+                match std::fs::remove_file(path) {
+                    Ok(()) => {
+                        current.bytes = current.bytes.saturating_sub(size);
+                        dropped += 1;
+                    }
+                    Err(e) => log::warn!("Failed to drop queued screenshot {:?}: {}", path, e),
+                }
+            } else {
+                kept.push(path);
+            }
+        }
+        remaining = kept;
+    }
+    if dropped > 0 {
+        log::info!("Offline queue over budget: dropped {} screenshots", dropped);
+    }
+
+    Ok(current)
+}
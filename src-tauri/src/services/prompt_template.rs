@@ -0,0 +1,709 @@
+use anyhow::{anyhow, Result};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+use crate::storage::config::AppConfig;
+
+/// Name of the template backed by the legacy single prompt file of each kind
+/// (`digest_prompt.txt` / `extract_prompt.txt`).
+pub const DEFAULT_TEMPLATE: &str = "default";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TemplateKind {
+    Extract,
+    Digest,
+}
+
+impl TemplateKind {
+    pub fn parse(kind: &str) -> Result<Self> {
+        match kind.trim() {
+            "extract" => Ok(Self::Extract),
+            "digest" => Ok(Self::Digest),
+            other => Err(anyhow!("Unknown template kind: {}", other)),
+        }
+    }
+
+    fn legacy_path(self) -> PathBuf {
+        match self {
+            Self::Extract => AppConfig::extract_prompt_path(),
+            Self::Digest => AppConfig::prompt_path(),
+        }
+    }
+
+    fn variables(self) -> &'static [VarSpec] {
+        match self {
+            Self::Extract => EXTRACT_VARS,
+            Self::Digest => DIGEST_VARS,
+        }
+    }
+
+    /// A template of this kind should use at least one of these variables,
+    /// otherwise the model never sees the day's data.
+    fn data_variables(self) -> &'static [&'static str] {
+        match self {
+            Self::Extract => &["activity_log", "entries"],
+            Self::Digest => &["batch_summaries", "batches"],
+        }
+    }
+}
+
+/// A variable a template kind provides. `fields` is non-empty for lists,
+/// which are iterated with `{#each name}...{/each}`.
+pub struct VarSpec {
+    pub name: &'static str,
+    pub fields: &'static [&'static str],
+}
+
+const fn text(name: &'static str) -> VarSpec {
+    VarSpec { name, fields: &[] }
+}
+
+const EXTRACT_VARS: &[VarSpec] = &[
+    text("activity_log"),
+    text("date"),
+    text("weekday"),
+    text("time"),
+    text("screenshot_count"),
+    text("has_images"),
+    VarSpec {
+        name: "entries",
+        fields: &[
+            "index",
+            "timestamp",
+            "app",
+            "title",
+            "url",
+            "repo",
+            "branch",
+            "focus",
+            "screen_text",
+        ],
+    },
+];
+
+const DIGEST_VARS: &[VarSpec] = &[
+    text("batch_summaries"),
+    text("app_usage"),
+    text("projects"),
+    text("websites"),
+    text("repos"),
+    text("commits"),
+    text("meetings"),
+    text("date"),
+    text("weekday"),
+    text("hours_tracked"),
+    text("top_apps"),
+    text("batch_count"),
+    VarSpec {
+        name: "batches",
+        fields: &["index", "timestamp", "summary"],
+    },
+    VarSpec {
+        name: "apps",
+        fields: &["index", "name", "minutes"],
+    },
+];
+
+#[derive(Debug, Clone)]
+pub enum Value {
+    Text(String),
+    List(Vec<Variables>),
+}
+
+/// Values available while rendering a template.
+#[derive(Debug, Clone, Default)]
+pub struct Variables {
+    values: HashMap<String, Value>,
+}
+
+impl Variables {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn set(&mut self, name: &str, value: impl Into<String>) -> &mut Self {
+        self.values.insert(name.to_string(), Value::Text(value.into()));
+        self
+    }
+
+    pub fn set_list(&mut self, name: &str, items: Vec<Variables>) -> &mut Self {
+        self.values.insert(name.to_string(), Value::List(items));
+        self
+    }
+
+    pub fn text(&self, name: &str) -> &str {
+        match self.values.get(name) {
+            Some(Value::Text(s)) => s,
+            _ => "",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct PromptTemplate {
+    pub name: String,
+    pub kind: TemplateKind,
+    pub description: String,
+    #[serde(skip)]
+    pub body: String,
+    pub path: String,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct Validation {
+    /// Problems that make the template unusable (syntax, unknown variables).
+    pub errors: Vec<String>,
+    /// Suspicious but renderable (e.g. the day's data is never referenced).
+    pub warnings: Vec<String>,
+}
+
+impl Validation {
+    pub fn is_ok(&self) -> bool {
+        self.errors.is_empty()
+    }
+}
+
+#[derive(Debug, Clone)]
+enum Node {
+    Text(String),
+    Var { name: String, line: usize },
+    If { name: String, line: usize, then: Vec<Node>, otherwise: Vec<Node> },
+    Each { name: String, line: usize, body: Vec<Node> },
+}
+
+/// Template syntax:
+/// - `{name}` inserts a variable
+/// - `{#if name}...{:else}...{/if}` renders a branch if the variable is non-empty
+/// - `{#each list}...{/each}` repeats for every item; item fields are plain variables
+/// - `{{` and `}}` are literal braces
+///
+/// Any other brace (JSON examples, `{ }`) is kept as literal text.
+fn parse(source: &str) -> Result<Vec<Node>> {
+    // An `{#if}` / `{#each}` whose closing tag has not been seen yet
+    struct Open {
+        directive: &'static str,
+        name: String,
+        line: usize,
+        then: Vec<Node>,
+        otherwise: Option<Vec<Node>>,
+    }
+
+    let mut root: Vec<Node> = Vec::new();
+    let mut stack: Vec<Open> = Vec::new();
+    let mut text = String::new();
+    let mut line = 1;
+    let mut rest = source;
+
+    fn current<'a>(root: &'a mut Vec<Node>, stack: &'a mut [Open]) -> &'a mut Vec<Node> {
+        match stack.last_mut() {
+            Some(open) => open.otherwise.as_mut().unwrap_or(&mut open.then),
+            None => root,
+        }
+    }
+
+    while let Some(c) = rest.chars().next() {
+        if rest.starts_with("{{") || rest.starts_with("}}") {
+            text.push(c);
+            rest = &rest[2..];
+            continue;
+        }
+        if c == '{' {
+            if let Some((tag, len)) = read_tag(rest) {
+                let nodes = current(&mut root, &mut stack);
+                if !text.is_empty() {
+                    nodes.push(Node::Text(std::mem::take(&mut text)));
+                }
+                match tag {
+                    Tag::Var(name) => nodes.push(Node::Var { name, line }),
+                    Tag::Open(directive, name) => stack.push(Open {
+                        directive,
+                        name,
+                        line,
+                        then: Vec::new(),
+                        otherwise: None,
+                    }),
+                    Tag::Else => match stack.last_mut() {
+                        Some(open) if open.directive == "if" && open.otherwise.is_none() => {
+                            open.otherwise = Some(Vec::new())
+                        }
+                        _ => return Err(anyhow!("Line {}: {{:else}} outside of {{#if}}", line)),
+                    },
+                    Tag::Close(directive) => {
+                        let open = match stack.pop() {
+                            Some(open) if open.directive == directive => open,
+                            Some(open) => {
+                                return Err(anyhow!(
+                                    "Line {}: {{/{}}} closes {{#{} {}}} opened on line {}",
+                                    line,
+                                    directive,
+                                    open.directive,
+                                    open.name,
+                                    open.line
+                                ))
+                            }
+                            None => {
+                                return Err(anyhow!(
+                                    "Line {}: {{/{}}} without matching {{#{}}}",
+                                    line,
+                                    directive,
+                                    directive
+                                ))
+                            }
+                        };
+                        let node = if open.directive == "if" {
+                            Node::If {
+                                name: open.name,
+                                line: open.line,
+                                then: open.then,
+                                otherwise: open.otherwise.unwrap_or_default(),
+                            }
+                        } else {
+                            Node::Each {
+                                name: open.name,
+                                line: open.line,
+                                body: open.then,
+                            }
+                        };
+                        current(&mut root, &mut stack).push(node);
+                    }
+                }
+                rest = &rest[len..];
+                continue;
+            }
+            if rest[1..].starts_with(['#', '/', ':']) {
+                let snippet: String = rest.chars().take_while(|c| *c != '\n').take(40).collect();
+                return Err(anyhow!("Line {}: malformed block tag {}", line, snippet));
+            }
+        }
+        if c == '\n' {
+            line += 1;
+        }
+        text.push(c);
+        rest = &rest[c.len_utf8()..];
+    }
+
+    if let Some(open) = stack.pop() {
+        return Err(anyhow!(
+            "Line {}: {{#{} {}}} is never closed",
+            open.line,
+            open.directive,
+            open.name
+        ));
+    }
+    if !text.is_empty() {
+        root.push(Node::Text(text));
+    }
+    Ok(root)
+}
+
+enum Tag {
+    Var(String),
+    Open(&'static str, String),
+    Else,
+    Close(&'static str),
+}
+
+fn is_identifier(s: &str) -> bool {
+    let mut chars = s.chars();
+    matches!(chars.next(), Some(c) if c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+/// Recognize a tag at the start of `s`; returns the tag and its length in bytes.
+fn read_tag(s: &str) -> Option<(Tag, usize)> {
+    let end = s.find('}')?;
+    let inner = &s[1..end];
+    if inner.contains(['\n', '{']) {
+        return None;
+    }
+    let tag = if let Some(args) = inner.strip_prefix("#if ") {
+        Tag::Open("if", identifier(args)?)
+    } else if let Some(args) = inner.strip_prefix("#each ") {
+        Tag::Open("each", identifier(args)?)
+    } else {
+        match inner {
+            ":else" => Tag::Else,
+            "/if" => Tag::Close("if"),
+            "/each" => Tag::Close("each"),
+            name if is_identifier(name) => Tag::Var(name.to_string()),
+            _ => return None,
+        }
+    };
+    Some((tag, end + 1))
+}
+
+fn identifier(s: &str) -> Option<String> {
+    let s = s.trim();
+    is_identifier(s).then(|| s.to_string())
+}
+
+/// Check a template against the variables its kind provides.
+pub fn validate(body: &str, kind: TemplateKind) -> Validation {
+    let mut validation = Validation::default();
+    let nodes = match parse(body) {
+        Ok(nodes) => nodes,
+        Err(e) => {
+            validation.errors.push(e.to_string());
+            return validation;
+        }
+    };
+
+    let specs = kind.variables();
+    let mut used = Vec::new();
+    check_nodes(&nodes, specs, &[], &mut used, &mut validation);
+
+    let data_vars = kind.data_variables();
+    if !used.iter().any(|name| data_vars.contains(&name.as_str())) {
+        validation.warnings.push(format!(
+            "Template never uses {}, so the model will not see the activity data",
+            data_vars
+                .iter()
+                .map(|v| format!("{{{}}}", v))
+                .collect::<Vec<_>>()
+                .join(" or ")
+        ));
+    }
+    validation
+}
+
+fn check_nodes(
+    nodes: &[Node],
+    specs: &[VarSpec],
+    item_fields: &[&str],
+    used: &mut Vec<String>,
+    validation: &mut Validation,
+) {
+    let lookup = |name: &str| specs.iter().find(|s| s.name == name);
+    for node in nodes {
+        match node {
+            Node::Text(_) => {}
+            Node::Var { name, line } => {
+                used.push(name.clone());
+                if item_fields.contains(&name.as_str()) {
+                    continue;
+                }
+                match lookup(name) {
+                    Some(spec) if !spec.fields.is_empty() => validation.errors.push(format!(
+                        "Line {}: {{{}}} is a list, use {{#each {}}}",
+                        line, name, name
+                    )),
+                    Some(_) => {}
+                    None => validation
+                        .errors
+                        .push(unknown_variable(*line, name, specs, item_fields)),
+                }
+            }
+            Node::If {
+                name,
+                line,
+                then,
+                otherwise,
+            } => {
+                used.push(name.clone());
+                if !item_fields.contains(&name.as_str()) && lookup(name).is_none() {
+                    validation
+                        .errors
+                        .push(unknown_variable(*line, name, specs, item_fields));
+                }
+                check_nodes(then, specs, item_fields, used, validation);
+                check_nodes(otherwise, specs, item_fields, used, validation);
+            }
+            Node::Each { name, line, body } => {
+                used.push(name.clone());
+                match lookup(name) {
+                    Some(spec) if !spec.fields.is_empty() => {
+                        check_nodes(body, specs, spec.fields, used, validation)
+                    }
+                    Some(_) => validation.errors.push(format!(
+                        "Line {}: {{#each {}}} needs a list, {} is text",
+                        line, name, name
+                    )),
+                    None => validation
+                        .errors
+                        .push(unknown_variable(*line, name, specs, item_fields)),
+                }
+            }
+        }
+    }
+}
+
+fn unknown_variable(line: usize, name: &str, specs: &[VarSpec], item_fields: &[&str]) -> String {
+    let known: Vec<&str> = specs
+        .iter()
+        .map(|s| s.name)
+        .chain(item_fields.iter().copied())
+        .collect();
+    let closest = known
+        .iter()
+        .map(|k| (edit_distance(k, name), *k))
+        .filter(|(d, _)| *d <= 3)
+        .min_by_key(|(d, _)| *d);
+    match closest {
+        Some((_, suggestion)) => format!(
+            "Line {}: unknown variable {{{}}}, did you mean {{{}}}?",
+            line, name, suggestion
+        ),
+        None => format!(
+            "Line {}: unknown variable {{{}}}; available: {}",
+            line,
+            name,
+            known.join(", ")
+        ),
+    }
+}
+
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut prev: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut cur = vec![i + 1];
+        for (j, cb) in b.iter().enumerate() {
+            let cost = if ca == *cb { 0 } else { 1 };
+            cur.push((prev[j] + cost).min(prev[j + 1] + 1).min(cur[j] + 1));
+        }
+        prev = cur;
+    }
+    prev[b.len()]
+}
+
+/// Render `body` with `vars`. Fails on syntax errors and unknown variables.
+pub fn render(body: &str, vars: &Variables) -> Result<String> {
+    let nodes = parse(body)?;
+    let mut out = String::with_capacity(body.len());
+    render_nodes(&nodes, &[vars], &mut out)?;
+    Ok(out)
+}
+
+fn lookup<'a>(scopes: &[&'a Variables], name: &str) -> Option<&'a Value> {
+    scopes.iter().rev().find_map(|s| s.values.get(name))
+}
+
+fn render_nodes(nodes: &[Node], scopes: &[&Variables], out: &mut String) -> Result<()> {
+    for node in nodes {
+        match node {
+            Node::Text(text) => out.push_str(text),
+            Node::Var { name, line } => match lookup(scopes, name) {
+                Some(Value::Text(value)) => out.push_str(value),
+                Some(Value::List(_)) => {
+                    return Err(anyhow!("Line {}: {{{}}} is a list, use {{#each {}}}", line, name, name))
+                }
+                None => return Err(anyhow!("Line {}: unknown variable {{{}}}", line, name)),
+            },
+            Node::If {
+                name,
+                line,
+                then,
+                otherwise,
+            } => {
+                let truthy = match lookup(scopes, name) {
+                    Some(Value::Text(value)) => !value.trim().is_empty() && value.trim() != "0",
+                    Some(Value::List(items)) => !items.is_empty(),
+                    None => return Err(anyhow!("Line {}: unknown variable {{{}}}", line, name)),
+                };
+                render_nodes(if truthy { then } else { otherwise }, scopes, out)?;
+            }
+            Node::Each { name, line, body } => {
+                let Some(Value::List(items)) = lookup(scopes, name) else {
+                    return Err(anyhow!("Line {}: {{#each {}}} needs a list", line, name));
+                };
+                for item in items {
+                    let mut inner = scopes.to_vec();
+                    inner.push(item);
+                    render_nodes(body, &inner, out)?;
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Split `---`-delimited front-matter (`key: value` lines) from the body.
+fn parse_front_matter(source: &str) -> (HashMap<String, String>, &str) {
+    let mut meta = HashMap::new();
+    let Some(rest) = source
+        .strip_prefix("---\n")
+        .or_else(|| source.strip_prefix("---\r\n"))
+    else {
+        return (meta, source);
+    };
+    let mut offset = 0;
+    for line in rest.split_inclusive('\n') {
+        if line.trim_end() == "---" {
+            return (meta, &rest[offset + line.len()..]);
+        }
+        if let Some((key, value)) = line.split_once(':') {
+            meta.insert(
+                key.trim().to_lowercase(),
+                value.trim().trim_matches('"').to_string(),
+            );
+        }
+        offset += line.len();
+    }
+    (HashMap::new(), source)
+}
+
+/// Load the legacy prompt file of `kind`, creating it with `default_body` first.
+fn load_legacy(kind: TemplateKind, default_body: &str) -> PromptTemplate {
+    let path = kind.legacy_path();
+    if !path.exists() {
+        if let Some(parent) = path.parent() {
+            let _ = std::fs::create_dir_all(parent);
+        }
+        let _ = std::fs::write(&path, default_body);
+    }
+    let body = match std::fs::read_to_string(&path) {
+        Ok(content) if !content.trim().is_empty() => content,
+        _ => default_body.to_string(),
+    };
+    PromptTemplate {
+        name: DEFAULT_TEMPLATE.to_string(),
+        kind,
+        description: "Default prompt".to_string(),
+        body,
+        path: path.to_string_lossy().to_string(),
+    }
+}
+
+/// Templates in the prompts directory. Files without a valid `kind` are skipped.
+fn load_directory() -> Vec<PromptTemplate> {
+    let dir = AppConfig::prompts_dir();
+    let Ok(entries) = std::fs::read_dir(&dir) else {
+        return Vec::new();
+    };
+
+    let mut templates = Vec::new();
+    for entry in entries.flatten() {
+        let path = entry.path();
+        let is_template = path
+            .extension()
+            .is_some_and(|ext| ext == "md" || ext == "txt");
+        let Some(name) = path.file_stem().map(|s| s.to_string_lossy().to_string()) else {
+            continue;
+        };
+        if !is_template || name == DEFAULT_TEMPLATE {
+            continue;
+        }
+        let Ok(source) = std::fs::read_to_string(&path) else {
+            continue;
+        };
+        let (meta, body) = parse_front_matter(&source);
+        let kind = match meta.get("kind").map(|k| TemplateKind::parse(k)) {
+            Some(Ok(kind)) => kind,
+            _ => {
+                log::warn!("Skipping prompt template {:?}: missing or invalid `kind`", path);
+                continue;
+            }
+        };
+        templates.push(PromptTemplate {
+            name,
+            kind,
+            description: meta.get("description").cloned().unwrap_or_default(),
+            body: body.to_string(),
+            path: path.to_string_lossy().to_string(),
+        });
+    }
+    templates.sort_by(|a, b| a.name.cmp(&b.name));
+    templates
+}
+
+/// All templates: the two defaults followed by the prompts directory.
+pub fn list_templates(default_extract: &str, default_digest: &str) -> Vec<PromptTemplate> {
+    let mut templates = vec![
+        load_legacy(TemplateKind::Extract, default_extract),
+        load_legacy(TemplateKind::Digest, default_digest),
+    ];
+    templates.extend(load_directory());
+    templates
+}
+
+/// Find template `name` of `kind`; an empty name means the default.
+pub fn load_template(kind: TemplateKind, name: &str, default_body: &str) -> Result<PromptTemplate> {
+    let name = name.trim();
+    if name.is_empty() || name == DEFAULT_TEMPLATE {
+        return Ok(load_legacy(kind, default_body));
+    }
+    let template = load_directory()
+        .into_iter()
+        .find(|t| t.name == name)
+        .ok_or_else(|| anyhow!("Prompt template {} not found", name))?;
+    if template.kind != kind {
+        return Err(anyhow!(
+            "Prompt template {} is a {:?} template, not {:?}",
+            name,
+            template.kind,
+            kind
+        ));
+    }
+    Ok(template)
+}
+
+pub struct RenderedPrompt {
+    pub text: String,
+    /// Template source the text was rendered from.
+    pub body: String,
+}
+
+/// Template `name`, or `builtin` for "default". Unlike `load_template`, the
+/// editable default prompt file is not used: it holds the main prompt, not a
+/// variant of it such as the text-only extract prompt.
+pub fn load_template_or_builtin(
+    kind: TemplateKind,
+    name: &str,
+    builtin: &str,
+) -> Result<PromptTemplate> {
+    let name = name.trim();
+    if name.is_empty() || name == DEFAULT_TEMPLATE {
+        return Ok(PromptTemplate {
+            name: DEFAULT_TEMPLATE.to_string(),
+            kind,
+            description: "Built-in prompt".to_string(),
+            body: builtin.to_string(),
+            path: String::new(),
+        });
+    }
+    load_template(kind, name, builtin)
+}
+
+/// Render template `name` (`default_body` for "default"). A missing or invalid
+/// template is an error: sending a different prompt than the configured one
+/// would go unnoticed.
+pub fn render_named(
+    kind: TemplateKind,
+    name: &str,
+    default_body: &str,
+    vars: &Variables,
+) -> Result<RenderedPrompt> {
+    render_checked(load_template(kind, name, default_body)?, vars)
+}
+
+/// `render_named` with the template from `load_template_or_builtin`.
+pub fn render_named_or_builtin(
+    kind: TemplateKind,
+    name: &str,
+    builtin: &str,
+    vars: &Variables,
+) -> Result<RenderedPrompt> {
+    render_checked(load_template_or_builtin(kind, name, builtin)?, vars)
+}
+
+fn render_checked(template: PromptTemplate, vars: &Variables) -> Result<RenderedPrompt> {
+    let validation = validate(&template.body, template.kind);
+    for warning in &validation.warnings {
+        log::warn!("Prompt template {}: {}", template.name, warning);
+    }
+    if !validation.is_ok() {
+        return Err(anyhow!(
+            "Prompt template {} is invalid: {}",
+            template.name,
+            validation.errors.join("; ")
+        ));
+    }
+    let text = render(&template.body, vars)
+        .map_err(|e| anyhow!("Prompt template {} failed to render: {}", template.name, e))?;
+    Ok(RenderedPrompt {
+        text,
+        body: template.body,
+    })
+}
@@ -0,0 +1,244 @@
+use regex::Regex;
+use std::collections::HashMap;
+use std::ops::Range;
+use std::sync::OnceLock;
+
+use crate::storage::config::RedactionSettings;
+
+const EMAIL_PATTERN: &str = r"[A-Za-z0-9._%+-]+@[A-Za-z0-9.-]+\.[A-Za-z]{2,}";
+/// International numbers ("+33 6 12 34 56 78"), "06 12 34 56 78" and grouped
+/// digits like "(555) 123-4567"; dates and times have too few groups to match.
+const PHONE_PATTERN: &str = r"\+\d{1,3}(?:[\s.-]?\d{1,4}){3,5}\b|\b0\d(?:[\s.-]\d{2}){4}\b|(?:\(\d{2,4}\)[\s.-]?|\b\d{2,4}[\s.-])\d{3,4}[\s.-]\d{3,4}\b";
+const CARD_PATTERN: &str = r"\b(?:\d[ -]?){12,18}\d\b";
+/// Well-known key prefixes, plus long tokens checked by `looks_like_secret`.
+const API_KEY_PATTERN: &str = r"\b(?:(?:sk|pk|rk)-[A-Za-z0-9_-]{16,}|gh[pousr]_[A-Za-z0-9]{20,}|xox[abprs]-[A-Za-z0-9-]{10,}|AKIA[0-9A-Z]{16}|AIza[0-9A-Za-z_-]{35}|[A-Za-z0-9_-]{32,})\b";
+
+/// Second check on a regex match before it is redacted.
+type Accept = fn(&str) -> bool;
+
+struct Detector {
+    label: String,
+    regex: Regex,
+    accept: Accept,
+}
+
+fn any_match(_: &str) -> bool {
+    true
+}
+
+/// Luhn checksum, so order numbers and timestamps are not taken for cards.
+fn is_card_number(text: &str) -> bool {
+    let digits: Vec<u32> = text.chars().filter_map(|c| c.to_digit(10)).collect();
+    let sum: u32 = digits
+        .iter()
+        .rev()
+        .enumerate()
+        .map(|(i, &d)| match (i % 2 == 1, d * 2) {
+            (true, doubled) if doubled > 9 => doubled - 9,
+            (true, doubled) => doubled,
+            (false, _) => d,
+        })
+        .sum();
+    sum % 10 == 0
+}
+
+/// Prefixed keys always match; generic long tokens need upper case, lower case
+/// and digits so commit hashes and plain words are kept.
+fn looks_like_secret(text: &str) -> bool {
+    const PREFIXES: &[&str] = &[
+        "sk-", "pk-", "rk-", "ghp_", "gho_", "ghu_", "ghs_", "ghr_", "xox", "AKIA", "AIza",
+    ];
+    if PREFIXES.iter().any(|prefix| text.starts_with(prefix)) {
+        return true;
+    }
+    text.chars().any(|c| c.is_ascii_uppercase())
+        && text.chars().any(|c| c.is_ascii_lowercase())
+        && text.chars().any(|c| c.is_ascii_digit())
+}
+
+/// Placeholders already in the text; detectors never match inside them.
+fn placeholder_pattern() -> &'static Regex {
+    static PATTERN: OnceLock<Regex> = OnceLock::new();
+    PATTERN.get_or_init(|| Regex::new(r"\[[A-Z0-9_]+_\d+\]").unwrap())
+}
+
+/// Replaces sensitive values with placeholders such as `[EMAIL_1]`. The same
+/// value always gets the same placeholder, so a mapping loaded from earlier
+/// batches of the day keeps the digest consistent and can be reversed locally.
+pub struct Redactor {
+    detectors: Vec<Detector>,
+    /// (placeholder, original value), in the order they were assigned.
+    mapping: Vec<(String, String)>,
+    placeholders: HashMap<String, String>,
+    counts: HashMap<String, usize>,
+    saved: usize,
+}
+
+impl Redactor {
+    /// A redactor that leaves text untouched when redaction is turned off.
+    /// `known` is the mapping already stored for the day.
+    pub fn new(settings: &RedactionSettings, known: Vec<(String, String)>) -> Self {
+        let mut detectors = Vec::new();
+        if settings.enabled {
+            let builtin: [(bool, &str, &str, Accept); 4] = [
+                (settings.api_keys, "SECRET", API_KEY_PATTERN, looks_like_secret),
+                (settings.emails, "EMAIL", EMAIL_PATTERN, any_match),
+                (settings.credit_cards, "CARD", CARD_PATTERN, is_card_number),
+                (settings.phone_numbers, "PHONE", PHONE_PATTERN, any_match),
+            ];
+            for (enabled, label, pattern, accept) in builtin {
+                if enabled {
+                    detectors.push(Detector {
+                        label: label.to_string(),
+                        regex: Regex::new(pattern).unwrap(),
+                        accept,
+                    });
+                }
+            }
+            for rule in &settings.rules {
+                match Regex::new(&rule.pattern) {
+                    Ok(regex) if !rule.pattern.is_empty() => detectors.push(Detector {
+                        label: placeholder_label(&rule.label),
+                        regex,
+                        accept: any_match,
+                    }),
+                    Ok(_) => {}
+                    Err(e) => log::warn!("Ignoring invalid redaction rule {:?}: {}", rule.pattern, e),
+                }
+            }
+            let words: Vec<String> = settings
+                .words
+                .iter()
+                .map(|w| w.trim())
+                .filter(|w| !w.is_empty())
+                .map(regex::escape)
+                .collect();
+            if !words.is_empty() {
+                let pattern = format!(r"(?i)\b(?:{})\b", words.join("|"));
+                match Regex::new(&pattern) {
+                    Ok(regex) => detectors.push(Detector {
+                        label: "REDACTED".to_string(),
+                        regex,
+                        accept: any_match,
+                    }),
+                    Err(e) => log::warn!("Ignoring redaction word list: {}", e),
+                }
+            }
+        }
+
+        let mut counts: HashMap<String, usize> = HashMap::new();
+        for (placeholder, _) in &known {
+            if let Some((label, n)) = placeholder
+                .trim_start_matches('[')
+                .trim_end_matches(']')
+                .rsplit_once('_')
+            {
+                let n = n.parse().unwrap_or(0);
+                let count = counts.entry(label.to_string()).or_default();
+                *count = (*count).max(n);
+            }
+        }
+        Self {
+            detectors,
+            placeholders: known.iter().map(|(p, v)| (v.clone(), p.clone())).collect(),
+            saved: known.len(),
+            mapping: known,
+            counts,
+        }
+    }
+
+    pub fn redact(&mut self, text: &str) -> String {
+        let mut text = text.to_string();
+        for i in 0..self.detectors.len() {
+            let protected: Vec<Range<usize>> =
+                placeholder_pattern().find_iter(&text).map(|m| m.range()).collect();
+            let detector = &self.detectors[i];
+            let found: Vec<(Range<usize>, String)> = detector
+                .regex
+                .find_iter(&text)
+                .filter(|m| m.start() < m.end() && (detector.accept)(m.as_str()))
+                .filter(|m| !protected.iter().any(|p| p.start < m.end() && m.start() < p.end))
+                .map(|m| (m.range(), m.as_str().to_string()))
+                .collect();
+            if found.is_empty() {
+                continue;
+            }
+            let label = detector.label.clone();
+            let mut redacted = String::with_capacity(text.len());
+            let mut last = 0;
+            for (range, value) in found {
+                redacted.push_str(&text[last..range.start]);
+                redacted.push_str(&self.placeholder(&label, &value));
+                last = range.end;
+            }
+            redacted.push_str(&text[last..]);
+            text = redacted;
+        }
+        text
+    }
+
+    /// Whether any detector is configured.
+    pub fn is_active(&self) -> bool {
+        !self.detectors.is_empty()
+    }
+
+    /// Byte ranges of `text` that `redact` would replace, without assigning
+    /// placeholders.
+    pub fn sensitive_ranges(&self, text: &str) -> Vec<Range<usize>> {
+        self.detectors
+            .iter()
+            .flat_map(|detector| {
+                detector
+                    .regex
+                    .find_iter(text)
+                    .filter(|m| m.start() < m.end() && (detector.accept)(m.as_str()))
+                    .map(|m| m.range())
+            })
+            .collect()
+    }
+
+    fn placeholder(&mut self, label: &str, value: &str) -> String {
+        if let Some(placeholder) = self.placeholders.get(value) {
+            return placeholder.clone();
+        }
+        let count = self.counts.entry(label.to_string()).or_default();
+        *count += 1;
+        let placeholder = format!("[{}_{}]", label, count);
+        self.placeholders.insert(value.to_string(), placeholder.clone());
+        self.mapping.push((placeholder.clone(), value.to_string()));
+        placeholder
+    }
+
+    /// Every placeholder known to this redactor, for `restore`.
+    pub fn mapping(&self) -> &[(String, String)] {
+        &self.mapping
+    }
+
+    /// Placeholders assigned since the redactor was built or last drained.
+    pub fn take_new(&mut self) -> Vec<(String, String)> {
+        let new = self.mapping[self.saved..].to_vec();
+        self.saved = self.mapping.len();
+        new
+    }
+}
+
+/// Upper-case placeholder label from a rule name ("ticket id" -> "TICKET_ID").
+fn placeholder_label(name: &str) -> String {
+    let label: String = name
+        .trim()
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c.to_ascii_uppercase() } else { '_' })
+        .collect();
+    if label.is_empty() {
+        "REDACTED".to_string()
+    } else {
+        label
+    }
+}
+
+/// Put the original values back in place of their placeholders.
+pub fn restore(text: &str, mapping: &[(String, String)]) -> String {
+    mapping
+        .iter()
+        .fold(text.to_string(), |text, (placeholder, value)| text.replace(placeholder, value))
+}
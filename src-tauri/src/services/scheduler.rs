@@ -7,12 +7,21 @@ use tokio::time::{interval, sleep, Duration};
 
 use tauri::Manager;
 
-use crate::services::activity_log::ActivityLog;
+use crate::services::activity_log::{ActivityLog, CaptureContext};
+use crate::services::browser_tab::{self, TabTracker};
+use crate::services::dev_context::DevContextEnricher;
 use crate::services::digest_generator::DigestGenerator;
+use crate::services::image_redaction::ImageRedactor;
+use crate::services::jobs::{Cancelled, JobKind};
+use crate::services::keyframes::KeyframeSelector;
 use crate::services::llm_client::LlmClient;
-use crate::services::screenshot::{DHash, ScreenshotCapture};
+use crate::services::meetings::{MeetingDetector, MeetingTracker};
+use crate::services::ocr;
+use crate::services::offline_queue;
+use crate::services::screenshot::{self, CaptureSettings, ScreenshotCapture};
 use crate::services::window_info;
-use crate::storage::config::AppConfig;
+use crate::storage::config::{AppConfig, LlmStep};
+use crate::storage::projects::{MatchContext, ProjectMatcher};
 use crate::storage::screenshot_store::ScreenshotStore;
 use crate::AppState;
 
@@ -23,9 +32,39 @@ struct TickResult {
     was_skipped: bool,
 }
 
+/// State the screenshot capture task keeps between ticks.
+struct CaptureTask {
+    store: ScreenshotStore,
+    activity_log: Arc<ActivityLog>,
+    settings: CaptureSettings,
+    project_matcher: ProjectMatcher,
+    image_redactor: ImageRedactor,
+    ocr_enabled: bool,
+    keyframes: KeyframeSelector,
+    /// Active browser tabs, when the browser integration is enabled.
+    tabs: Option<Arc<TabTracker>>,
+    excluded_domains: Vec<String>,
+    /// Git context of terminals and IDEs, when enabled.
+    dev_context: Option<DevContextEnricher>,
+    /// Meeting signals and the interval they extend, when detection is enabled.
+    meetings: Option<(MeetingDetector, MeetingTracker)>,
+}
+
 pub struct Scheduler;
 
 impl Scheduler {
+    /// Fail if the configured capture relies on a tool this system lacks
+    /// (tesseract for OCR, window listing for blurred apps).
+    pub async fn check_capture(config: &AppConfig) -> Result<()> {
+        ImageRedactor::new(config).check().await?;
+        if config.ocr_mode != "off" {
+            ocr::check_installed()
+                .await
+                .map_err(|e| anyhow::anyhow!("OCR needs tesseract: {}", e))?;
+        }
+        Ok(())
+    }
+
     pub fn start(
         config: AppConfig,
         activity_log: Arc<ActivityLog>,
@@ -34,25 +73,61 @@ impl Scheduler {
     ) {
         let screenshot_interval = config.screenshot_interval_secs;
         let batch_interval = config.batch_interval_secs;
-        let dedup_threshold = config.dedup_threshold;
         let data_dir = config.data_path();
 
+        // Browser integration: the extension reports the active tab
+        let tabs = config.browser_integration_enabled.then(|| Arc::new(TabTracker::new()));
+        if let Some(tabs) = &tabs {
+            let tabs = tabs.clone();
+            let port = config.browser_integration_port;
+            let stop_rx_clone = _stop_rx.clone();
+            tauri::async_runtime::spawn(async move {
+                if let Err(e) = browser_tab::serve(port, tabs, stop_rx_clone).await {
+                    log::error!("Browser integration error: {}", e);
+                }
+            });
+        }
+
         // Screenshot capture task
-        let log_clone = activity_log.clone();
-        let data_dir_clone = data_dir.clone();
+        let mut capture = CaptureTask {
+            store: ScreenshotStore::new(data_dir.clone()),
+            activity_log: activity_log.clone(),
+            settings: CaptureSettings::from_config(&config),
+            project_matcher: ProjectMatcher::new(&config.projects),
+            image_redactor: ImageRedactor::new(&config),
+            ocr_enabled: config.ocr_mode != "off",
+            keyframes: KeyframeSelector::new(&config),
+            tabs,
+            excluded_domains: config.excluded_domains.clone(),
+            dev_context: DevContextEnricher::new(&config),
+            meetings: MeetingDetector::new(&config).map(|detector| (detector, MeetingTracker::new())),
+        };
         let mut stop_rx_clone = _stop_rx.clone();
         let capture_app_handle = app_handle.clone();
+        let capture_config = config.clone();
 
         tauri::async_runtime::spawn(async move {
-            let store = ScreenshotStore::new(data_dir_clone);
             let mut ticker = interval(Duration::from_secs(screenshot_interval));
-            let mut last_hash: Option<DHash> = None;
+            if let Err(e) = Self::check_capture(&capture_config).await {
+                log::error!("Screenshot capture unavailable: {}", e);
+                let _ = capture_app_handle.emit("monitoring-status", serde_json::json!({
+                    "active": true,
+                    "error": e.to_string(),
+                }));
+            }
+            // Capture errors are reported once until a tick succeeds again
+            let mut last_error: Option<String> = None;
 
             loop {
                 tokio::select! {
                     _ = ticker.tick() => {
-                        match Self::capture_tick(&store, &log_clone, &mut last_hash, dedup_threshold) {
+                        // Project rules and masks edited in Settings apply from the next capture on
+                        let config = capture_app_handle.state::<AppState>().config.lock().unwrap().clone();
+                        capture.project_matcher = ProjectMatcher::new(&config.projects);
+                        capture.image_redactor = ImageRedactor::new(&config);
+                        match capture.tick().await {
                             Ok(tick) => {
+                                last_error = None;
                                 let _ = capture_app_handle.emit("activity-tick", serde_json::json!({
                                     "app_name": tick.app_name,
                                     "window_title": tick.window_title,
@@ -62,6 +137,14 @@ impl Scheduler {
                             }
                             Err(e) => {
                                 log::error!("Screenshot capture error: {}", e);
+                                let error = format!("Screenshot capture failed: {}", e);
+                                if last_error.as_ref() != Some(&error) {
+                                    let _ = capture_app_handle.emit("monitoring-status", serde_json::json!({
+                                        "active": true,
+                                        "error": error,
+                                    }));
+                                    last_error = Some(error);
+                                }
                             }
                         }
                     }
@@ -91,26 +174,76 @@ impl Scheduler {
                 tokio::select! {
                     _ = ticker.tick() => {
                         log::info!("Batch tick fired, checking for unbatched entries...");
-                        let config = app_handle.state::<AppState>().config.lock().unwrap().clone();
-                        let llm = LlmClient::new(
-                            &config.llm_provider,
-                            &config.api_key,
-                            &config.model,
-                            &config.api_endpoint,
-                            Some(config.data_path()),
-                        );
-                        match DigestGenerator::process_batch(&log_clone, &store, &llm).await {
-                            Ok(Some(summary)) => {
-                                log::info!("Batch processed: {}", &summary[..summary.len().min(100)]);
-                                let _ = app_handle.emit("monitoring-status", serde_json::json!({
-                                    "active": true,
-                                    "last_batch_summary": summary,
-                                }));
+                        let state = app_handle.state::<AppState>();
+                        let config = state.config.lock().unwrap().clone();
+                        let today = Local::now().format("%Y-%m-%d").to_string();
+                        let llm = LlmClient::for_step(&config, LlmStep::Extraction);
+
+                        // Offline: keep screenshots queued on disk instead of failing every chunk.
+                        // The endpoint is only probed after a failed request, until one succeeds.
+                        if !llm.api_key_is_empty()
+                            && llm.has_failing_provider()
+                            && !llm.is_reachable().await
+                        {
+                            let budget = config.offline_queue_budget_mb * 1024 * 1024;
+                            match log_clone
+                                .get_unbatched_entries()
+                                .and_then(|entries| offline_queue::enforce_budget(&entries, budget))
+                            {
+                                Ok(queue) => {
+                                    log::info!(
+                                        "LLM endpoint unreachable, {} entries queued ({} KB)",
+                                        queue.depth,
+                                        queue.bytes / 1024
+                                    );
+                                    let _ = app_handle.emit("monitoring-status", serde_json::json!({
+                                        "active": true,
+                                        "offline": true,
+                                        "queue_depth": queue.depth,
+                                        "queue_bytes": queue.bytes,
+                                    }));
+                                }
+                                Err(e) => log::error!("Failed to check offline queue: {}", e),
                             }
-                            Ok(None) => {
-                                log::info!("Batch tick: no unbatched entries to process");
+                        } else {
+                            let job = state.jobs.start(JobKind::Batch, &today);
+                            crate::refresh_tray_menu(&app_handle);
+                            let llm = llm.with_cancel(job.token().clone());
+                            let result =
+                                DigestGenerator::process_batch(&log_clone, &store, &llm, &config, job.token())
+                                    .await;
+                            drop(job);
+                            crate::refresh_tray_menu(&app_handle);
+                            let queue = log_clone
+                                .get_unbatched_entries()
+                                .map(|entries| offline_queue::status(&entries))
+                                .unwrap_or_default();
+                            match result {
+                                Ok(Some(summary)) => {
+                                    log::info!("Batch processed: {}", &summary[..summary.len().min(100)]);
+                                    let _ = app_handle.emit("monitoring-status", serde_json::json!({
+                                        "active": true,
+                                        "offline": false,
+                                        "last_batch_summary": summary,
+                                        "queue_depth": queue.depth,
+                                        "queue_bytes": queue.bytes,
+                                    }));
+                                }
+                                Ok(None) => {
+                                    log::info!("Batch tick: no unbatched entries to process");
+                                }
+                                Err(e) if e.is::<Cancelled>() => log::info!("Batch processing cancelled"),
+                                Err(e) => {
+                                    log::error!("Batch processing error: {}", e);
+                                    let _ = app_handle.emit("monitoring-status", serde_json::json!({
+                                        "active": true,
+                                        "offline": false,
+                                        "error": e.to_string(),
+                                        "queue_depth": queue.depth,
+                                        "queue_bytes": queue.bytes,
+                                    }));
+                                }
                             }
-                            Err(e) => log::error!("Batch processing error: {}", e),
                         }
                     }
                     _ = stop_rx_clone.changed() => {
@@ -159,15 +292,10 @@ impl Scheduler {
             }
         });
     }
+}
 
-    fn capture_tick(
-        store: &ScreenshotStore,
-        activity_log: &Arc<ActivityLog>,
-        last_hash: &mut Option<DHash>,
-        dedup_threshold: u32,
-    ) -> Result<TickResult> {
-        let (jpeg_data, hash) = ScreenshotCapture::capture()?;
-
+impl CaptureTask {
+    async fn tick(&mut self) -> Result<TickResult> {
         // Always get window info for the activity tick
         let window_info = window_info::get_active_window().unwrap_or_else(|_| {
             window_info::ActiveWindowInfo {
@@ -175,33 +303,118 @@ impl Scheduler {
                 app_name: String::new(),
             }
         });
+        let tab = self
+            .tabs
+            .as_ref()
+            .and_then(|tabs| tabs.current(&window_info.title));
+
+        // Meetings are tracked on every tick, including skipped captures
+        if let Some((detector, tracker)) = &mut self.meetings {
+            let platform = detector.match_window(
+                &window_info.app_name,
+                &window_info.title,
+                tab.as_ref().map(|tab| tab.url.as_str()),
+            );
+            let devices = detector.devices_in_use().await;
+            if let Err(e) = tracker.update(&self.activity_log, platform, devices) {
+                log::warn!("Failed to record meeting: {}", e);
+            }
+        }
 
-        // Dedup: skip saving if too similar to last screenshot
-        let (hash_distance, was_skipped) = if let Some(ref prev_hash) = last_hash {
-            let distance = prev_hash.distance(&hash);
-            if distance < dedup_threshold {
-                log::debug!("Screenshot skipped (hash distance: {})", distance);
-                (distance, true)
-            } else {
-                (distance, false)
+        // Excluded sites are never captured. A focused browser without a
+        // matching tab report could be on one, so it is not captured either.
+        let excluded = match &tab {
+            Some(tab) => browser_tab::is_excluded(tab, &self.excluded_domains),
+            None => {
+                self.tabs.is_some()
+                    && self.excluded_domains.iter().any(|d| !d.trim().is_empty())
+                    && browser_tab::is_browser(&window_info.app_name)
             }
-        } else {
-            (0, false)
         };
+        if excluded {
+            log::debug!("Screenshot skipped (excluded or unknown domain)");
+            return Ok(TickResult {
+                app_name: window_info.app_name,
+                window_title: window_info.title,
+                hash_distance: 0,
+                was_skipped: true,
+            });
+        }
+
+        let capture = ScreenshotCapture::capture(&self.settings)?;
+        let hash = capture.hash;
+
+        // Dedup: skip saving unless this capture is a new keyframe
+        let decision = self
+            .keyframes
+            .select(&hash, &window_info.app_name, &window_info.title);
+        let (hash_distance, was_skipped) = (decision.distance, !decision.keep);
+        if was_skipped {
+            log::debug!("Screenshot skipped (hash distance: {})", hash_distance);
+        }
 
         if !was_skipped {
-            *last_hash = Some(hash.clone());
+            // Only the redacted frame is written to disk
+            let is_window = capture.is_window();
+            let mut image = capture.image;
+            // OCR reads the window only; the thumbnail is too small for text
+            let redaction = self
+                .image_redactor
+                .apply(&mut image, &capture.screen, &capture.area, self.ocr_enabled)
+                .await?;
+            let mut masked = redaction.masked;
+            let ocr_text = redaction
+                .words
+                .map(|words| ocr::text(&words))
+                .unwrap_or_default();
+            let thumbnail = match capture.thumbnail {
+                Some(mut thumbnail) => {
+                    masked += self
+                        .image_redactor
+                        .apply(&mut thumbnail, &capture.screen, &capture.screen, false)
+                        .await?
+                        .masked;
+                    Some(thumbnail)
+                }
+                None => None,
+            };
+            if masked > 0 {
+                log::debug!("Screenshot redacted: {} areas masked", masked);
+            }
+            if let Some(thumbnail) = &thumbnail {
+                image = screenshot::with_thumbnail(&image, thumbnail);
+            }
+            let data = screenshot::encode(&image, self.settings.format, self.settings.quality)?;
+
+            // Context enrichers
+            let dev_context = match &self.dev_context {
+                Some(enricher) => enricher.enrich().await,
+                None => None,
+            };
 
             // Save screenshot
-            let path = store.save_screenshot(&jpeg_data)?;
+            let path = self.store.save_screenshot(&data, self.settings.extension())?;
             let timestamp = Local::now().to_rfc3339();
+            let project_id = self.project_matcher.match_project(&MatchContext {
+                app_name: &window_info.app_name,
+                window_title: &window_info.title,
+                repo_path: dev_context.as_ref().map(|dev| dev.repo_path.as_str()),
+                url: tab.as_ref().map(|tab| tab.url.as_str()),
+            });
 
-            activity_log.insert_activity(
+            self.activity_log.insert_activity(
                 &timestamp,
                 &path.to_string_lossy(),
                 &window_info.title,
                 &window_info.app_name,
                 &hash.to_hex(),
+                &CaptureContext {
+                    project_id: project_id.as_deref(),
+                    ocr_text: &ocr_text,
+                    window_bounds: is_window.then_some(&capture.area),
+                    tab: tab.as_ref(),
+                    dev_context: dev_context.as_ref(),
+                },
             )?;
 
             log::debug!(
@@ -1,13 +1,19 @@
-use anyhow::Result;
+use anyhow::{anyhow, Result};
 use image::codecs::jpeg::JpegEncoder;
-use image::imageops::FilterType;
-use image::DynamicImage;
+use image::codecs::png::PngEncoder;
+use image::codecs::webp::WebPEncoder;
+use image::imageops::{self, FilterType};
+use image::{DynamicImage, ImageFormat, Rgba, RgbaImage};
 use std::io::Cursor;
-use xcap::Monitor;
+use xcap::{Monitor, Window};
+
+use crate::storage::config::AppConfig;
 
-const TARGET_WIDTH: u32 = 1280;
-const TARGET_HEIGHT: u32 = 720;
 const HASH_SIZE: u32 = 8;
+/// Full-screen context thumbnails fit in this many pixels.
+const THUMBNAIL_SIZE: u32 = 480;
+/// Height of the dark strip between a window capture and its thumbnail.
+const THUMBNAIL_GAP: u32 = 8;
 
 /// A simple perceptual hash (dHash) computed manually to avoid image crate version conflicts.
 #[derive(Debug, Clone)]
@@ -47,31 +53,237 @@ impl DHash {
     }
 }
 
+/// Position and size of a screen region, in the same units as window
+/// geometry reported by the OS.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ScreenArea {
+    pub x: i32,
+    pub y: i32,
+    pub width: u32,
+    pub height: u32,
+}
+
+/// Which part of the primary monitor is captured.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CaptureMode {
+    /// The whole monitor.
+    Screen,
+    /// The monitor capture cropped to the focused window's bounds.
+    Adaptive,
+    /// The focused window's own pixels, without the windows overlapping it.
+    Window,
+}
+
+/// How captures are sized and stored, from the app config.
+#[derive(Debug, Clone)]
+pub struct CaptureSettings {
+    pub max_width: u32,
+    pub max_height: u32,
+    pub filter: FilterType,
+    pub format: ImageFormat,
+    /// JPEG quality; PNG and WebP are lossless.
+    pub quality: u8,
+    pub mode: CaptureMode,
+    /// Add a small full-screen thumbnail below window captures.
+    pub thumbnail: bool,
+}
+
+impl CaptureSettings {
+    pub fn from_config(config: &AppConfig) -> Self {
+        let filter = match config.capture_filter.as_str() {
+            "nearest" => FilterType::Nearest,
+            "triangle" => FilterType::Triangle,
+            "catmullrom" => FilterType::CatmullRom,
+            "gaussian" => FilterType::Gaussian,
+            _ => FilterType::Lanczos3,
+        };
+        let format = match config.image_format.as_str() {
+            "png" => ImageFormat::Png,
+            "webp" => ImageFormat::WebP,
+            _ => ImageFormat::Jpeg,
+        };
+        Self {
+            max_width: config.capture_width.max(1),
+            max_height: config.capture_height.max(1),
+            filter,
+            format,
+            quality: config.image_quality.clamp(1, 100),
+            mode: match config.capture_mode.as_str() {
+                "adaptive" => CaptureMode::Adaptive,
+                "window" => CaptureMode::Window,
+                _ => CaptureMode::Screen,
+            },
+            thumbnail: config.capture_thumbnail,
+        }
+    }
+
+    /// File extension of stored captures.
+    pub fn extension(&self) -> &'static str {
+        self.format.extensions_str()[0]
+    }
+}
+
+/// A resized capture of the primary monitor or of its focused window.
+pub struct Capture {
+    pub image: DynamicImage,
+    pub hash: DHash,
+    /// The monitor the capture was taken on.
+    pub screen: ScreenArea,
+    /// The part of the monitor in `image`: the whole screen, or the focused
+    /// window in adaptive and window modes.
+    pub area: ScreenArea,
+    /// Downscaled capture of the whole monitor, for window captures when
+    /// thumbnails are enabled.
+    pub thumbnail: Option<DynamicImage>,
+}
+
+impl Capture {
+    /// Whether `image` only shows the focused window.
+    pub fn is_window(&self) -> bool {
+        self.area != self.screen
+    }
+}
+
 pub struct ScreenshotCapture;
 
 impl ScreenshotCapture {
-    /// Capture the primary monitor and return resized JPEG bytes + perceptual hash.
-    pub fn capture() -> Result<(Vec<u8>, DHash)> {
+    /// Capture the primary monitor, scaled down to fit the configured size, with
+    /// its perceptual hash. In adaptive and window modes only the focused window
+    /// is kept, so it fills the size the whole screen would and its text keeps
+    /// more detail; the hash then ignores changes in background windows.
+    pub fn capture(settings: &CaptureSettings) -> Result<Capture> {
         let monitors = Monitor::all()?;
         let monitor = monitors
             .into_iter()
             .find(|m| m.is_primary().unwrap_or(false))
             .or_else(|| Monitor::all().ok()?.into_iter().next())
-            .ok_or_else(|| anyhow::anyhow!("No monitor found"))?;
+            .ok_or_else(|| anyhow!("No monitor found"))?;
 
-        let raw_image = monitor.capture_image()?;
+        let full = DynamicImage::ImageRgba8(monitor.capture_image()?);
+        let screen = ScreenArea {
+            x: monitor.x()?,
+            y: monitor.y()?,
+            width: monitor.width()?,
+            height: monitor.height()?,
+        };
 
-        let dynamic = DynamicImage::ImageRgba8(raw_image);
-        let resized = dynamic.resize(TARGET_WIDTH, TARGET_HEIGHT, FilterType::Lanczos3);
+        let window = match settings.mode {
+            CaptureMode::Screen => None,
+            _ => {
+                let window = focused_window(&screen);
+                if window.is_none() {
+                    log::debug!("No focused window found, capturing the whole screen");
+                }
+                window
+            }
+        };
+        let thumbnail = (settings.thumbnail && window.is_some())
+            .then(|| full.resize(THUMBNAIL_SIZE, THUMBNAIL_SIZE, settings.filter));
+
+        let (dynamic, area) = match window {
+            Some((window, bounds)) => {
+                let own_pixels = match settings.mode {
+                    CaptureMode::Window => match window.capture_image() {
+                        Ok(image) => Some((DynamicImage::ImageRgba8(image), bounds)),
+                        Err(e) => {
+                            log::debug!("Window capture failed, cropping the screen: {}", e);
+                            None
+                        }
+                    },
+                    _ => None,
+                };
+                match own_pixels {
+                    Some(captured) => captured,
+                    None => crop(&full, &screen, &bounds).unwrap_or((full, screen)),
+                }
+            }
+            None => (full, screen),
+        };
+
+        // Only scale down; small windows keep their native resolution
+        let resized = if dynamic.width() > settings.max_width || dynamic.height() > settings.max_height {
+            dynamic.resize(settings.max_width, settings.max_height, settings.filter)
+        } else {
+            dynamic
+        };
 
         // Compute perceptual hash for dedup
         let hash = DHash::compute(&resized);
 
-        // Encode as JPEG
-        let mut jpeg_buf = Cursor::new(Vec::new());
-        let encoder = JpegEncoder::new_with_quality(&mut jpeg_buf, 85);
-        resized.to_rgb8().write_with_encoder(encoder)?;
+        Ok(Capture {
+            image: resized,
+            hash,
+            screen,
+            area,
+            thumbnail: thumbnail.filter(|_| area != screen),
+        })
+    }
+}
+
+/// The focused window and its bounds.
+fn focused_window(screen: &ScreenArea) -> Option<(Window, ScreenArea)> {
+    let window = Window::all()
+        .ok()?
+        .into_iter()
+        .find(|w| w.is_focused().unwrap_or(false) && !w.is_minimized().unwrap_or(false))?;
+    let bounds = ScreenArea {
+        x: window.x().ok()?,
+        y: window.y().ok()?,
+        width: window.width().ok()?,
+        height: window.height().ok()?,
+    };
+    // A focused window on another monitor is not part of this capture
+    clip(&bounds, screen)?;
+    Some((window, bounds))
+}
+
+/// The part of `area` that lies on `screen`.
+fn clip(area: &ScreenArea, screen: &ScreenArea) -> Option<ScreenArea> {
+    let left = area.x.max(screen.x);
+    let top = area.y.max(screen.y);
+    let right = (area.x + area.width as i32).min(screen.x + screen.width as i32);
+    let bottom = (area.y + area.height as i32).min(screen.y + screen.height as i32);
+    (right > left && bottom > top).then_some(ScreenArea {
+        x: left,
+        y: top,
+        width: (right - left) as u32,
+        height: (bottom - top) as u32,
+    })
+}
+
+/// Crop `full`, a capture of `screen`, to the visible part of `bounds`.
+fn crop(full: &DynamicImage, screen: &ScreenArea, bounds: &ScreenArea) -> Option<(DynamicImage, ScreenArea)> {
+    let area = clip(bounds, screen)?;
+    // Captured pixels per screen unit (2 on Retina displays)
+    let scale = full.width() as f64 / screen.width.max(1) as f64;
+    let x = ((area.x - screen.x) as f64 * scale).round() as u32;
+    let y = ((area.y - screen.y) as f64 * scale).round() as u32;
+    let width = ((area.width as f64 * scale).round() as u32).min(full.width().saturating_sub(x));
+    let height = ((area.height as f64 * scale).round() as u32).min(full.height().saturating_sub(y));
+    (width > 0 && height > 0).then(|| (full.crop_imm(x, y, width, height), area))
+}
+
+/// A window capture with the full-screen thumbnail below it.
+pub fn with_thumbnail(image: &DynamicImage, thumbnail: &DynamicImage) -> DynamicImage {
+    let width = image.width().max(thumbnail.width());
+    let height = image.height() + THUMBNAIL_GAP + thumbnail.height();
+    let mut combined = DynamicImage::ImageRgba8(RgbaImage::from_pixel(width, height, Rgba([0, 0, 0, 255])));
+    imageops::replace(&mut combined, image, 0, 0);
+    imageops::replace(&mut combined, thumbnail, 0, (image.height() + THUMBNAIL_GAP) as i64);
+    combined
+}
 
-        Ok((jpeg_buf.into_inner(), hash))
+/// Encode an image as `format`; `quality` only applies to JPEG.
+pub fn encode(image: &DynamicImage, format: ImageFormat, quality: u8) -> Result<Vec<u8>> {
+    let mut buf = Cursor::new(Vec::new());
+    match format {
+        ImageFormat::Png => image.to_rgb8().write_with_encoder(PngEncoder::new(&mut buf))?,
+        ImageFormat::WebP => image
+            .to_rgba8()
+            .write_with_encoder(WebPEncoder::new_lossless(&mut buf))?,
+        _ => image
+            .to_rgb8()
+            .write_with_encoder(JpegEncoder::new_with_quality(&mut buf, quality))?,
     }
+    Ok(buf.into_inner())
 }
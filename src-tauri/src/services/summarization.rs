@@ -0,0 +1,191 @@
+use anyhow::Result;
+use chrono::{DateTime, Timelike};
+
+use crate::services::activity_log::BatchSummary;
+use crate::services::jobs::CancelToken;
+use crate::services::llm_client::LlmClient;
+
+/// Merge rounds after the hourly pass; each round roughly halves the text.
+const MAX_REDUCE_ROUNDS: usize = 3;
+/// Target length of an hourly summary.
+const HOUR_SUMMARY_WORDS: usize = 150;
+/// Target length of a summary that merges several sections.
+const MERGED_SUMMARY_WORDS: usize = 250;
+
+const CONDENSE_PROMPT: &str = "\
+You are condensing notes from a computer activity monitoring system.
+
+Below are activity summaries covering {range}. Rewrite them as one chronological summary of at most {words} words.
+
+Keep precise times (HH:MM), application names, project names, file names, error messages and concrete outcomes. Drop repetition and descriptions of screen layout. Reply with the summary only.
+
+{summaries}";
+
+/// Activity summary for a span of the day.
+#[derive(Debug, Clone)]
+pub struct Section {
+    /// "HH:MM" of the first and last batch in the span.
+    pub start: String,
+    pub end: String,
+    pub text: String,
+}
+
+impl Section {
+    pub fn label(&self) -> String {
+        if self.start == self.end {
+            self.start.clone()
+        } else {
+            format!("{}\u{2013}{}", self.start, self.end)
+        }
+    }
+}
+
+/// Rough token count of `text` (about 4 characters per token for English).
+pub fn estimate_tokens(text: &str) -> usize {
+    text.chars().count().div_ceil(4)
+}
+
+/// Hour and "HH:MM" of a batch timestamp (RFC 3339).
+fn time_of(timestamp: &str) -> (u32, String) {
+    match DateTime::parse_from_rfc3339(timestamp) {
+        Ok(t) => (t.hour(), t.format("%H:%M").to_string()),
+        Err(_) => {
+            let hour = timestamp.get(11..13).and_then(|h| h.parse().ok()).unwrap_or(0);
+            let time = timestamp.get(11..16).unwrap_or(timestamp).to_string();
+            (hour, time)
+        }
+    }
+}
+
+/// Batches grouped by clock hour. An hour larger than `budget` tokens is split
+/// so that every group fits in one condense request.
+fn hour_groups(batches: &[BatchSummary], budget: usize) -> Vec<Section> {
+    let mut groups: Vec<Section> = Vec::new();
+    let mut current_hour = None;
+    for batch in batches {
+        let (hour, time) = time_of(&batch.timestamp);
+        let text = format!("### {}\n{}\n\n", time, batch.summary.trim());
+        match groups.last_mut() {
+            Some(group)
+                if current_hour == Some(hour)
+                    && estimate_tokens(&group.text) + estimate_tokens(&text) <= budget =>
+            {
+                group.end = time;
+                group.text.push_str(&text);
+            }
+            _ => groups.push(Section {
+                start: time.clone(),
+                end: time,
+                text,
+            }),
+        }
+        current_hour = Some(hour);
+    }
+    groups
+}
+
+/// Consecutive sections combined into groups of at most `budget` tokens, with
+/// at least two sections per group so every round makes progress.
+fn merge_groups(sections: Vec<Section>, budget: usize) -> Vec<Vec<Section>> {
+    let mut groups: Vec<Vec<Section>> = Vec::new();
+    let mut size = 0;
+    for section in sections {
+        let tokens = estimate_tokens(&section.text);
+        match groups.last_mut() {
+            Some(group) if group.len() < 2 || size + tokens <= budget => {
+                group.push(section);
+                size += tokens;
+            }
+            _ => {
+                size = tokens;
+                groups.push(vec![section]);
+            }
+        }
+    }
+    groups
+}
+
+async fn condense_section(
+    llm_client: &LlmClient,
+    section: Section,
+    words: usize,
+    cancel: &CancelToken,
+) -> Result<Section> {
+    // Already short enough: skip the request
+    if estimate_tokens(&section.text) <= words * 4 / 3 {
+        return Ok(section);
+    }
+    cancel.check()?;
+    let prompt = CONDENSE_PROMPT
+        .replace("{range}", &section.label())
+        .replace("{words}", &words.to_string())
+        .replace("{summaries}", &section.text);
+    let text = llm_client.send_multimodal(&prompt, &[]).await?;
+    Ok(Section {
+        text: text.trim().to_string(),
+        ..section
+    })
+}
+
+/// Map-reduce the day's batch summaries until they fit in `budget` tokens:
+/// summarize each hour, then merge neighbouring summaries for a few rounds.
+/// Each condense request is kept under `request_budget` tokens of input.
+pub async fn condense_batches(
+    llm_client: &LlmClient,
+    batches: &[BatchSummary],
+    budget: usize,
+    request_budget: usize,
+    cancel: &CancelToken,
+) -> Result<Vec<Section>> {
+    let groups = hour_groups(batches, request_budget);
+    log::info!(
+        "Condensing {} batches into {} hourly summaries",
+        batches.len(),
+        groups.len()
+    );
+    let mut sections = Vec::with_capacity(groups.len());
+    for group in groups {
+        sections.push(condense_section(llm_client, group, HOUR_SUMMARY_WORDS, cancel).await?);
+    }
+
+    for round in 1..=MAX_REDUCE_ROUNDS {
+        let total: usize = sections.iter().map(|s| estimate_tokens(&s.text)).sum();
+        if total <= budget || sections.len() <= 1 {
+            break;
+        }
+        log::info!(
+            "Summaries still ~{} tokens (budget {}), merge round {}",
+            total,
+            budget,
+            round
+        );
+        let mut merged = Vec::new();
+        for group in merge_groups(sections, request_budget) {
+            if group.len() == 1 {
+                merged.extend(group);
+                continue;
+            }
+            let text = group
+                .iter()
+                .map(|s| format!("### {}\n{}\n\n", s.label(), s.text))
+                .collect::<String>();
+            let section = Section {
+                start: group[0].start.clone(),
+                end: group[group.len() - 1].end.clone(),
+                text,
+            };
+            merged.push(condense_section(llm_client, section, MERGED_SUMMARY_WORDS, cancel).await?);
+        }
+        sections = merged;
+    }
+
+    let total: usize = sections.iter().map(|s| estimate_tokens(&s.text)).sum();
+    if total > budget {
+        log::warn!(
+            "Condensed summaries are still ~{} tokens, above the budget of {}",
+            total,
+            budget
+        );
+    }
+    Ok(sections)
+}
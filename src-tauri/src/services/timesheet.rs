@@ -0,0 +1,367 @@
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, Duration, FixedOffset, NaiveDate, Utc};
+use std::collections::{BTreeMap, HashMap};
+
+use crate::services::activity_log::{ActivityEntry, ActivityLog};
+use crate::storage::projects::Project;
+
+/// Gaps between captures longer than this are treated as a break, not work.
+const MAX_ENTRY_GAP_SECS: i64 = 15 * 60;
+/// Blocks shorter than this are folded into the surrounding block.
+const MIN_BLOCK_SECS: i64 = 3 * 60;
+/// Time credited to the last capture before a break.
+const TRAILING_ENTRY_SECS: i64 = 60;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TimesheetFormat {
+    Csv,
+    Ics,
+    Toggl,
+    Clockify,
+}
+
+impl TimesheetFormat {
+    pub fn parse(format: &str) -> Result<Self> {
+        match format {
+            "csv" => Ok(Self::Csv),
+            "ics" => Ok(Self::Ics),
+            "toggl" => Ok(Self::Toggl),
+            "clockify" => Ok(Self::Clockify),
+            other => Err(anyhow!("Unknown timesheet format: {}", other)),
+        }
+    }
+}
+
+/// A contiguous stretch of work on one project (or, without projects, one app).
+#[derive(Debug, Clone)]
+pub struct WorkBlock {
+    pub start: DateTime<FixedOffset>,
+    pub end: DateTime<FixedOffset>,
+    pub app_name: String,
+    pub window_title: String,
+    pub project: String,
+    pub client: String,
+}
+
+impl WorkBlock {
+    pub fn duration_secs(&self) -> i64 {
+        (self.end - self.start).num_seconds()
+    }
+}
+
+/// One capture stretched until the next capture (or a break).
+struct Segment {
+    start: DateTime<FixedOffset>,
+    end: DateTime<FixedOffset>,
+    key: String,
+    entry: ActivityEntry,
+}
+
+/// Collect work blocks for every day in `start_date..=end_date` (YYYY-MM-DD).
+pub fn build_work_blocks(
+    activity_log: &ActivityLog,
+    projects: &[Project],
+    start_date: &str,
+    end_date: &str,
+) -> Result<Vec<WorkBlock>> {
+    let start = NaiveDate::parse_from_str(start_date, "%Y-%m-%d")?;
+    let end = NaiveDate::parse_from_str(end_date, "%Y-%m-%d")?;
+    if end < start {
+        return Err(anyhow!("End date {} is before start date {}", end_date, start_date));
+    }
+
+    let mut blocks = Vec::new();
+    let mut day = start;
+    while day <= end {
+        let date = day.format("%Y-%m-%d").to_string();
+        if activity_log.date_exists(&date) {
+            let entries = activity_log.get_entries_for_date(&date)?;
+            blocks.extend(blocks_for_entries(&entries, projects));
+        }
+        day = day
+            .succ_opt()
+            .ok_or_else(|| anyhow!("Date out of range after {}", day))?;
+    }
+    Ok(blocks)
+}
+
+fn blocks_for_entries(entries: &[ActivityEntry], projects: &[Project]) -> Vec<WorkBlock> {
+    let segments = segments_for_entries(entries);
+    let merged = merge_segments(segments);
+
+    merged
+        .into_iter()
+        .map(|group| {
+            let start = group[0].start;
+            let end = group[group.len() - 1].end;
+            let (app_name, window_title) = dominant_app_and_title(&group);
+            let project = group[0]
+                .entry
+                .project_id
+                .as_ref()
+                .and_then(|id| projects.iter().find(|p| &p.id == id));
+            WorkBlock {
+                start,
+                end,
+                app_name,
+                window_title,
+                project: project.map(|p| p.name.clone()).unwrap_or_default(),
+                client: project.map(|p| p.client.clone()).unwrap_or_default(),
+            }
+        })
+        .collect()
+}
+
+fn segments_for_entries(entries: &[ActivityEntry]) -> Vec<Segment> {
+    timed_entries(entries)
+        .into_iter()
+        .map(|(start, secs, entry)| Segment {
+            start,
+            end: start + Duration::seconds(secs),
+            key: entry
+                .project_id
+                .clone()
+                .unwrap_or_else(|| format!("app:{}", entry.app_name)),
+            entry: entry.clone(),
+        })
+        .collect()
+}
+
+/// Entries with a valid timestamp, each with the seconds of work it stands
+/// for: the time until the next capture, or `TRAILING_ENTRY_SECS` before a
+/// break and for the last capture.
+fn timed_entries(entries: &[ActivityEntry]) -> Vec<(DateTime<FixedOffset>, i64, &ActivityEntry)> {
+    let parsed: Vec<(DateTime<FixedOffset>, &ActivityEntry)> = entries
+        .iter()
+        .filter_map(|e| DateTime::parse_from_rfc3339(&e.timestamp).ok().map(|t| (t, e)))
+        .collect();
+
+    parsed
+        .iter()
+        .enumerate()
+        .map(|(i, (start, entry))| {
+            let secs = match parsed.get(i + 1) {
+                Some((next, _)) if (*next - *start).num_seconds() <= MAX_ENTRY_GAP_SECS => {
+                    (*next - *start).num_seconds()
+                }
+                _ => TRAILING_ENTRY_SECS,
+            };
+            (*start, secs, *entry)
+        })
+        .collect()
+}
+
+/// Time spent per key over a day's entries (ordered by timestamp), in
+/// seconds, longest first. Entries without a key still end the previous
+/// entry's time but are not counted.
+pub fn time_by<K: Ord>(
+    entries: &[ActivityEntry],
+    key: impl Fn(&ActivityEntry) -> Option<K>,
+) -> Vec<(K, i64)> {
+    let mut totals: BTreeMap<K, i64> = BTreeMap::new();
+    for (_, secs, entry) in timed_entries(entries) {
+        if let Some(key) = key(entry) {
+            *totals.entry(key).or_default() += secs;
+        }
+    }
+    let mut totals: Vec<(K, i64)> = totals.into_iter().collect();
+    totals.sort_by_key(|(_, secs)| std::cmp::Reverse(*secs));
+    totals
+}
+
+/// Group segments into blocks: consecutive segments with the same key join,
+/// and short interruptions between contiguous segments are absorbed by the
+/// preceding block.
+fn merge_segments(segments: Vec<Segment>) -> Vec<Vec<Segment>> {
+    let mut groups: Vec<Vec<Segment>> = Vec::new();
+    for segment in segments {
+        match groups.last_mut() {
+            Some(group)
+                if group[group.len() - 1].end == segment.start
+                    && group[0].key == segment.key =>
+            {
+                group.push(segment)
+            }
+            _ => groups.push(vec![segment]),
+        }
+    }
+
+    let mut result: Vec<Vec<Segment>> = Vec::new();
+    for mut group in groups {
+        let group_secs = (group[group.len() - 1].end - group[0].start).num_seconds();
+        if let Some(prev) = result.last_mut() {
+            let contiguous = prev[prev.len() - 1].end == group[0].start;
+            if contiguous && (group_secs < MIN_BLOCK_SECS || prev[0].key == group[0].key) {
+                // Keep the block's key: relabel absorbed segments
+                let key = prev[0].key.clone();
+                for seg in group.iter_mut() {
+                    seg.key = key.clone();
+                    seg.entry.project_id = prev[0].entry.project_id.clone();
+                }
+                prev.extend(group);
+                continue;
+            }
+        }
+        result.push(group);
+    }
+    result
+}
+
+/// App and window title that account for the most time in a block.
+fn dominant_app_and_title(group: &[Segment]) -> (String, String) {
+    let mut by_title: HashMap<(&str, &str), i64> = HashMap::new();
+    for seg in group {
+        *by_title
+            .entry((seg.entry.app_name.as_str(), seg.entry.window_title.as_str()))
+            .or_default() += (seg.end - seg.start).num_seconds();
+    }
+    by_title
+        .into_iter()
+        .max_by_key(|(_, secs)| *secs)
+        .map(|((app, title), _)| (app.to_string(), title.to_string()))
+        .unwrap_or_default()
+}
+
+pub fn render(blocks: &[WorkBlock], format: TimesheetFormat) -> Result<String> {
+    match format {
+        TimesheetFormat::Csv => Ok(render_csv(blocks)),
+        TimesheetFormat::Ics => Ok(render_ics(blocks)),
+        TimesheetFormat::Toggl => render_toggl(blocks),
+        TimesheetFormat::Clockify => render_clockify(blocks),
+    }
+}
+
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+fn render_csv(blocks: &[WorkBlock]) -> String {
+    let mut out = String::from("date,start,end,duration_minutes,project,client,app,title\n");
+    for block in blocks {
+        out.push_str(&format!(
+            "{},{},{},{},{},{},{},{}\n",
+            block.start.format("%Y-%m-%d"),
+            block.start.format("%H:%M"),
+            block.end.format("%H:%M"),
+            (block.duration_secs() + 30) / 60,
+            csv_field(&block.project),
+            csv_field(&block.client),
+            csv_field(&block.app_name),
+            csv_field(&block.window_title),
+        ));
+    }
+    out
+}
+
+fn ics_text(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace(';', "\\;")
+        .replace(',', "\\,")
+        .replace('\n', "\\n")
+        .replace('\r', "")
+}
+
+/// Fold content lines longer than 75 octets as required by RFC 5545.
+fn ics_fold(line: &str) -> String {
+    let mut out = String::new();
+    let mut len = 0;
+    for c in line.chars() {
+        let c_len = c.len_utf8();
+        if len + c_len > 75 {
+            out.push_str("\r\n ");
+            len = 1;
+        }
+        out.push(c);
+        len += c_len;
+    }
+    out
+}
+
+fn ics_time(t: &DateTime<FixedOffset>) -> String {
+    t.with_timezone(&Utc).format("%Y%m%dT%H%M%SZ").to_string()
+}
+
+fn block_label(block: &WorkBlock) -> String {
+    if block.project.is_empty() {
+        block.app_name.clone()
+    } else {
+        block.project.clone()
+    }
+}
+
+fn render_ics(blocks: &[WorkBlock]) -> String {
+    let stamp = Utc::now().format("%Y%m%dT%H%M%SZ").to_string();
+    let mut lines = vec![
+        "BEGIN:VCALENDAR".to_string(),
+        "VERSION:2.0".to_string(),
+        "PRODID:-//Diaroo//Timesheet//EN".to_string(),
+        "CALSCALE:GREGORIAN".to_string(),
+    ];
+    for block in blocks {
+        let description = format!("{} - {}", block.app_name, block.window_title);
+        lines.push("BEGIN:VEVENT".to_string());
+        lines.push(format!(
+            "UID:{}-{}@diaroo",
+            ics_time(&block.start),
+            block.duration_secs()
+        ));
+        lines.push(format!("DTSTAMP:{}", stamp));
+        lines.push(format!("DTSTART:{}", ics_time(&block.start)));
+        lines.push(format!("DTEND:{}", ics_time(&block.end)));
+        lines.push(format!("SUMMARY:{}", ics_text(&block_label(block))));
+        lines.push(format!("DESCRIPTION:{}", ics_text(&description)));
+        if !block.client.is_empty() {
+            lines.push(format!("CATEGORIES:{}", ics_text(&block.client)));
+        }
+        lines.push("END:VEVENT".to_string());
+    }
+    lines.push("END:VCALENDAR".to_string());
+
+    let mut out = lines.iter().map(|l| ics_fold(l)).collect::<Vec<_>>().join("\r\n");
+    out.push_str("\r\n");
+    out
+}
+
+/// Time entries shaped like Toggl Track's API (`start`/`stop`/`duration`).
+fn render_toggl(blocks: &[WorkBlock]) -> Result<String> {
+    let entries: Vec<serde_json::Value> = blocks
+        .iter()
+        .map(|block| {
+            serde_json::json!({
+                "description": block.window_title,
+                "start": block.start.to_rfc3339(),
+                "stop": block.end.to_rfc3339(),
+                "duration": block.duration_secs(),
+                "project": block.project,
+                "client": block.client,
+                "tags": [block.app_name],
+                "created_with": "diaroo",
+            })
+        })
+        .collect();
+    Ok(serde_json::to_string_pretty(&entries)?)
+}
+
+/// Time entries shaped like Clockify's API (`start`/`end`/`projectName`).
+fn render_clockify(blocks: &[WorkBlock]) -> Result<String> {
+    let entries: Vec<serde_json::Value> = blocks
+        .iter()
+        .map(|block| {
+            serde_json::json!({
+                "description": block.window_title,
+                "start": block.start.with_timezone(&Utc).to_rfc3339(),
+                "end": block.end.with_timezone(&Utc).to_rfc3339(),
+                "projectName": block.project,
+                "clientName": block.client,
+                "tags": [block.app_name],
+                "billable": !block.project.is_empty(),
+            })
+        })
+        .collect();
+    Ok(serde_json::to_string_pretty(&entries)?)
+}
@@ -0,0 +1,207 @@
+use anyhow::Result;
+use chrono::{Duration, Local};
+use regex::Regex;
+use rusqlite::{params, Connection};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+use super::config::AppConfig;
+
+/// Every outbound LLM request, kept apart from the per-day activity databases
+/// so it survives day cleanups and can be reviewed on its own.
+const AUDIT_DB: &str = "audit.db";
+
+const SCHEMA: &str = "
+    CREATE TABLE IF NOT EXISTS llm_requests (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        timestamp TEXT NOT NULL,
+        provider TEXT NOT NULL,
+        endpoint TEXT NOT NULL,
+        model TEXT NOT NULL,
+        prompt_hash TEXT NOT NULL,
+        prompt_bytes INTEGER NOT NULL,
+        prompt TEXT,
+        images TEXT NOT NULL DEFAULT '[]',
+        response_bytes INTEGER,
+        activity_ids TEXT NOT NULL DEFAULT '[]',
+        error TEXT
+    );
+    CREATE INDEX IF NOT EXISTS idx_llm_requests_timestamp ON llm_requests(timestamp);
+";
+
+const REDACTED: &str = "[REDACTED]";
+
+/// Where and how requests are recorded.
+#[derive(Debug, Clone)]
+pub struct AuditSettings {
+    pub data_dir: PathBuf,
+    /// Store the full prompt text; otherwise only its hash and size.
+    pub store_prompts: bool,
+    /// Entries older than this are deleted; 0 keeps everything.
+    pub retention_days: u32,
+}
+
+impl AuditSettings {
+    /// `None` when auditing is turned off.
+    pub fn from_config(config: &AppConfig) -> Option<Self> {
+        config.audit_log_enabled.then(|| Self {
+            data_dir: config.data_path(),
+            store_prompts: config.audit_store_prompts,
+            retention_days: config.audit_retention_days,
+        })
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AuditImage {
+    pub hash: String,
+    pub bytes: usize,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct AuditEntry {
+    pub id: i64,
+    pub timestamp: String,
+    pub provider: String,
+    pub endpoint: String,
+    pub model: String,
+    pub prompt_hash: String,
+    pub prompt_bytes: i64,
+    pub prompt: Option<String>,
+    pub images: Vec<AuditImage>,
+    /// `None` when the request failed.
+    pub response_bytes: Option<i64>,
+    pub activity_ids: Vec<i64>,
+    pub error: Option<String>,
+}
+
+/// One request as seen by the client.
+pub struct AuditRecord<'a> {
+    pub provider: &'a str,
+    pub endpoint: &'a str,
+    pub model: &'a str,
+    pub prompt: &'a str,
+    /// Images actually attached to the request (CLI providers send none).
+    pub images: &'a [Vec<u8>],
+    /// Response size in bytes, or the error.
+    pub response: std::result::Result<usize, String>,
+    pub activity_ids: &'a [i64],
+    /// Values to scrub from everything stored (API keys).
+    pub secrets: &'a [&'a str],
+}
+
+/// SHA-256 hex digest of `bytes`.
+pub fn content_hash(bytes: &[u8]) -> String {
+    Sha256::digest(bytes)
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+/// Replace `secrets` and anything shaped like a bearer token or API key.
+pub fn redact(text: &str, secrets: &[&str]) -> String {
+    static KEY_PATTERN: OnceLock<Regex> = OnceLock::new();
+    let pattern = KEY_PATTERN.get_or_init(|| {
+        Regex::new(r"(?i)(bearer\s+[A-Za-z0-9._~+/-]{8,}|\bsk-[A-Za-z0-9_-]{8,}|([?&](api_?key|key|token)=)[^&\s]+)")
+            .unwrap()
+    });
+    let mut text = text.to_string();
+    for secret in secrets.iter().map(|s| s.trim()).filter(|s| s.len() >= 8) {
+        text = text.replace(secret, REDACTED);
+    }
+    pattern
+        .replace_all(&text, |caps: &regex::Captures| match caps.get(2) {
+            Some(param) => format!("{}{}", param.as_str(), REDACTED),
+            None => REDACTED.to_string(),
+        })
+        .into_owned()
+}
+
+fn open(data_dir: &Path) -> Result<Connection> {
+    std::fs::create_dir_all(data_dir)?;
+    let conn = Connection::open(data_dir.join(AUDIT_DB))?;
+    conn.execute_batch(SCHEMA)?;
+    Ok(conn)
+}
+
+/// Store one request and apply the retention window.
+pub fn record(settings: &AuditSettings, record: AuditRecord) -> Result<()> {
+    let conn = open(&settings.data_dir)?;
+    let images: Vec<AuditImage> = record
+        .images
+        .iter()
+        .map(|img| AuditImage {
+            hash: content_hash(img),
+            bytes: img.len(),
+        })
+        .collect();
+    let (response_bytes, error) = match &record.response {
+        Ok(bytes) => (Some(*bytes as i64), None),
+        Err(e) => (None, Some(redact(e, record.secrets))),
+    };
+    conn.execute(
+        "INSERT INTO llm_requests (timestamp, provider, endpoint, model, prompt_hash, prompt_bytes,
+                                   prompt, images, response_bytes, activity_ids, error)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11)",
+        params![
+            Local::now().to_rfc3339(),
+            record.provider,
+            redact(record.endpoint, record.secrets),
+            record.model,
+            content_hash(record.prompt.as_bytes()),
+            record.prompt.len() as i64,
+            settings
+                .store_prompts
+                .then(|| redact(record.prompt, record.secrets)),
+            serde_json::to_string(&images)?,
+            response_bytes,
+            serde_json::to_string(record.activity_ids)?,
+            error,
+        ],
+    )?;
+
+    if settings.retention_days > 0 {
+        let cutoff = Local::now() - Duration::days(settings.retention_days as i64);
+        conn.execute(
+            "DELETE FROM llm_requests WHERE timestamp < ?1",
+            params![cutoff.to_rfc3339()],
+        )?;
+    }
+    Ok(())
+}
+
+/// Most recent requests first.
+pub fn list(data_dir: &Path, limit: u32, offset: u32) -> Result<Vec<AuditEntry>> {
+    if !data_dir.join(AUDIT_DB).exists() {
+        return Ok(Vec::new());
+    }
+    let conn = open(data_dir)?;
+    let mut stmt = conn.prepare(
+        "SELECT id, timestamp, provider, endpoint, model, prompt_hash, prompt_bytes, prompt,
+                images, response_bytes, activity_ids, error
+         FROM llm_requests ORDER BY id DESC LIMIT ?1 OFFSET ?2",
+    )?;
+    let entries = stmt
+        .query_map(params![limit, offset], |row| {
+            let images: String = row.get(8)?;
+            let activity_ids: String = row.get(10)?;
+            Ok(AuditEntry {
+                id: row.get(0)?,
+                timestamp: row.get(1)?,
+                provider: row.get(2)?,
+                endpoint: row.get(3)?,
+                model: row.get(4)?,
+                prompt_hash: row.get(5)?,
+                prompt_bytes: row.get(6)?,
+                prompt: row.get(7)?,
+                images: serde_json::from_str(&images).unwrap_or_default(),
+                response_bytes: row.get(9)?,
+                activity_ids: serde_json::from_str(&activity_ids).unwrap_or_default(),
+                error: row.get(11)?,
+            })
+        })?
+        .collect::<std::result::Result<Vec<_>, _>>()?;
+    Ok(entries)
+}
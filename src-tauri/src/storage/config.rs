@@ -2,6 +2,9 @@ use anyhow::Result;
 use serde::{Deserialize, Serialize};
 use std::path::PathBuf;
 
+use super::profiles::{DigestProfile, DEFAULT_PROFILE};
+use super::projects::Project;
+
 /// Return the app-specific data directory without touching the parent directory.
 /// On macOS `dirs::data_local_dir()` resolves `~/Library/Application Support/`
 /// which triggers TCC prompts for "access data from other apps". By constructing
@@ -52,9 +55,96 @@ pub struct AppConfig {
     pub api_key: String,
     pub model: String,
     pub api_endpoint: String,
+    /// Overrides of the LLM settings above for screenshot extraction and digests;
+    /// empty fields use the settings above.
+    pub extraction_llm: LlmSettings,
+    pub digest_llm: LlmSettings,
+    /// Q&A runs through the claude CLI unless this is set.
+    pub qa_llm: LlmSettings,
+    /// Providers tried in order when a step's provider fails or is cooling down.
+    pub llm_fallbacks: Vec<LlmSettings>,
+    /// When every provider fails, store a summary built from app names instead of
+    /// keeping the screenshots for the next batch.
+    pub llm_text_fallback: bool,
+    /// Disk space for screenshots queued while the LLM endpoint is unreachable;
+    /// above it frames are downsampled, then dropped.
+    pub offline_queue_budget_mb: u64,
+    /// Record every outbound LLM request in `audit.db` in the data directory.
+    pub audit_log_enabled: bool,
+    /// Keep full prompt text in the audit log instead of only its hash.
+    pub audit_store_prompts: bool,
+    /// Days to keep audit entries; 0 keeps them forever.
+    pub audit_retention_days: u32,
+    /// Placeholders for personal data in text sent to the LLM.
+    pub redaction: RedactionSettings,
+    /// Masks applied to screenshots before they are saved.
+    pub image_redaction: ImageRedactionSettings,
     pub screenshot_interval_secs: u64,
+    /// "screen" keeps the whole primary monitor; "adaptive" crops it to the
+    /// focused window so the window gets the full capture size; "window"
+    /// captures the focused window's own pixels, leaving out overlapping windows.
+    pub capture_mode: String,
+    /// Add a small full-screen thumbnail below adaptive and window captures.
+    pub capture_thumbnail: bool,
+    /// Captures larger than this are scaled down to fit, keeping their ratio.
+    pub capture_width: u32,
+    pub capture_height: u32,
+    /// Resampling filter used when scaling down: "nearest", "triangle",
+    /// "catmullrom", "gaussian" or "lanczos3".
+    pub capture_filter: String,
+    /// "jpeg", "webp" or "png"; WebP and PNG are lossless.
+    pub image_format: String,
+    /// JPEG quality (1-100).
+    pub image_quality: u8,
     pub batch_interval_secs: u64,
+    /// Accept active tab reports from the Diaroo browser extension on
+    /// `127.0.0.1:browser_integration_port`.
+    pub browser_integration_enabled: bool,
+    pub browser_integration_port: u16,
+    /// Screenshots are not kept while the active tab is on one of these
+    /// domains (or their subdomains), or while a browser is focused and its
+    /// tab is unknown.
+    pub excluded_domains: Vec<String>,
+    /// Record the git repository, branch and last commit of the focused
+    /// terminal or IDE (Linux only).
+    pub dev_context_enabled: bool,
+    /// App names (matched as lowercase substrings) treated as terminals and IDEs.
+    pub dev_context_apps: Vec<String>,
+    /// Local repositories whose commits of the day are listed in digests.
+    pub git_repos: Vec<String>,
+    /// `git log --author` pattern; empty uses each repository's `user.email`.
+    pub git_author: String,
+    /// Record meeting intervals from conferencing app windows and meeting URLs.
+    pub meeting_detection_enabled: bool,
+    /// Also count a call while the microphone or camera is in use (Linux only).
+    pub meeting_device_detection: bool,
+    /// Extra window title patterns (regex) that mean a meeting.
+    pub meeting_title_patterns: Vec<String>,
+    /// Local `.ics` file whose timed events of the day are added to digests.
+    pub meeting_calendar_path: String,
     pub dedup_threshold: u32,
+    /// Keep a capture when the focused app or window title changes, even if
+    /// the screen looks similar.
+    pub keyframe_on_title_change: bool,
+    /// Minimum time between two keyframes; 0 keeps every change.
+    pub keyframe_min_interval_secs: u64,
+    /// Force a keyframe after this long without one; 0 never forces.
+    pub keyframe_max_interval_secs: u64,
+    /// Images sent per batch request, picked as the most distinct frames; the
+    /// other frames are only described by their window titles. 0 sends every
+    /// frame in requests of up to 10 images.
+    pub keyframes_per_batch: usize,
+    /// "text" stores the LLM's free-text batch summary; "structured" requests JSON
+    /// and also stores per-screenshot observations.
+    pub extraction_mode: String,
+    /// Local OCR of each saved screenshot: "off", "alongside" (screen text is
+    /// added to the extract prompt next to the images) or "instead" (only the
+    /// text is sent, no images).
+    pub ocr_mode: String,
+    /// Prompt template used for batch extraction ("default" = extract_prompt.txt).
+    pub extract_template: String,
+    /// Prompt template used for daily digests ("default" = digest_prompt.txt).
+    pub digest_template: String,
     pub data_dir: String,
     pub pet_name: String,
     pub pet_size: String,
@@ -66,8 +156,194 @@ pub struct AppConfig {
     pub auto_start_monitoring_time_enabled: bool,
     pub auto_start_monitoring_time: String,
     pub launch_at_startup: bool,
+    pub projects: Vec<Project>,
+    /// Ask the LLM to pick a project for batches whose entries matched no rule.
+    pub project_llm_classification: bool,
+    /// Also write each digest into a notes vault (Obsidian, Logseq, ...).
+    pub note_export_enabled: bool,
+    pub note_export_vault_path: String,
+    /// Path of the daily note inside the vault; supports {date}, {year}, {month}, {day}.
+    pub note_export_filename_template: String,
+    /// "overwrite" replaces the whole note; "append_under_heading" only updates
+    /// Diaroo's block under `note_export_heading` and keeps the rest of the note.
+    pub note_export_mode: String,
+    pub note_export_heading: String,
+    /// Extra report styles next to the default report.
+    pub digest_profiles: Vec<DigestProfile>,
+    /// Estimated prompt size (tokens) above which batch summaries are condensed
+    /// hour by hour before the digest request; 0 never condenses.
+    pub digest_token_threshold: usize,
+    /// Per-model overrides of `digest_token_threshold`, first match wins.
+    pub model_token_thresholds: Vec<ModelTokenThreshold>,
+}
+
+/// The step an LLM request belongs to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LlmStep {
+    Extraction,
+    Digest,
+    Qa,
+}
+
+/// Provider, key, model and endpoint of an LLM client.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LlmSettings {
+    pub llm_provider: String,
+    pub api_key: String,
+    pub model: String,
+    pub api_endpoint: String,
+}
+
+impl LlmSettings {
+    pub fn is_empty(&self) -> bool {
+        self.llm_provider.is_empty()
+            && self.api_key.is_empty()
+            && self.model.is_empty()
+            && self.api_endpoint.is_empty()
+    }
+
+    /// Whether `overrides` switch to another provider that needs a model
+    /// without naming one; the model is not inherited across providers.
+    pub fn switch_lacks_model(&self, overrides: &LlmSettings) -> bool {
+        let provider = overrides.llm_provider.trim();
+        !provider.is_empty()
+            && provider != self.llm_provider
+            && !matches!(provider, "claude-code" | "codex")
+            && overrides.model.trim().is_empty()
+    }
+
+    /// `self` with the non-empty fields of `overrides` applied. The key, model
+    /// and endpoint are not inherited across providers; an empty endpoint lets
+    /// the client pick its default.
+    pub fn with_overrides(&self, overrides: &LlmSettings) -> LlmSettings {
+        fn or(value: &str, fallback: &str) -> String {
+            if value.trim().is_empty() { fallback } else { value }.trim().to_string()
+        }
+        let switches_provider = !overrides.llm_provider.trim().is_empty()
+            && overrides.llm_provider.trim() != self.llm_provider;
+        let inherit = |value: &str, fallback: &str| {
+            if switches_provider {
+                value.trim().to_string()
+            } else {
+                or(value, fallback)
+            }
+        };
+        LlmSettings {
+            llm_provider: or(&overrides.llm_provider, &self.llm_provider),
+            api_key: inherit(&overrides.api_key, &self.api_key),
+            model: inherit(&overrides.model, &self.model),
+            api_endpoint: inherit(&overrides.api_endpoint, &self.api_endpoint),
+        }
+    }
+}
+
+/// Which values are replaced by placeholders before text leaves the machine.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RedactionSettings {
+    pub enabled: bool,
+    pub emails: bool,
+    pub phone_numbers: bool,
+    pub credit_cards: bool,
+    /// Bearer tokens, `sk-...` keys and similar long random strings.
+    pub api_keys: bool,
+    /// Extra patterns, e.g. ticket IDs; the label names the placeholder.
+    pub rules: Vec<RedactionRule>,
+    /// Words matched case-insensitively, e.g. customer names.
+    pub words: Vec<String>,
+    /// Put the real values back into the saved report.
+    pub restore_in_report: bool,
+}
+
+impl Default for RedactionSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            emails: true,
+            phone_numbers: true,
+            credit_cards: true,
+            api_keys: true,
+            rules: Vec::new(),
+            words: Vec::new(),
+            restore_in_report: true,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RedactionRule {
+    pub label: String,
+    /// Regular expression; the whole match is replaced.
+    pub pattern: String,
+}
+
+/// Parts of each screenshot hidden before it is written to disk.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ImageRedactionSettings {
+    pub enabled: bool,
+    /// Screen areas always filled, e.g. where notifications appear.
+    pub regions: Vec<MaskRegion>,
+    /// Apps whose windows are blurred, matched case-insensitively.
+    pub blur_apps: Vec<String>,
+    /// Run tesseract on each frame and fill words matching the text redaction
+    /// patterns.
+    pub ocr: bool,
+}
+
+/// Rectangle in percent of the screen width and height.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MaskRegion {
+    pub x: f64,
+    pub y: f64,
+    pub width: f64,
+    pub height: f64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ModelTokenThreshold {
+    /// Model name, or a prefix ending in `*` (e.g. "llama3*").
+    pub model: String,
+    pub max_tokens: usize,
 }
 
+impl ModelTokenThreshold {
+    fn matches(&self, model: &str) -> bool {
+        let pattern = self.model.trim();
+        match pattern.strip_suffix('*') {
+            Some(prefix) => model.starts_with(prefix),
+            None => !pattern.is_empty() && model == pattern,
+        }
+    }
+}
+
+/// Terminals and IDEs whose repository is recorded with developer context.
+const DEFAULT_DEV_CONTEXT_APPS: &[&str] = &[
+    "terminal",
+    "konsole",
+    "alacritty",
+    "kitty",
+    "wezterm",
+    "foot",
+    "xterm",
+    "tilix",
+    "code",
+    "cursor",
+    "zed",
+    "idea",
+    "pycharm",
+    "goland",
+    "webstorm",
+    "clion",
+    "rustrover",
+    "sublime",
+    "emacs",
+    "vim",
+];
+
 impl Default for AppConfig {
     fn default() -> Self {
         let data_dir = app_data_dir().join("data");
@@ -77,9 +353,46 @@ impl Default for AppConfig {
             api_key: String::new(),
             model: "openai/gpt-4o-mini".to_string(),
             api_endpoint: String::new(),
+            extraction_llm: LlmSettings::default(),
+            digest_llm: LlmSettings::default(),
+            qa_llm: LlmSettings::default(),
+            llm_fallbacks: Vec::new(),
+            llm_text_fallback: false,
+            offline_queue_budget_mb: 500,
+            audit_log_enabled: true,
+            audit_store_prompts: false,
+            audit_retention_days: 30,
+            redaction: RedactionSettings::default(),
+            image_redaction: ImageRedactionSettings::default(),
             screenshot_interval_secs: 5,
+            capture_mode: "screen".to_string(),
+            capture_thumbnail: false,
+            capture_width: 1280,
+            capture_height: 720,
+            capture_filter: "lanczos3".to_string(),
+            image_format: "jpeg".to_string(),
+            image_quality: 85,
             batch_interval_secs: 300,
+            browser_integration_enabled: false,
+            browser_integration_port: 47613,
+            excluded_domains: Vec::new(),
+            dev_context_enabled: false,
+            dev_context_apps: DEFAULT_DEV_CONTEXT_APPS.iter().map(|app| app.to_string()).collect(),
+            git_repos: Vec::new(),
+            git_author: String::new(),
+            meeting_detection_enabled: false,
+            meeting_device_detection: false,
+            meeting_title_patterns: Vec::new(),
+            meeting_calendar_path: String::new(),
             dedup_threshold: 5,
+            keyframe_on_title_change: false,
+            keyframe_min_interval_secs: 0,
+            keyframe_max_interval_secs: 0,
+            keyframes_per_batch: 0,
+            extraction_mode: "text".to_string(),
+            ocr_mode: "off".to_string(),
+            extract_template: "default".to_string(),
+            digest_template: "default".to_string(),
             data_dir: data_dir.to_string_lossy().to_string(),
             pet_name: "platypus".to_string(),
             pet_size: "medium".to_string(),
@@ -91,6 +404,16 @@ impl Default for AppConfig {
             auto_start_monitoring_time_enabled: false,
             auto_start_monitoring_time: "09:00".to_string(),
             launch_at_startup: false,
+            projects: Vec::new(),
+            project_llm_classification: false,
+            note_export_enabled: false,
+            note_export_vault_path: String::new(),
+            note_export_filename_template: "Journal/{date}.md".to_string(),
+            note_export_mode: "append_under_heading".to_string(),
+            note_export_heading: "## Diaroo".to_string(),
+            digest_profiles: Vec::new(),
+            digest_token_threshold: 24000,
+            model_token_thresholds: Vec::new(),
         }
     }
 }
@@ -108,6 +431,11 @@ impl AppConfig {
         app_config_dir().join("extract_prompt.txt")
     }
 
+    /// Directory of named prompt templates (`*.md` / `*.txt` with front-matter).
+    pub fn prompts_dir() -> PathBuf {
+        app_config_dir().join("prompts")
+    }
+
     pub fn load() -> Result<Self> {
         let path = Self::config_path();
         if path.exists() {
@@ -134,4 +462,113 @@ impl AppConfig {
     pub fn data_path(&self) -> PathBuf {
         PathBuf::from(&self.data_dir)
     }
+
+    /// LLM settings for `step`, falling back to the top-level settings.
+    pub fn llm_settings(&self, step: LlmStep) -> LlmSettings {
+        self.base_llm_settings().with_overrides(self.llm_overrides(step))
+    }
+
+    fn base_llm_settings(&self) -> LlmSettings {
+        LlmSettings {
+            llm_provider: self.llm_provider.clone(),
+            api_key: self.api_key.clone(),
+            model: self.model.clone(),
+            api_endpoint: self.api_endpoint.clone(),
+        }
+    }
+
+    fn llm_overrides(&self, step: LlmStep) -> &LlmSettings {
+        match step {
+            LlmStep::Extraction => &self.extraction_llm,
+            LlmStep::Digest => &self.digest_llm,
+            LlmStep::Qa => &self.qa_llm,
+        }
+    }
+
+    /// Fail on step or profile overrides that switch provider without naming
+    /// a model, whose requests would be sent without one.
+    pub fn check_llm_overrides(&self) -> Result<()> {
+        let base = self.base_llm_settings();
+        for (step, name) in [
+            (LlmStep::Extraction, "extraction"),
+            (LlmStep::Digest, "digest"),
+            (LlmStep::Qa, "Q&A"),
+        ] {
+            let overrides = self.llm_overrides(step);
+            if base.switch_lacks_model(overrides) {
+                return Err(anyhow::anyhow!(
+                    "The {} LLM settings switch to {} but name no model",
+                    name,
+                    overrides.llm_provider.trim()
+                ));
+            }
+        }
+        let digest = self.llm_settings(LlmStep::Digest);
+        for profile in &self.digest_profiles {
+            let overrides = profile.llm_overrides();
+            if digest.switch_lacks_model(&overrides) {
+                return Err(anyhow::anyhow!(
+                    "Report profile {} switches to {} but names no model",
+                    profile.name,
+                    overrides.llm_provider.trim()
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    /// The default profile, built from the top-level report settings.
+    pub fn default_profile(&self) -> DigestProfile {
+        DigestProfile {
+            name: DEFAULT_PROFILE.to_string(),
+            template: self.digest_template.clone(),
+            auto_report_enabled: self.auto_report_enabled,
+            auto_report_time: self.auto_report_time.clone(),
+            ..Default::default()
+        }
+    }
+
+    /// The default profile followed by the configured ones.
+    pub fn all_profiles(&self) -> Vec<DigestProfile> {
+        let mut profiles = vec![self.default_profile()];
+        profiles.extend(
+            self.digest_profiles
+                .iter()
+                .filter(|p| !p.name.trim().is_empty() && p.name != DEFAULT_PROFILE)
+                .cloned(),
+        );
+        profiles
+    }
+
+    /// Look up a profile by name; `None` or an empty name is the default profile.
+    /// An empty template is resolved to the report template from settings.
+    pub fn digest_profile(&self, name: Option<&str>) -> Result<DigestProfile> {
+        let name = name.map(str::trim).filter(|n| !n.is_empty()).unwrap_or(DEFAULT_PROFILE);
+        let mut profile = self
+            .all_profiles()
+            .into_iter()
+            .find(|p| p.name == name)
+            .ok_or_else(|| anyhow::anyhow!("Unknown report profile: {}", name))?;
+        if profile.template.trim().is_empty() {
+            profile.template = self.digest_template.clone();
+        }
+        Ok(profile)
+    }
+
+    /// Token threshold for digest prompts sent to `model`.
+    pub fn digest_token_threshold_for(&self, model: &str) -> usize {
+        self.model_token_thresholds
+            .iter()
+            .find(|t| t.matches(model))
+            .map(|t| t.max_tokens)
+            .unwrap_or(self.digest_token_threshold)
+    }
+
+    /// Profiles with an automatic report time.
+    pub fn scheduled_profiles(&self) -> Vec<DigestProfile> {
+        self.all_profiles()
+            .into_iter()
+            .filter(|p| p.auto_report_enabled)
+            .collect()
+    }
 }
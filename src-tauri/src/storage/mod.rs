@@ -1,3 +1,7 @@
+pub mod audit_log;
 pub mod config;
 pub mod pets;
+pub mod profiles;
+pub mod projects;
+pub mod report_history;
 pub mod screenshot_store;
@@ -0,0 +1,87 @@
+use serde::{Deserialize, Serialize};
+
+use super::config::LlmSettings;
+
+/// Name of the profile built from the top-level report settings.
+pub const DEFAULT_PROFILE: &str = "default";
+/// Report file of the default profile.
+pub const DEFAULT_REPORT_FILE: &str = "report.md";
+
+/// A named report style (standup, journal, weekly summary, ...) generated from
+/// the same day of data.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DigestProfile {
+    pub name: String,
+    /// Digest prompt template; empty uses the report template from settings.
+    pub template: String,
+    /// Overrides for the digest request only; empty fields use the main settings.
+    pub llm_provider: String,
+    pub model: String,
+    pub api_key: String,
+    pub api_endpoint: String,
+    /// File written in the date folder, e.g. "standup.md".
+    pub output_filename: String,
+    pub auto_report_enabled: bool,
+    pub auto_report_time: String,
+}
+
+impl Default for DigestProfile {
+    fn default() -> Self {
+        Self {
+            name: String::new(),
+            template: String::new(),
+            llm_provider: String::new(),
+            model: String::new(),
+            api_key: String::new(),
+            api_endpoint: String::new(),
+            output_filename: String::new(),
+            auto_report_enabled: false,
+            auto_report_time: "17:00".to_string(),
+        }
+    }
+}
+
+impl DigestProfile {
+    pub fn is_default(&self) -> bool {
+        self.name == DEFAULT_PROFILE
+    }
+
+    /// The profile's LLM fields, applied on top of the digest settings.
+    pub fn llm_overrides(&self) -> LlmSettings {
+        LlmSettings {
+            llm_provider: self.llm_provider.clone(),
+            api_key: self.api_key.clone(),
+            model: self.model.clone(),
+            api_endpoint: self.api_endpoint.clone(),
+        }
+    }
+
+    /// Plain `.md` file name inside the date folder. Falls back to the profile
+    /// name; never returns the default profile's `report.md` for other profiles,
+    /// nor `current.md`, whose versions folder would be the default report's
+    /// `current` pointer.
+    pub fn report_file(&self) -> String {
+        if self.is_default() {
+            return DEFAULT_REPORT_FILE.to_string();
+        }
+        let requested = std::path::Path::new(self.output_filename.trim())
+            .file_name()
+            .map(|n| n.to_string_lossy().to_string())
+            .unwrap_or_default();
+        let stem = requested.strip_suffix(".md").unwrap_or(&requested);
+        let stem = if stem.is_empty() { self.name.as_str() } else { stem };
+        let stem: String = stem
+            .chars()
+            .map(|c| if c.is_alphanumeric() || c == '-' || c == '_' { c } else { '-' })
+            .collect();
+        match stem.trim_matches('-') {
+            "" => "profile.md".to_string(),
+            // Case-insensitive file systems would still collide with "Report.md"
+            stem if matches!(stem.to_lowercase().as_str(), "report" | "current") => {
+                format!("{}-profile.md", stem)
+            }
+            stem => format!("{}.md", stem),
+        }
+    }
+}
@@ -0,0 +1,159 @@
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RuleKind {
+    /// Case-insensitive match on the application name.
+    App,
+    /// Regular expression searched in the window title.
+    TitleRegex,
+    /// Path prefix of the repository the focused window is working in.
+    RepoPath,
+    /// Domain (or parent domain) of the active browser tab.
+    UrlDomain,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProjectRule {
+    pub kind: RuleKind,
+    pub pattern: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Project {
+    pub id: String,
+    pub name: String,
+    pub client: String,
+    pub rules: Vec<ProjectRule>,
+}
+
+impl Default for Project {
+    fn default() -> Self {
+        Self {
+            id: uuid::Uuid::new_v4().to_string(),
+            name: String::new(),
+            client: String::new(),
+            rules: Vec::new(),
+        }
+    }
+}
+
+impl Project {
+    /// Name shown in digests, e.g. "Website redesign (Acme)".
+    pub fn display_name(&self) -> String {
+        if self.client.is_empty() {
+            self.name.clone()
+        } else {
+            format!("{} ({})", self.name, self.client)
+        }
+    }
+}
+
+/// What is known about the focused window at capture time.
+#[derive(Debug, Clone, Default)]
+pub struct MatchContext<'a> {
+    pub app_name: &'a str,
+    pub window_title: &'a str,
+    pub repo_path: Option<&'a str>,
+    pub url: Option<&'a str>,
+}
+
+enum CompiledRule {
+    App(String),
+    TitleRegex(Regex),
+    RepoPath(String),
+    UrlDomain(String),
+}
+
+/// Project rules compiled once per monitoring session. Projects are tried in
+/// list order and the first project with a matching rule wins.
+pub struct ProjectMatcher {
+    projects: Vec<(String, Vec<CompiledRule>)>,
+}
+
+impl ProjectMatcher {
+    pub fn new(projects: &[Project]) -> Self {
+        let projects = projects
+            .iter()
+            .map(|project| {
+                let rules = project
+                    .rules
+                    .iter()
+                    .filter(|rule| !rule.pattern.trim().is_empty())
+                    .filter_map(|rule| compile_rule(&project.name, rule))
+                    .collect();
+                (project.id.clone(), rules)
+            })
+            .collect();
+        Self { projects }
+    }
+
+    pub fn match_project(&self, ctx: &MatchContext<'_>) -> Option<String> {
+        self.projects
+            .iter()
+            .find(|(_, rules)| rules.iter().any(|rule| rule_matches(rule, ctx)))
+            .map(|(id, _)| id.clone())
+    }
+}
+
+fn compile_rule(project_name: &str, rule: &ProjectRule) -> Option<CompiledRule> {
+    let pattern = rule.pattern.trim();
+    match rule.kind {
+        RuleKind::App => Some(CompiledRule::App(pattern.to_lowercase())),
+        RuleKind::TitleRegex => match Regex::new(pattern) {
+            Ok(re) => Some(CompiledRule::TitleRegex(re)),
+            Err(e) => {
+                log::warn!(
+                    "Ignoring invalid title regex {:?} for project {}: {}",
+                    pattern,
+                    project_name,
+                    e
+                );
+                None
+            }
+        },
+        RuleKind::RepoPath => Some(CompiledRule::RepoPath(
+            pattern.trim_end_matches(['/', '\\']).to_string(),
+        )),
+        RuleKind::UrlDomain => Some(CompiledRule::UrlDomain(
+            pattern.trim_start_matches("*.").to_lowercase(),
+        )),
+    }
+}
+
+fn rule_matches(rule: &CompiledRule, ctx: &MatchContext<'_>) -> bool {
+    match rule {
+        CompiledRule::App(app) => ctx.app_name.to_lowercase() == *app,
+        CompiledRule::TitleRegex(re) => re.is_match(ctx.window_title),
+        CompiledRule::RepoPath(prefix) => ctx.repo_path.is_some_and(|path| {
+            path == prefix
+                || path
+                    .strip_prefix(prefix.as_str())
+                    .is_some_and(|rest| rest.starts_with(['/', '\\']))
+        }),
+        CompiledRule::UrlDomain(domain) => ctx
+            .url
+            .and_then(url_host)
+            .is_some_and(|host| host_in_domain(&host, domain)),
+    }
+}
+
+/// Whether `host` is `domain` or one of its subdomains.
+pub fn host_in_domain(host: &str, domain: &str) -> bool {
+    host == domain || host.ends_with(&format!(".{}", domain))
+}
+
+/// Extract the lowercase host from a URL without pulling in a URL parser.
+pub fn url_host(url: &str) -> Option<String> {
+    let rest = url.split_once("://").map(|(_, r)| r).unwrap_or(url);
+    let authority = rest.split(['/', '?', '#']).next()?;
+    let host = authority.rsplit('@').next()?;
+    let host = host.split(':').next()?.trim();
+    if host.is_empty() {
+        None
+    } else {
+        Some(host.to_lowercase())
+    }
+}
@@ -0,0 +1,281 @@
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, Local};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+use super::audit_log;
+
+/// Versions of `<date>/report.md` live in `<date>/reports/report-<id>.md` with a
+/// `.json` sidecar; other report files (profiles) use `<date>/reports/<stem>/`.
+/// The report file itself is always a copy of the current version so everything
+/// that reads it keeps working; `current` in the versions folder holds its id.
+const VERSIONS_DIR: &str = "reports";
+const CURRENT_POINTER: &str = "current";
+
+/// How a report was produced.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ReportMeta {
+    pub provider: String,
+    pub model: String,
+    pub prompt_hash: String,
+    pub prompt_tokens: Option<u64>,
+    pub completion_tokens: Option<u64>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReportVersion {
+    pub id: String,
+    pub created_at: String,
+    #[serde(flatten)]
+    pub meta: ReportMeta,
+    #[serde(skip_deserializing)]
+    pub is_current: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DiffOp {
+    Equal,
+    Insert,
+    Delete,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct DiffLine {
+    pub op: DiffOp,
+    pub text: String,
+}
+
+/// Stable hex hash used to tell prompt revisions apart.
+pub fn prompt_hash(prompt: &str) -> String {
+    audit_log::content_hash(prompt.as_bytes())
+}
+
+fn stem(report_file: &str) -> &str {
+    report_file.strip_suffix(".md").unwrap_or(report_file)
+}
+
+fn versions_dir(date_dir: &Path, report_file: &str) -> PathBuf {
+    match stem(report_file) {
+        "report" => date_dir.join(VERSIONS_DIR),
+        stem => date_dir.join(VERSIONS_DIR).join(stem),
+    }
+}
+
+fn version_path(date_dir: &Path, report_file: &str, id: &str) -> PathBuf {
+    versions_dir(date_dir, report_file).join(format!("{}-{}.md", stem(report_file), id))
+}
+
+fn meta_path(date_dir: &Path, report_file: &str, id: &str) -> PathBuf {
+    versions_dir(date_dir, report_file).join(format!("{}-{}.json", stem(report_file), id))
+}
+
+/// Ids come from the frontend, so only accept what `new_version_id` produces.
+fn validate_id(id: &str) -> Result<()> {
+    if id.is_empty()
+        || !id
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_' || c == '.')
+        || id.contains("..")
+    {
+        return Err(anyhow!("Invalid report version id: {}", id));
+    }
+    Ok(())
+}
+
+fn slug(text: &str) -> String {
+    let slug: String = text
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() || c == '.' { c } else { '-' })
+        .collect();
+    let slug = slug.trim_matches(['-', '.']).to_string();
+    if slug.is_empty() {
+        "none".to_string()
+    } else {
+        slug
+    }
+}
+
+fn new_version_id(
+    date_dir: &Path,
+    report_file: &str,
+    created_at: DateTime<Local>,
+    model: &str,
+) -> String {
+    let base = format!("{}-{}", created_at.format("%Y%m%d-%H%M%S"), slug(model));
+    let mut id = base.clone();
+    let mut n = 2;
+    while version_path(date_dir, report_file, &id).exists() {
+        id = format!("{}-{}", base, n);
+        n += 1;
+    }
+    id
+}
+
+fn write_atomic(path: &Path, contents: &str) -> Result<()> {
+    let mut tmp = path.as_os_str().to_owned();
+    tmp.push(".tmp");
+    let tmp = PathBuf::from(tmp);
+    std::fs::write(&tmp, contents)?;
+    std::fs::rename(&tmp, path)?;
+    Ok(())
+}
+
+fn write_version(
+    date_dir: &Path,
+    report_file: &str,
+    markdown: &str,
+    meta: ReportMeta,
+    created_at: DateTime<Local>,
+) -> Result<ReportVersion> {
+    std::fs::create_dir_all(versions_dir(date_dir, report_file))?;
+    let id = new_version_id(date_dir, report_file, created_at, &meta.model);
+    let version = ReportVersion {
+        id: id.clone(),
+        created_at: created_at.to_rfc3339(),
+        meta,
+        is_current: false,
+    };
+    std::fs::write(version_path(date_dir, report_file, &id), markdown)?;
+    // `is_current` is derived from the pointer file, not stored per version
+    let mut sidecar = serde_json::to_value(&version)?;
+    if let Some(obj) = sidecar.as_object_mut() {
+        obj.remove("is_current");
+    }
+    std::fs::write(
+        meta_path(date_dir, report_file, &id),
+        serde_json::to_string_pretty(&sidecar)?,
+    )?;
+    Ok(version)
+}
+
+/// Keep a report written before versioning existed as the first version.
+fn import_unversioned_report(date_dir: &Path, report_file: &str) -> Result<()> {
+    let report_path = date_dir.join(report_file);
+    let pointer = versions_dir(date_dir, report_file).join(CURRENT_POINTER);
+    if !report_path.exists() || pointer.exists() {
+        return Ok(());
+    }
+    let markdown = std::fs::read_to_string(&report_path)?;
+    let created_at: DateTime<Local> = std::fs::metadata(&report_path)
+        .and_then(|m| m.modified())
+        .map(DateTime::from)
+        .unwrap_or_else(|_| Local::now());
+    let meta = ReportMeta {
+        model: "unknown".to_string(),
+        ..Default::default()
+    };
+    let version = write_version(date_dir, report_file, &markdown, meta, created_at)?;
+    write_atomic(&pointer, &version.id)?;
+    log::info!("Imported existing {} as version {}", report_file, version.id);
+    Ok(())
+}
+
+/// Store `markdown` as a new version of `report_file` and make it current.
+pub fn save_version(
+    date_dir: &Path,
+    report_file: &str,
+    markdown: &str,
+    meta: ReportMeta,
+) -> Result<ReportVersion> {
+    import_unversioned_report(date_dir, report_file)?;
+    let mut version = write_version(date_dir, report_file, markdown, meta, Local::now())?;
+    promote(date_dir, report_file, &version.id)?;
+    version.is_current = true;
+    Ok(version)
+}
+
+pub fn current_version_id(date_dir: &Path, report_file: &str) -> Option<String> {
+    std::fs::read_to_string(versions_dir(date_dir, report_file).join(CURRENT_POINTER))
+        .ok()
+        .map(|s| s.trim().to_string())
+        .filter(|s| !s.is_empty())
+}
+
+/// All versions of a day's report file, newest first.
+pub fn list_versions(date_dir: &Path, report_file: &str) -> Result<Vec<ReportVersion>> {
+    let dir = versions_dir(date_dir, report_file);
+    if !dir.exists() {
+        return Ok(Vec::new());
+    }
+    let current = current_version_id(date_dir, report_file);
+
+    let mut versions = Vec::new();
+    for entry in std::fs::read_dir(&dir)? {
+        let path = entry?.path();
+        if !path.extension().is_some_and(|ext| ext == "json") {
+            continue;
+        }
+        let parsed = std::fs::read_to_string(&path)
+            .map_err(anyhow::Error::from)
+            .and_then(|text| Ok(serde_json::from_str::<ReportVersion>(&text)?));
+        match parsed {
+            Ok(mut version) => {
+                version.is_current = current.as_deref() == Some(version.id.as_str());
+                versions.push(version);
+            }
+            Err(e) => log::warn!("Skipping unreadable report metadata {:?}: {}", path, e),
+        }
+    }
+    versions.sort_by(|a, b| b.created_at.cmp(&a.created_at));
+    Ok(versions)
+}
+
+pub fn read_version(date_dir: &Path, report_file: &str, id: &str) -> Result<String> {
+    validate_id(id)?;
+    std::fs::read_to_string(version_path(date_dir, report_file, id))
+        .map_err(|e| anyhow!("Report version {} not found: {}", id, e))
+}
+
+/// Make `id` the current version by copying it over the report file.
+pub fn promote(date_dir: &Path, report_file: &str, id: &str) -> Result<()> {
+    let markdown = read_version(date_dir, report_file, id)?;
+    write_atomic(&date_dir.join(report_file), &markdown)?;
+    write_atomic(
+        &versions_dir(date_dir, report_file).join(CURRENT_POINTER),
+        id,
+    )?;
+    Ok(())
+}
+
+/// Line diff from `old` to `new` (longest common subsequence).
+pub fn diff_lines(old: &str, new: &str) -> Vec<DiffLine> {
+    let a: Vec<&str> = old.lines().collect();
+    let b: Vec<&str> = new.lines().collect();
+
+    // lcs[i][j] = LCS length of a[i..] and b[j..]
+    let mut lcs = vec![vec![0u32; b.len() + 1]; a.len() + 1];
+    for i in (0..a.len()).rev() {
+        for j in (0..b.len()).rev() {
+            lcs[i][j] = if a[i] == b[j] {
+                lcs[i + 1][j + 1] + 1
+            } else {
+                lcs[i + 1][j].max(lcs[i][j + 1])
+            };
+        }
+    }
+
+    let line = |op, text: &str| DiffLine {
+        op,
+        text: text.to_string(),
+    };
+    let mut out = Vec::with_capacity(a.len().max(b.len()));
+    let (mut i, mut j) = (0, 0);
+    while i < a.len() && j < b.len() {
+        if a[i] == b[j] {
+            out.push(line(DiffOp::Equal, a[i]));
+            i += 1;
+            j += 1;
+        } else if lcs[i + 1][j] >= lcs[i][j + 1] {
+            out.push(line(DiffOp::Delete, a[i]));
+            i += 1;
+        } else {
+            out.push(line(DiffOp::Insert, b[j]));
+            j += 1;
+        }
+    }
+    out.extend(a[i..].iter().map(|t| line(DiffOp::Delete, t)));
+    out.extend(b[j..].iter().map(|t| line(DiffOp::Insert, t)));
+    out
+}
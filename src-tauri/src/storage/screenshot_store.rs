@@ -2,6 +2,10 @@ use anyhow::Result;
 use chrono::Local;
 use std::path::PathBuf;
 
+use super::report_history::{self, ReportMeta};
+
+const SCREENSHOT_EXTENSIONS: [&str; 4] = ["jpg", "jpeg", "png", "webp"];
+
 pub struct ScreenshotStore {
     base_dir: PathBuf,
 }
@@ -32,12 +36,12 @@ impl ScreenshotStore {
         Ok(dir)
     }
 
-    pub fn save_screenshot(&self, jpeg_data: &[u8]) -> Result<PathBuf> {
+    pub fn save_screenshot(&self, data: &[u8], extension: &str) -> Result<PathBuf> {
         let dir = self.ensure_today_dir()?;
         let timestamp = Local::now().format("%Y%m%d_%H%M%S%.3f").to_string();
-        let filename = format!("screenshot_{}.jpg", timestamp);
+        let filename = format!("screenshot_{}.{}", timestamp, extension);
         let path = dir.join(&filename);
-        std::fs::write(&path, jpeg_data)?;
+        std::fs::write(&path, data)?;
         Ok(path)
     }
 
@@ -48,14 +52,22 @@ impl ScreenshotStore {
         Ok(())
     }
 
-    pub fn save_report_for_date(&self, markdown: &str, date: &str) -> Result<PathBuf> {
+    /// Save the report as a new version under `reports/` and make it the current
+    /// `report_file` (e.g. `report.md`). Earlier versions are kept.
+    pub fn save_report_for_date(
+        &self,
+        markdown: &str,
+        date: &str,
+        report_file: &str,
+        meta: ReportMeta,
+    ) -> Result<PathBuf> {
         let dir = self.ensure_date_dir(date)?;
-        let path = dir.join("report.md");
-        std::fs::write(&path, markdown)?;
-        Ok(path)
+        let version = report_history::save_version(&dir, report_file, markdown, meta)?;
+        log::info!("Saved {} version {} for {}", report_file, version.id, date);
+        Ok(dir.join(report_file))
     }
 
-    /// Delete all screenshot .jpg files in a date's folder.
+    /// Delete all screenshot images (.jpg, .png, .webp) in a date's folder.
     pub fn cleanup_screenshots_for_date(&self, date: &str) -> Result<u32> {
         let dir = self.date_dir(date);
         let mut deleted = 0u32;
@@ -63,7 +75,11 @@ impl ScreenshotStore {
             for entry in std::fs::read_dir(&dir)? {
                 let entry = entry?;
                 let path = entry.path();
-                if path.extension().map_or(false, |ext| ext == "jpg") {
+                if path
+                    .extension()
+                    .and_then(|ext| ext.to_str())
+                    .is_some_and(|ext| SCREENSHOT_EXTENSIONS.contains(&ext))
+                {
                     std::fs::remove_file(&path)?;
                     deleted += 1;
                 }
@@ -1,14 +1,65 @@
 import { invoke } from "@tauri-apps/api/core";
 import type { PetInfo } from "../sprites/types";
 
+export type ProjectRuleKind = "app" | "title_regex" | "repo_path" | "url_domain";
+
+export interface ProjectRule {
+  kind: ProjectRuleKind;
+  pattern: string;
+}
+
+export interface Project {
+  id: string;
+  name: string;
+  client: string;
+  rules: ProjectRule[];
+}
+
 export interface AppConfig {
   llm_provider: string;
   api_key: string;
   model: string;
   api_endpoint: string;
+  extraction_llm: LlmSettings;
+  digest_llm: LlmSettings;
+  qa_llm: LlmSettings;
+  llm_fallbacks: LlmSettings[];
+  llm_text_fallback: boolean;
+  offline_queue_budget_mb: number;
+  audit_log_enabled: boolean;
+  audit_store_prompts: boolean;
+  audit_retention_days: number;
+  redaction: RedactionSettings;
+  image_redaction: ImageRedactionSettings;
   screenshot_interval_secs: number;
+  capture_mode: string;
+  capture_thumbnail: boolean;
+  capture_width: number;
+  capture_height: number;
+  capture_filter: string;
+  image_format: string;
+  image_quality: number;
   batch_interval_secs: number;
+  browser_integration_enabled: boolean;
+  browser_integration_port: number;
+  excluded_domains: string[];
+  dev_context_enabled: boolean;
+  dev_context_apps: string[];
+  git_repos: string[];
+  git_author: string;
+  meeting_detection_enabled: boolean;
+  meeting_device_detection: boolean;
+  meeting_title_patterns: string[];
+  meeting_calendar_path: string;
   dedup_threshold: number;
+  keyframe_on_title_change: boolean;
+  keyframe_min_interval_secs: number;
+  keyframe_max_interval_secs: number;
+  keyframes_per_batch: number;
+  extraction_mode: string;
+  ocr_mode: string;
+  extract_template: string;
+  digest_template: string;
   data_dir: string;
   pet_name: string;
   pet_size: string;
@@ -20,6 +71,70 @@ export interface AppConfig {
   auto_start_monitoring_time_enabled: boolean;
   auto_start_monitoring_time: string;
   launch_at_startup: boolean;
+  projects: Project[];
+  project_llm_classification: boolean;
+  note_export_enabled: boolean;
+  note_export_vault_path: string;
+  note_export_filename_template: string;
+  note_export_mode: "overwrite" | "append_under_heading";
+  note_export_heading: string;
+  digest_profiles: DigestProfile[];
+  digest_token_threshold: number;
+  model_token_thresholds: ModelTokenThreshold[];
+}
+
+export interface RedactionRule {
+  label: string;
+  pattern: string;
+}
+
+export interface RedactionSettings {
+  enabled: boolean;
+  emails: boolean;
+  phone_numbers: boolean;
+  credit_cards: boolean;
+  api_keys: boolean;
+  rules: RedactionRule[];
+  words: string[];
+  restore_in_report: boolean;
+}
+
+export interface MaskRegion {
+  x: number;
+  y: number;
+  width: number;
+  height: number;
+}
+
+export interface ImageRedactionSettings {
+  enabled: boolean;
+  regions: MaskRegion[];
+  blur_apps: string[];
+  ocr: boolean;
+}
+
+export interface ModelTokenThreshold {
+  model: string;
+  max_tokens: number;
+}
+
+export interface LlmSettings {
+  llm_provider: string;
+  api_key: string;
+  model: string;
+  api_endpoint: string;
+}
+
+export interface DigestProfile {
+  name: string;
+  template: string;
+  llm_provider: string;
+  model: string;
+  api_key: string;
+  api_endpoint: string;
+  output_filename: string;
+  auto_report_enabled: boolean;
+  auto_report_time: string;
 }
 
 export async function startMonitoring(): Promise<void> {
@@ -30,8 +145,23 @@ export async function stopMonitoring(): Promise<void> {
   return invoke("stop_monitoring");
 }
 
-export async function generateDigest(date?: string): Promise<string> {
-  return invoke("generate_digest", { date });
+export async function generateDigest(date?: string, profile?: string): Promise<string> {
+  return invoke("generate_digest", { date, profile });
+}
+
+export interface JobInfo {
+  id: string;
+  kind: "digest" | "batch";
+  date: string;
+  started_at: string;
+}
+
+export async function listJobs(): Promise<JobInfo[]> {
+  return invoke("list_jobs");
+}
+
+export async function cancelJob(id: string): Promise<void> {
+  return invoke("cancel_job", { id });
 }
 
 export async function getConfig(): Promise<AppConfig> {
@@ -65,6 +195,7 @@ export async function removePet(name: string): Promise<void> {
 export interface DateInfo {
   date: string;
   has_report: boolean;
+  profiles: string[];
 }
 
 export async function listDataDates(): Promise<DateInfo[]> {
@@ -75,12 +206,71 @@ export async function listReports(): Promise<string[]> {
   return invoke("list_reports");
 }
 
-export async function readReport(date: string): Promise<string> {
-  return invoke("read_report", { date });
+export async function readReport(date: string, profile?: string): Promise<string> {
+  return invoke("read_report", { date, profile });
+}
+
+export async function openReportFile(date: string, profile?: string): Promise<void> {
+  return invoke("open_report_file", { date, profile });
+}
+
+export interface ReportVersion {
+  id: string;
+  created_at: string;
+  provider: string;
+  model: string;
+  prompt_hash: string;
+  prompt_tokens: number | null;
+  completion_tokens: number | null;
+  is_current: boolean;
+}
+
+export interface DiffLine {
+  op: "equal" | "insert" | "delete";
+  text: string;
+}
+
+export async function listReportVersions(
+  date: string,
+  profile?: string,
+): Promise<ReportVersion[]> {
+  return invoke("list_report_versions", { date, profile });
+}
+
+export async function readReportVersion(
+  date: string,
+  id: string,
+  profile?: string,
+): Promise<string> {
+  return invoke("read_report_version", { date, id, profile });
+}
+
+export async function diffReportVersions(
+  date: string,
+  fromId: string,
+  toId: string,
+  profile?: string,
+): Promise<DiffLine[]> {
+  return invoke("diff_report_versions", { date, fromId, toId, profile });
+}
+
+export async function promoteReportVersion(
+  date: string,
+  id: string,
+  profile?: string,
+): Promise<void> {
+  return invoke("promote_report_version", { date, id, profile });
 }
 
-export async function openReportFile(date: string): Promise<void> {
-  return invoke("open_report_file", { date });
+export type TimesheetFormat = "csv" | "ics" | "toggl" | "clockify";
+
+export async function exportTimesheet(
+  startDate: string,
+  endDate: string,
+  format: TimesheetFormat,
+  path: string,
+): Promise<number> {
+  return invoke("export_timesheet", { startDate, endDate, format, path });
 }
 
 export async function openPromptFile(): Promise<void> {
@@ -91,6 +281,57 @@ export async function openExtractPromptFile(): Promise<void> {
   return invoke("open_extract_prompt_file");
 }
 
+export type TemplateKind = "extract" | "digest";
+
+export interface PromptTemplate {
+  name: string;
+  kind: TemplateKind;
+  description: string;
+  path: string;
+}
+
+export interface PromptPreview {
+  template: string;
+  text: string;
+  errors: string[];
+  warnings: string[];
+}
+
+export async function listPromptTemplates(): Promise<PromptTemplate[]> {
+  return invoke("list_prompt_templates");
+}
+
+export async function renderPromptPreview(
+  kind: TemplateKind,
+  template?: string,
+  date?: string,
+): Promise<PromptPreview> {
+  return invoke("render_prompt_preview", { kind, template, date });
+}
+
+export async function openPromptsDir(): Promise<void> {
+  return invoke("open_prompts_dir");
+}
+
+export interface AuditEntry {
+  id: number;
+  timestamp: string;
+  provider: string;
+  endpoint: string;
+  model: string;
+  prompt_hash: string;
+  prompt_bytes: number;
+  prompt: string | null;
+  images: { hash: string; bytes: number }[];
+  response_bytes: number | null;
+  activity_ids: number[];
+  error: string | null;
+}
+
+export async function listLlmAudit(limit?: number, offset?: number): Promise<AuditEntry[]> {
+  return invoke("list_llm_audit", { limit, offset });
+}
+
 export async function savePetPosition(x: number, y: number): Promise<void> {
   return invoke("save_pet_position", { x, y });
 }
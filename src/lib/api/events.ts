@@ -2,8 +2,14 @@ import { listen } from "@tauri-apps/api/event";
 
 export interface MonitoringStatus {
   active: boolean;
-  screenshots_today: number;
-  last_batch_time: string | null;
+  // The LLM endpoint is unreachable and screenshots are being queued.
+  offline?: boolean;
+  last_batch_summary?: string;
+  // Screenshots waiting for a batch, and their size on disk.
+  queue_depth?: number;
+  queue_bytes?: number;
+  // Why the last batch failed, e.g. an invalid prompt template.
+  error?: string;
 }
 
 export interface ClaudeOutput {